@@ -0,0 +1,215 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+
+use failure::{Error, ResultExt};
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+
+use crate::checker::parse_anchors;
+use crate::link::normalize_path;
+use crate::snapshot::fnv1a;
+
+pub const INDEX_FILE: &str = ".check-links-anchors.json";
+
+/// Bumped whenever the way anchors are found changes, so that indexes saved by older
+/// versions are thrown out instead of giving different answers.
+const INDEX_VERSION: u32 = 3;
+
+/// The anchors in each local file, so that a file only has to be parsed once no matter
+/// how many links point into it. It can be saved between runs, in which case files are
+/// only parsed again when their content changes.
+pub struct AnchorIndex {
+    path: Option<PathBuf>,
+    files: Mutex<BTreeMap<PathBuf, IndexEntry>>,
+    /// The anchors of the files we've looked at in this run.
+    parsed: Mutex<BTreeMap<PathBuf, Arc<Anchors>>>,
+    /// Whether anything's been indexed since the index was loaded.
+    changed: AtomicBool,
+}
+
+#[derive(Clone, Deserialize, Serialize)]
+struct IndexEntry {
+    /// Hex encoded hash of the file's content when it was parsed.
+    hash: String,
+    anchors: Anchors,
+}
+
+/// The anchors in a file. The slugs generated from headings are matched ignoring case,
+/// like GitHub does, while ids given explicitly have to match exactly.
+#[derive(Clone, Default, Deserialize, Serialize)]
+pub struct Anchors {
+    /// Always lowercase.
+    slugs: BTreeSet<String>,
+    ids: BTreeSet<String>,
+}
+
+impl Anchors {
+    pub fn contains(&self, section: &str) -> bool {
+        self.ids.contains(section) || self.slugs.contains(&section.to_lowercase())
+    }
+}
+
+#[derive(Deserialize, Serialize)]
+struct IndexFile {
+    version: u32,
+    files: BTreeMap<PathBuf, IndexEntry>,
+}
+
+impl AnchorIndex {
+    /// An index that's only kept for this run.
+    pub fn in_memory() -> Self {
+        AnchorIndex {
+            path: None,
+            files: Mutex::new(BTreeMap::new()),
+            parsed: Mutex::new(BTreeMap::new()),
+            changed: AtomicBool::new(false),
+        }
+    }
+
+    /// Load a saved index, or start a new one if it doesn't exist yet or was saved by a
+    /// version that finds anchors differently.
+    pub fn load(path: &Path) -> Result<Self, Error> {
+        let mut files = BTreeMap::new();
+        if path.exists() {
+            let contents = fs::read_to_string(path)
+                .with_context(|_| format!("failed to read anchor index {}", path.display()))?;
+            // Older versions save anchors differently, so the version is checked before
+            // the rest is parsed.
+            let index: serde_json::Value = serde_json::from_str(&contents)
+                .with_context(|_| format!("failed to parse anchor index {}", path.display()))?;
+            if index["version"] == INDEX_VERSION {
+                let index: IndexFile = serde_json::from_value(index)
+                    .with_context(|_| format!("failed to parse anchor index {}", path.display()))?;
+                files = index.files;
+            }
+        }
+        Ok(AnchorIndex {
+            path: Some(path.to_path_buf()),
+            files: Mutex::new(files),
+            parsed: Mutex::new(BTreeMap::new()),
+            changed: AtomicBool::new(false),
+        })
+    }
+
+    /// Get the anchors in a file, parsing it if it's changed since it was indexed.
+    pub fn anchors(&self, file: &Path) -> Result<Arc<Anchors>, Error> {
+        let file = &normalize_path(file);
+        if let Some(anchors) = self.parsed.lock().unwrap().get(file) {
+            return Ok(anchors.clone());
+        }
+        let contents = fs::read_to_string(file)?;
+        let hash = format!("{:016x}", fnv1a(contents.as_bytes()));
+        let indexed = match self.files.lock().unwrap().get(file) {
+            Some(entry) if entry.hash == hash => Some(entry.anchors.clone()),
+            _ => None,
+        };
+        // The file is parsed without holding the lock, so other files can be looked up
+        // in the meantime.
+        let anchors = match indexed {
+            Some(anchors) => anchors,
+            None => {
+                let anchors = find_anchors(&contents);
+                self.changed.store(true, Ordering::Relaxed);
+                self.files.lock().unwrap().insert(
+                    file.to_path_buf(),
+                    IndexEntry {
+                        hash,
+                        anchors: anchors.clone(),
+                    },
+                );
+                anchors
+            }
+        };
+        let anchors = Arc::new(anchors);
+        self.parsed
+            .lock()
+            .unwrap()
+            .insert(file.to_path_buf(), anchors.clone());
+        Ok(anchors)
+    }
+
+    /// Save the index if it's kept between runs and has changed, dropping files that no
+    /// longer exist.
+    pub fn save(&self) -> Result<(), Error> {
+        let path = match &self.path {
+            Some(path) => path,
+            None => return Ok(()),
+        };
+        let mut files = self.files.lock().unwrap();
+        let n_files = files.len();
+        files.retain(|file, _| file.exists());
+        if files.len() == n_files && !self.changed.load(Ordering::Relaxed) {
+            return Ok(());
+        }
+        let index = IndexFile {
+            version: INDEX_VERSION,
+            files: files.clone(),
+        };
+        fs::write(path, serde_json::to_string_pretty(&index)?)
+            .with_context(|_| format!("failed to write anchor index {}", path.display()))?;
+        Ok(())
+    }
+}
+
+/// Find the anchors that headings and HTML elements in a document define.
+fn find_anchors(contents: &str) -> Anchors {
+    lazy_static! {
+        // Markdown headings, which may be in Rust doc comments, and Org headings.
+        static ref HEADING_RE: Regex =
+            Regex::new(r"^\s*(?://[/!]\s?)?(?:#{1,6}|\*+)\s+(.+?)\s*#*\s*$").unwrap();
+        // The underlines of Setext style Markdown headings.
+        static ref UNDERLINE_RE: Regex = Regex::new(r"^\s{0,3}(?:=+|-+)\s*$").unwrap();
+    }
+    let mut anchors = Anchors {
+        slugs: BTreeSet::new(),
+        ids: parse_anchors(contents).into_iter().collect(),
+    };
+    let mut previous = "";
+    for line in contents.lines() {
+        if let Some(caps) = HEADING_RE.captures(line) {
+            insert_heading(&mut anchors, &caps[1]);
+        } else if UNDERLINE_RE.is_match(line) && !previous.trim().is_empty() {
+            insert_heading(&mut anchors, previous.trim());
+        }
+        previous = line;
+    }
+    anchors
+}
+
+/// Add the anchors for a heading: its slug, and the id given explicitly with an
+/// attribute block like `## Title {#custom-id}` (or kramdown's `{: #custom-id}`), if
+/// there is one.
+fn insert_heading(anchors: &mut Anchors, heading: &str) {
+    lazy_static! {
+        static ref ATTRIBUTES_RE: Regex = Regex::new(r"\s*\{:?\s*#([^\s}]+)[^}]*\}\s*$").unwrap();
+    }
+    match ATTRIBUTES_RE.captures(heading) {
+        Some(caps) => {
+            anchors.ids.insert(caps[1].to_string());
+            anchors
+                .slugs
+                .insert(slugify(&heading[..caps.get(0).unwrap().start()]));
+        }
+        None => {
+            anchors.slugs.insert(slugify(heading));
+        }
+    }
+}
+
+/// Turn a heading into the anchor GitHub gives it: lowercase, with spaces turned into
+/// dashes and other punctuation dropped.
+fn slugify(heading: &str) -> String {
+    heading
+        .trim()
+        .to_lowercase()
+        .chars()
+        .filter_map(|c| match c {
+            ' ' => Some('-'),
+            c if c.is_alphanumeric() || c == '-' || c == '_' => Some(c),
+            _ => None,
+        })
+        .collect()
+}
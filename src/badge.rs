@@ -0,0 +1,86 @@
+use std::fs;
+use std::path::Path;
+
+use failure::{Error, ResultExt};
+
+use crate::maybe_pluralize;
+use crate::messages::Counts;
+
+const GREEN: &str = "#4c1";
+const YELLOW: &str = "#dfb317";
+const RED: &str = "#e05d44";
+const GREY: &str = "#555";
+
+/// Roughly how wide a string is in 11px Verdana, the font shields.io badges use. Badges
+/// are only ever a few words, so close is good enough.
+fn text_width(text: &str) -> u32 {
+    text.chars()
+        .map(|c| match c {
+            'i' | 'j' | 'l' | '.' | ',' | ':' | ';' | '!' | '|' | '\'' | ' ' => 4,
+            'f' | 'r' | 't' | '/' | '(' | ')' | '[' | ']' => 5,
+            'm' | 'w' | 'M' | 'W' => 10,
+            _ => 7,
+        })
+        .sum()
+}
+
+/// Render a badge in the style of shields.io with the results of a run, like
+/// "links | 532 ok / 3 broken".
+pub fn render(counts: &Counts) -> String {
+    let ok = counts.links - counts.errors - counts.warnings - counts.skipped - counts.ignored;
+    let mut message = format!("{} ok", ok);
+    if counts.errors > 0 {
+        message.push_str(&format!(" / {} broken", counts.errors));
+    }
+    if counts.warnings > 0 {
+        message.push_str(&format!(
+            " / {} warning{}",
+            counts.warnings,
+            maybe_pluralize(counts.warnings)
+        ));
+    }
+    let color = if counts.errors > 0 {
+        RED
+    } else if counts.warnings > 0 {
+        YELLOW
+    } else {
+        GREEN
+    };
+    let label = "links";
+    let label_width = text_width(label) + 10;
+    let message_width = text_width(&message) + 10;
+    let width = label_width + message_width;
+    format!(
+        r##"<svg xmlns="http://www.w3.org/2000/svg" width="{width}" height="20" role="img" aria-label="{label}: {message}">
+<title>{label}: {message}</title>
+<linearGradient id="s" x2="0" y2="100%"><stop offset="0" stop-color="#bbb" stop-opacity=".1"/><stop offset="1" stop-opacity=".1"/></linearGradient>
+<clipPath id="r"><rect width="{width}" height="20" rx="3" fill="#fff"/></clipPath>
+<g clip-path="url(#r)"><rect width="{label_width}" height="20" fill="{grey}"/><rect x="{label_width}" width="{message_width}" height="20" fill="{color}"/><rect width="{width}" height="20" fill="url(#s)"/></g>
+<g fill="#fff" text-anchor="middle" font-family="Verdana,Geneva,DejaVu Sans,sans-serif" font-size="11">
+<text x="{label_x}" y="15" fill="#010101" fill-opacity=".3">{label}</text><text x="{label_x}" y="14">{label}</text>
+<text x="{message_x}" y="15" fill="#010101" fill-opacity=".3">{message}</text><text x="{message_x}" y="14">{message}</text>
+</g>
+</svg>
+"##,
+        width = width,
+        label = label,
+        message = message,
+        label_width = label_width,
+        message_width = message_width,
+        grey = GREY,
+        color = color,
+        label_x = label_width as f32 / 2.0,
+        message_x = label_width as f32 + message_width as f32 / 2.0,
+    )
+}
+
+/// Write a badge with the results of a run, creating the directory it goes in if need be.
+pub fn write(path: &Path, counts: &Counts) -> Result<(), Error> {
+    if let Some(dir) = path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+        fs::create_dir_all(dir)
+            .with_context(|_| format!("failed to create directory {}", dir.display()))?;
+    }
+    fs::write(path, render(counts))
+        .with_context(|_| format!("failed to write badge to {}", path.display()))?;
+    Ok(())
+}
@@ -0,0 +1,198 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::str::FromStr;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use crate::maybe_pluralize;
+
+/// Who last changed a line, according to `git blame`.
+#[derive(Clone, Debug)]
+pub struct Blame {
+    pub commit: String,
+    pub author: String,
+    pub author_mail: String,
+    /// When the change was authored, in seconds since the Unix epoch.
+    pub time: u64,
+}
+
+impl Blame {
+    /// The abbreviated commit hash, or `None` if the line hasn't been committed yet.
+    pub fn short_commit(&self) -> Option<&str> {
+        if self.commit.bytes().all(|b| b == b'0') {
+            None
+        } else {
+            Some(&self.commit[..self.commit.len().min(8)])
+        }
+    }
+
+    /// Check if the line was changed within the given period. Lines that haven't been
+    /// committed yet count as just changed.
+    pub fn is_within(&self, period: Duration) -> bool {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs();
+        now.saturating_sub(self.time) < period.as_secs()
+    }
+}
+
+impl fmt::Display for Blame {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.short_commit() {
+            Some(commit) => write!(
+                f,
+                "added by {} {} in {}",
+                self.author, self.author_mail, commit
+            ),
+            None => write!(f, "not committed yet"),
+        }
+    }
+}
+
+/// Runs `git blame` on files as needed, remembering the results.
+#[derive(Default)]
+pub struct Blamer {
+    files: HashMap<PathBuf, Option<Vec<Blame>>>,
+}
+
+impl Blamer {
+    /// Get the blame for a line (1-based) of a file. Returns `None` if the file isn't
+    /// tracked by git or git isn't available.
+    pub fn blame(&mut self, file: &Path, lnum: usize) -> Option<Blame> {
+        let lines = self
+            .files
+            .entry(file.to_path_buf())
+            .or_insert_with(|| blame_file(file));
+        lines.as_ref()?.get(lnum.checked_sub(1)?).cloned()
+    }
+}
+
+fn blame_file(file: &Path) -> Option<Vec<Blame>> {
+    // Run git from the file's directory so that files in other repositories (like
+    // submodules) work too.
+    let dir = match file.parent() {
+        Some(dir) if dir != Path::new("") => dir,
+        _ => Path::new("."),
+    };
+    let output = Command::new("git")
+        .arg("blame")
+        .arg("--line-porcelain")
+        .arg("--")
+        .arg(file.file_name()?)
+        .current_dir(dir)
+        .output()
+        .ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    Some(parse_porcelain(&String::from_utf8_lossy(&output.stdout)))
+}
+
+/// Parse the output of `git blame --line-porcelain`, which gives a header for every line
+/// followed by the line itself prefixed by a tab.
+fn parse_porcelain(output: &str) -> Vec<Blame> {
+    let mut lines = vec![];
+    let mut commit = None;
+    let mut author = String::new();
+    let mut author_mail = String::new();
+    let mut time = 0;
+    for line in output.lines() {
+        if line.starts_with('\t') {
+            if let Some(commit) = commit.take() {
+                lines.push(Blame {
+                    commit,
+                    author: author.clone(),
+                    author_mail: author_mail.clone(),
+                    time,
+                });
+            }
+        } else if commit.is_none() {
+            commit = line.split(' ').next().map(String::from);
+        } else if let Some(value) = line.strip_prefix("author ") {
+            author = value.to_string();
+        } else if let Some(value) = line.strip_prefix("author-mail ") {
+            author_mail = value.to_string();
+        } else if let Some(value) = line.strip_prefix("author-time ") {
+            time = value.parse().unwrap_or(0);
+        }
+    }
+    lines
+}
+
+/// A period of time given like `7d`, `12h`, or `2w`. A plain number is a number of days.
+#[derive(Debug, Clone, Copy)]
+pub struct GracePeriod(pub Duration);
+
+impl FromStr for GracePeriod {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (n, unit) = match s.find(|c: char| !c.is_ascii_digit()) {
+            Some(i) => (&s[..i], &s[i..]),
+            None => (s, "d"),
+        };
+        let n: u64 = n
+            .parse()
+            .map_err(|_| format!("invalid period '{}', expected something like '7d'", s))?;
+        let seconds = match unit {
+            "m" => 60,
+            "h" => 60 * 60,
+            "d" => 24 * 60 * 60,
+            "w" => 7 * 24 * 60 * 60,
+            _ => return Err(format!("invalid unit '{}', expected m, h, d, or w", unit)),
+        };
+        Ok(GracePeriod(Duration::from_secs(n * seconds)))
+    }
+}
+
+impl fmt::Display for GracePeriod {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let secs = self.0.as_secs();
+        let (n, unit) = if secs.is_multiple_of(24 * 60 * 60) {
+            (secs / (24 * 60 * 60), "day")
+        } else if secs.is_multiple_of(60 * 60) {
+            (secs / (60 * 60), "hour")
+        } else {
+            (secs / 60, "minute")
+        };
+        write!(f, "{} {}{}", n, unit, maybe_pluralize(n))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn secs(s: &str) -> u64 {
+        s.parse::<GracePeriod>().unwrap().0.as_secs()
+    }
+
+    #[test]
+    fn parse() {
+        assert_eq!(secs("30m"), 30 * 60);
+        assert_eq!(secs("12h"), 12 * 60 * 60);
+        assert_eq!(secs("7d"), 7 * 24 * 60 * 60);
+        assert_eq!(secs("2w"), 14 * 24 * 60 * 60);
+        // A plain number is a number of days.
+        assert_eq!(secs("3"), 3 * 24 * 60 * 60);
+        assert!("7y".parse::<GracePeriod>().is_err());
+        assert!("d".parse::<GracePeriod>().is_err());
+        assert!("".parse::<GracePeriod>().is_err());
+    }
+
+    #[test]
+    fn display() {
+        assert_eq!("1d".parse::<GracePeriod>().unwrap().to_string(), "1 day");
+        assert_eq!("2w".parse::<GracePeriod>().unwrap().to_string(), "14 days");
+        assert_eq!(
+            "36h".parse::<GracePeriod>().unwrap().to_string(),
+            "36 hours"
+        );
+        assert_eq!(
+            "90m".parse::<GracePeriod>().unwrap().to_string(),
+            "90 minutes"
+        );
+    }
+}
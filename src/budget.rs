@@ -0,0 +1,112 @@
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::Duration;
+
+use serde_json::{json, Value};
+
+use crate::maybe_pluralize;
+
+/// How many hosts to list in the log, slowest first. The JSON report has all of them.
+const LOGGED_HOSTS: usize = 5;
+
+/// The time spent on each stage of a run, to see where it goes when tuning how a run is
+/// set up in CI.
+pub struct Budget {
+    /// Walking the directories and extracting links from files.
+    pub walk: Duration,
+    /// From the start of the walk, when the first links start being verified, until the
+    /// last one is done.
+    pub verify: Duration,
+    /// The HTTP requests sent to each host.
+    pub hosts: Vec<(String, HostTime)>,
+}
+
+/// The time spent waiting on the requests to a host. Requests are sent concurrently, so
+/// the total for a host can be more than the time the run takes.
+#[derive(Clone, Copy, Default)]
+pub struct HostTime {
+    pub requests: u32,
+    pub total: Duration,
+}
+
+/// Keeps track of the time spent on the requests to each host while links are verified.
+#[derive(Default)]
+pub struct HostTimes(Mutex<HashMap<String, HostTime>>);
+
+impl HostTimes {
+    pub fn record(&self, host: &str, elapsed: Duration) {
+        let mut hosts = self.0.lock().unwrap();
+        let time = hosts.entry(host.to_string()).or_default();
+        time.requests += 1;
+        time.total += elapsed;
+    }
+
+    /// Get the time spent on each host, slowest first.
+    pub fn slowest(&self) -> Vec<(String, HostTime)> {
+        let mut hosts: Vec<(String, HostTime)> = self
+            .0
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|(host, time)| (host.clone(), *time))
+            .collect();
+        hosts.sort_by(|a, b| b.1.total.cmp(&a.1.total).then_with(|| a.0.cmp(&b.0)));
+        hosts
+    }
+}
+
+impl Budget {
+    /// Describe where the time went, with a line for each stage and the slowest hosts.
+    pub fn lines(&self) -> Vec<String> {
+        let mut lines = vec![
+            format!("walking and extracting links: {}", seconds(self.walk)),
+            format!("verifying links (wall time): {}", seconds(self.verify)),
+        ];
+        for (host, time) in self.hosts.iter().take(LOGGED_HOSTS) {
+            lines.push(format!(
+                "{}: {} over {} request{} ({} each on average)",
+                host,
+                seconds(time.total),
+                time.requests,
+                maybe_pluralize(time.requests),
+                seconds(time.total / time.requests.max(1)),
+            ));
+        }
+        if self.hosts.len() > LOGGED_HOSTS {
+            lines.push(format!(
+                "and {} more host{}",
+                self.hosts.len() - LOGGED_HOSTS,
+                maybe_pluralize(self.hosts.len() - LOGGED_HOSTS)
+            ));
+        }
+        lines
+    }
+
+    pub fn to_json(&self) -> Value {
+        let hosts: Vec<Value> = self
+            .hosts
+            .iter()
+            .map(|(host, time)| {
+                json!({
+                    "host": host,
+                    "requests": time.requests,
+                    "seconds": time.total.as_secs_f64(),
+                })
+            })
+            .collect();
+        json!({
+            "walk_seconds": self.walk.as_secs_f64(),
+            "verify_seconds": self.verify.as_secs_f64(),
+            "hosts": hosts,
+        })
+    }
+}
+
+/// Format a duration for people to read, like `250ms` or `1.50s`.
+pub fn seconds(duration: Duration) -> String {
+    if duration < Duration::from_secs(1) {
+        format!("{}ms", duration.as_millis())
+    } else {
+        format!("{:.2}s", duration.as_secs_f64())
+    }
+}
@@ -0,0 +1,218 @@
+use std::collections::{BTreeMap, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use failure::{Error, ResultExt};
+use isahc::http::Uri;
+use serde::{Deserialize, Serialize};
+
+/// The results of checking HTTP links in previous runs, saved between runs.
+pub struct Cache {
+    path: PathBuf,
+    entries: Mutex<BTreeMap<String, CacheEntry>>,
+    /// The hosts that have refused HEAD requests, kept apart from the entries so they
+    /// can be looked up for every request.
+    head_not_allowed: Mutex<HashSet<String>>,
+    /// The hosts that were flaky in previous runs, worked out once when the cache is
+    /// loaded.
+    flaky_hosts: HashSet<String>,
+}
+
+/// What we know about a URL from previous runs.
+#[derive(Clone, Default, Deserialize, Serialize)]
+#[serde(default)]
+pub struct CacheEntry {
+    /// The number of times the URL has been checked.
+    pub checks: u32,
+    /// The number of those checks that failed.
+    pub failures: u32,
+    /// When the URL was last checked, in seconds since the Unix epoch.
+    pub last_checked: Option<u64>,
+    /// When the URL was last reachable, in seconds since the Unix epoch.
+    pub last_ok: Option<u64>,
+    /// The `ETag` of the last successful response, to send with conditional requests.
+    pub etag: Option<String>,
+    /// The `Last-Modified` date of the last successful response, for the same reason.
+    pub last_modified: Option<String>,
+    /// Whether the server answered a HEAD request with a 405 but a GET worked.
+    pub head_not_allowed: bool,
+}
+
+/// Get the host of a URL in the cache.
+fn host_of(url: &str) -> Option<String> {
+    url.parse::<Uri>().ok()?.host().map(String::from)
+}
+
+pub fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs()
+}
+
+impl Cache {
+    /// Load the cache file, or start a new one if it doesn't exist yet.
+    pub fn load(path: &Path) -> Result<Self, Error> {
+        let entries: BTreeMap<String, CacheEntry> = if path.exists() {
+            let contents = fs::read_to_string(path)
+                .with_context(|_| format!("failed to read cache {}", path.display()))?;
+            serde_json::from_str(&contents)
+                .with_context(|_| format!("failed to parse cache {}", path.display()))?
+        } else {
+            BTreeMap::new()
+        };
+        let head_not_allowed = entries
+            .iter()
+            .filter(|(_, entry)| entry.head_not_allowed)
+            .filter_map(|(url, _)| host_of(url))
+            .collect();
+        let flaky_hosts = entries
+            .iter()
+            .filter(|(_, entry)| entry.failures > 0 && entry.failures < entry.checks)
+            .filter_map(|(url, _)| host_of(url))
+            .collect();
+        Ok(Cache {
+            path: path.to_path_buf(),
+            entries: Mutex::new(entries),
+            head_not_allowed: Mutex::new(head_not_allowed),
+            flaky_hosts,
+        })
+    }
+
+    pub fn get(&self, url: &str) -> Option<CacheEntry> {
+        self.entries.lock().unwrap().get(url).cloned()
+    }
+
+    /// Get the URLs that failed the last time they were checked.
+    pub fn failing(&self) -> HashSet<String> {
+        self.entries
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|(_, entry)| {
+                entry.last_checked.is_some() && entry.last_ok != entry.last_checked
+            })
+            .map(|(url, _)| url.clone())
+            .collect()
+    }
+
+    /// Check if links to a host have been unreliable in previous runs, meaning that some
+    /// URL on the host has both failed and worked.
+    pub fn is_flaky_host(&self, host: &str) -> bool {
+        self.flaky_hosts.contains(host)
+    }
+
+    /// Check if a host has refused HEAD requests in previous runs.
+    pub fn is_head_not_allowed(&self, host: &str) -> bool {
+        self.head_not_allowed.lock().unwrap().contains(host)
+    }
+
+    /// Remember that a URL only works with GET requests.
+    pub fn record_head_not_allowed(&self, url: &str) {
+        let mut entries = self.entries.lock().unwrap();
+        entries.entry(url.to_string()).or_default().head_not_allowed = true;
+        if let Some(host) = host_of(url) {
+            self.head_not_allowed.lock().unwrap().insert(host);
+        }
+    }
+
+    /// Record the result of checking a URL.
+    pub fn record(&self, url: &str, ok: bool) {
+        let now = now();
+        let mut entries = self.entries.lock().unwrap();
+        let entry = entries.entry(url.to_string()).or_default();
+        entry.checks += 1;
+        entry.last_checked = Some(now);
+        if ok {
+            entry.last_ok = Some(now);
+        } else {
+            entry.failures += 1;
+        }
+    }
+
+    /// Remember the validators from a successful response so that the next check can
+    /// be a conditional request.
+    pub fn record_validators(
+        &self,
+        url: &str,
+        etag: Option<String>,
+        last_modified: Option<String>,
+    ) {
+        let mut entries = self.entries.lock().unwrap();
+        let entry = entries.entry(url.to_string()).or_default();
+        if etag.is_some() {
+            entry.etag = etag;
+        }
+        if last_modified.is_some() {
+            entry.last_modified = last_modified;
+        }
+    }
+
+    pub fn save(&self) -> Result<(), Error> {
+        let entries = self.entries.lock().unwrap();
+        let contents = serde_json::to_string_pretty(&*entries)?;
+        fs::write(&self.path, contents)
+            .with_context(|_| format!("failed to write cache {}", self.path.display()))?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::process;
+
+    use super::*;
+
+    /// Load a cache from a file with the given entries, as if from a previous run.
+    fn load(name: &str, entries: &[(&str, CacheEntry)]) -> Cache {
+        let path =
+            std::env::temp_dir().join(format!("check-links-{}-{}.json", name, process::id()));
+        let entries: BTreeMap<&str, &CacheEntry> = entries.iter().map(|(u, e)| (*u, e)).collect();
+        fs::write(&path, serde_json::to_string(&entries).unwrap()).unwrap();
+        let cache = Cache::load(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+        cache
+    }
+
+    #[test]
+    fn hosts_that_refused_head_requests_are_remembered() {
+        let refused = CacheEntry {
+            head_not_allowed: true,
+            ..CacheEntry::default()
+        };
+        let cache = load(
+            "head",
+            &[
+                ("https://a.io/x", refused),
+                ("https://b.io/x", CacheEntry::default()),
+            ],
+        );
+        assert!(cache.is_head_not_allowed("a.io"));
+        assert!(!cache.is_head_not_allowed("b.io"));
+        cache.record_head_not_allowed("https://b.io/y");
+        assert!(cache.is_head_not_allowed("b.io"));
+        assert!(cache.get("https://b.io/y").unwrap().head_not_allowed);
+    }
+
+    #[test]
+    fn hosts_are_flaky_if_a_url_has_failed_and_worked() {
+        let entry = |checks, failures| CacheEntry {
+            checks,
+            failures,
+            ..CacheEntry::default()
+        };
+        let cache = load(
+            "flaky",
+            &[
+                ("https://a.io/x", entry(3, 1)),
+                ("https://b.io/x", entry(3, 3)),
+                ("https://b.io/y", entry(3, 0)),
+            ],
+        );
+        assert!(cache.is_flaky_host("a.io"));
+        assert!(!cache.is_flaky_host("b.io"));
+        assert!(!cache.is_flaky_host("c.io"));
+    }
+}
@@ -0,0 +1,195 @@
+use std::collections::HashMap;
+
+use serde_json::{json, Value};
+
+use crate::link::{Link, LinkKind, LinkStatus, Reason, RequestError};
+use crate::messages::Lang;
+
+/// Roughly why a link failed, so that a run can be summed up as the network being down
+/// or as pages having gone missing.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Cause {
+    Dns,
+    Connection,
+    Timeout,
+    NotFound,
+    ServerError,
+    OtherStatus,
+    MissingAnchor,
+    MissingFile,
+    Other,
+}
+
+impl Cause {
+    /// Work out why a bad link failed. Returns `None` for links that didn't.
+    pub fn of(link: &Link) -> Option<Self> {
+        let reason = match link.status.as_ref()? {
+            LinkStatus::Questionable(reason) => Some(reason),
+            LinkStatus::Unreachable(reason) => reason.as_ref(),
+            _ => return None,
+        };
+        match link.request_error {
+            Some(RequestError::NameResolution) => return Some(Cause::Dns),
+            Some(RequestError::Connect) => return Some(Cause::Connection),
+            Some(RequestError::Timeout) => return Some(Cause::Timeout),
+            _ => {}
+        }
+        Some(match reason {
+            Some(reason) => Cause::of_reason(reason),
+            None if matches!(link.kind, LinkKind::Local) => Cause::MissingFile,
+            None => Cause::Other,
+        })
+    }
+
+    fn of_reason(reason: &Reason) -> Self {
+        match reason {
+            Reason::Status(404) | Reason::Status(410) => Cause::NotFound,
+            Reason::Status(500..=599) => Cause::ServerError,
+            Reason::Status(_) => Cause::OtherStatus,
+            Reason::Timeout => Cause::Timeout,
+            Reason::MissingSection { .. } => Cause::MissingAnchor,
+            Reason::NotInLocalDocs(_) => Cause::MissingFile,
+            Reason::Noted(reason, _) | Reason::Environment(_, reason) => Cause::of_reason(reason),
+            Reason::HostUnhealthy | Reason::Other(_) => Cause::Other,
+        }
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            Cause::Dns => "dns",
+            Cause::Connection => "connection",
+            Cause::Timeout => "timeout",
+            Cause::NotFound => "not-found",
+            Cause::ServerError => "server-error",
+            Cause::OtherStatus => "other-status",
+            Cause::MissingAnchor => "anchor-missing",
+            Cause::MissingFile => "local-missing",
+            Cause::Other => "other",
+        }
+    }
+}
+
+/// The number of bad links with each cause in a run.
+#[derive(Default)]
+pub struct Causes(HashMap<Cause, u32>);
+
+impl Causes {
+    pub fn add(&mut self, link: &Link) {
+        if let Some(cause) = Cause::of(link) {
+            *self.0.entry(cause).or_default() += 1;
+        }
+    }
+
+    /// The causes, most common first.
+    fn most_common(&self) -> Vec<(Cause, u32)> {
+        let mut causes: Vec<(Cause, u32)> = self.0.iter().map(|(c, n)| (*c, *n)).collect();
+        causes.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.name().cmp(b.0.name())));
+        causes
+    }
+
+    /// Describe the causes with a line for each, like "not found (404 or 410): 3".
+    pub fn lines(&self, lang: Lang) -> Vec<String> {
+        self.most_common()
+            .into_iter()
+            .map(|(cause, n)| format!("{}: {}", lang.cause(cause), n))
+            .collect()
+    }
+
+    pub fn to_json(&self) -> Value {
+        let causes: serde_json::Map<String, Value> = self
+            .most_common()
+            .into_iter()
+            .map(|(cause, n)| (cause.name().to_string(), json!(n)))
+            .collect();
+        Value::Object(causes)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn checked(raw: &str, status: LinkStatus) -> Link {
+        let mut link = Link::new("README.md".into(), 1, raw.to_string());
+        link.status = Some(status);
+        link
+    }
+
+    #[test]
+    fn good_and_skipped_links_have_no_cause() {
+        let good = checked("https://a.io", LinkStatus::Reachable);
+        let skipped = checked("https://a.io", LinkStatus::Skipped(Reason::unreachable()));
+        assert_eq!(Cause::of(&good), None);
+        assert_eq!(Cause::of(&skipped), None);
+        assert_eq!(
+            Cause::of(&Link::new("README.md".into(), 1, "a.md".into())),
+            None
+        );
+    }
+
+    #[test]
+    fn causes_come_from_status_codes() {
+        let cause = |code| {
+            Cause::of(&checked(
+                "https://a.io",
+                LinkStatus::Unreachable(Some(Reason::Status(code))),
+            ))
+        };
+        assert_eq!(cause(404), Some(Cause::NotFound));
+        assert_eq!(cause(410), Some(Cause::NotFound));
+        assert_eq!(cause(503), Some(Cause::ServerError));
+        assert_eq!(cause(403), Some(Cause::OtherStatus));
+    }
+
+    #[test]
+    fn request_errors_come_before_reasons() {
+        let mut link = checked(
+            "https://a.io",
+            LinkStatus::Unreachable(Some(Reason::Timeout)),
+        );
+        link.request_error = Some(RequestError::NameResolution);
+        assert_eq!(Cause::of(&link), Some(Cause::Dns));
+        link.request_error = Some(RequestError::Other);
+        assert_eq!(Cause::of(&link), Some(Cause::Timeout));
+    }
+
+    #[test]
+    fn noted_reasons_have_the_cause_of_what_they_note() {
+        let reason = Reason::Environment(
+            String::from("staging"),
+            Box::new(Reason::Noted(
+                Box::new(Reason::Status(404)),
+                String::from("x"),
+            )),
+        );
+        let link = checked("https://a.io", LinkStatus::Questionable(reason));
+        assert_eq!(Cause::of(&link), Some(Cause::NotFound));
+    }
+
+    #[test]
+    fn local_links_without_a_reason_are_missing_files() {
+        let local = checked("docs/a.md", LinkStatus::Unreachable(None));
+        let remote = checked("https://a.io", LinkStatus::Unreachable(None));
+        assert_eq!(Cause::of(&local), Some(Cause::MissingFile));
+        assert_eq!(Cause::of(&remote), Some(Cause::Other));
+    }
+
+    #[test]
+    fn most_common_causes_come_first() {
+        let mut causes = Causes::default();
+        for code in &[500, 404, 404, 403, 502] {
+            causes.add(&checked(
+                "https://a.io",
+                LinkStatus::Unreachable(Some(Reason::Status(*code))),
+            ));
+        }
+        assert_eq!(
+            causes.most_common(),
+            vec![
+                (Cause::NotFound, 2),
+                (Cause::ServerError, 2),
+                (Cause::OtherStatus, 1)
+            ]
+        );
+    }
+}
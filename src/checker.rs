@@ -0,0 +1,364 @@
+use std::collections::{HashMap, HashSet};
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use isahc::http::Uri;
+use isahc::AsyncReadResponseExt;
+use regex::Regex;
+use tokio::sync::{Mutex as AsyncMutex, Semaphore, SemaphorePermit};
+
+use crate::anchors::AnchorIndex;
+use crate::budget::HostTimes;
+use crate::cache::Cache;
+use crate::crates_io::{self, CratesIo};
+use crate::github::GitHubLinks;
+use crate::includes::Includes;
+use crate::link::normalize_route;
+use crate::lint::Severity;
+use crate::snapshot::Snapshot;
+use crate::status_filter::StatusPattern;
+use crate::submodules::Submodules;
+
+/// State shared between all of the tasks verifying links.
+pub struct Checker {
+    pub http_client: isahc::HttpClient,
+    pub max_retry_wait: Duration,
+    /// Limits the number of HTTP requests in flight at once.
+    pub requests: Semaphore,
+    /// Number of consecutive failures after which we stop sending requests to a host.
+    /// A value of 0 disables this.
+    host_failure_threshold: usize,
+    /// Number of consecutive failures for each host.
+    host_failures: Mutex<HashMap<String, usize>>,
+    /// The severity of embedding insecure resources in HTML documents, if that's checked.
+    pub mixed_content: Option<Severity>,
+    /// The severity of `javascript:` links, if they're forbidden.
+    pub javascript: Option<Severity>,
+    /// The severity of hostnames that could be passing themselves off as others, if
+    /// that's checked.
+    pub homographs: Option<Severity>,
+    /// The severity of link text that's used for links to different places in one
+    /// document, if that's checked.
+    pub duplicate_text: Option<Severity>,
+    /// The severity of protocol-relative URLs, if that's checked.
+    pub protocol_relative: Option<Severity>,
+    /// The scheme to check protocol-relative URLs with.
+    pub protocol_relative_scheme: String,
+    /// Hosts that have answered a HEAD request with a 405, so we send them GET requests.
+    get_hosts: Mutex<HashSet<String>>,
+    /// Whether or not each host we've asked about uses HSTS.
+    hsts: Mutex<HashMap<String, bool>>,
+    /// Whether to verify the hash routes of single page apps, like `/#/guide/intro`.
+    pub hash_routes: bool,
+    /// The sitemap listing the routes of single page apps. Defaults to the
+    /// `/sitemap.xml` of each link's host.
+    pub sitemap: Option<String>,
+    /// The routes we've found in each sitemap. Each sitemap gets its own lock, like
+    /// pages do.
+    sitemaps: Mutex<HashMap<String, SitemapCell>>,
+    /// Whether to check that the fragments of HTTP links exist on the page.
+    pub remote_anchors: bool,
+    /// The pages we've fetched. Each page gets its own lock so that pages can be fetched
+    /// concurrently, but each one is only fetched once.
+    pages: Mutex<HashMap<String, PageCell>>,
+    /// Hashes of page content to compare against, if we're keeping a snapshot.
+    pub snapshot: Option<Snapshot>,
+    /// Results from previous runs, if we're keeping them.
+    pub cache: Option<Cache>,
+    /// Whether to race a HEAD and a GET request for links to hosts that have been flaky
+    /// in previous runs.
+    pub race_flaky: bool,
+    /// The submodules of the repository we're in, if it has any.
+    pub submodules: Option<Arc<Submodules>>,
+    /// The anchors in local files.
+    pub anchors: AnchorIndex,
+    /// The chapters that Markdown fragments are included in.
+    pub includes: Includes,
+    /// Where Git LFS keeps the objects it's fetched, if we're in a git repository.
+    pub lfs_objects: Option<PathBuf>,
+    /// Whether to assess how likely each HTTP link is to break in the future.
+    pub risk_scores: bool,
+    /// The ways links can fail that shouldn't count against them.
+    pub ignore_status: Vec<StatusPattern>,
+    /// The time spent on the requests to each host.
+    pub host_times: HostTimes,
+    /// Sends the requests for links to crates.io, which has a stricter crawler policy
+    /// than most hosts.
+    pub crates_io: Option<CratesIo>,
+    /// Looks up links to github.com through the API, if we're using it.
+    pub github: Option<GitHubLinks>,
+    /// Whether links to github.com are settled by the API rather than over HTTP.
+    pub github_api: bool,
+    /// Whether to warn about links to issues closed as not planned, and to pull requests
+    /// closed without being merged.
+    pub tracker_state: bool,
+    /// The only hosts external links may be checked on, if they're limited, like in
+    /// air-gapped CI where nothing else can be reached.
+    pub allowed_hosts: Option<Vec<String>>,
+}
+
+/// Holds a page once it's been fetched, or `None` inside if it couldn't be.
+type PageCell = Arc<AsyncMutex<Option<Option<Arc<Page>>>>>;
+
+/// Holds the routes in a sitemap once it's been fetched, or `None` inside if it
+/// couldn't be.
+type SitemapCell = Arc<AsyncMutex<Option<Option<Arc<HashSet<String>>>>>>;
+
+/// The contents of a page that we fetched.
+pub struct Page {
+    pub body: String,
+    /// The element ids and anchor names on the page.
+    pub anchors: HashSet<String>,
+}
+
+impl Checker {
+    pub fn new(
+        http_client: isahc::HttpClient,
+        max_retry_wait: Duration,
+        concurrency: usize,
+        host_failure_threshold: usize,
+    ) -> Self {
+        Checker {
+            http_client,
+            max_retry_wait,
+            requests: Semaphore::new(concurrency),
+            host_failure_threshold,
+            host_failures: Mutex::new(HashMap::new()),
+            mixed_content: None,
+            javascript: None,
+            homographs: None,
+            duplicate_text: None,
+            protocol_relative: None,
+            protocol_relative_scheme: String::from("https"),
+            get_hosts: Mutex::new(HashSet::new()),
+            hsts: Mutex::new(HashMap::new()),
+            hash_routes: false,
+            sitemap: None,
+            sitemaps: Mutex::new(HashMap::new()),
+            remote_anchors: false,
+            pages: Mutex::new(HashMap::new()),
+            snapshot: None,
+            cache: None,
+            race_flaky: false,
+            anchors: AnchorIndex::in_memory(),
+            submodules: None,
+            lfs_objects: None,
+            includes: Includes::default(),
+            risk_scores: false,
+            ignore_status: vec![],
+            host_times: HostTimes::default(),
+            crates_io: None,
+            github: None,
+            allowed_hosts: None,
+            github_api: false,
+            tracker_state: false,
+        }
+    }
+
+    /// Check if links to a host may be checked. Allowed hosts like `*.example.com` allow
+    /// any subdomain.
+    pub fn is_host_allowed(&self, host: Option<&str>) -> bool {
+        let allowed_hosts = match &self.allowed_hosts {
+            Some(allowed_hosts) => allowed_hosts,
+            None => return true,
+        };
+        let host = match host {
+            Some(host) => host.to_ascii_lowercase(),
+            None => return false,
+        };
+        allowed_hosts.iter().any(|allowed| {
+            let allowed = allowed.to_ascii_lowercase();
+            match allowed.strip_prefix("*.") {
+                Some(domain) => host.ends_with(&format!(".{}", domain)),
+                None => host == allowed,
+            }
+        })
+    }
+
+    /// Check if a host has failed too many times in a row to keep trying it.
+    pub fn is_host_unhealthy(&self, host: &str) -> bool {
+        if self.host_failure_threshold == 0 {
+            return false;
+        }
+        let host_failures = self.host_failures.lock().unwrap();
+        match host_failures.get(host) {
+            Some(n) => *n >= self.host_failure_threshold,
+            None => false,
+        }
+    }
+
+    /// Record whether or not we were able to get any response from a host.
+    pub fn record_host_result(&self, host: &str, ok: bool) {
+        let mut host_failures = self.host_failures.lock().unwrap();
+        if ok {
+            host_failures.remove(host);
+        } else {
+            *host_failures.entry(host.to_string()).or_insert(0) += 1;
+        }
+    }
+
+    /// Check if we should skip straight to a GET request for a host, because it's
+    /// refused HEAD requests in this run or a previous one.
+    pub fn prefers_get(&self, host: &str) -> bool {
+        self.get_hosts.lock().unwrap().contains(host)
+            || self
+                .cache
+                .as_ref()
+                .is_some_and(|cache| cache.is_head_not_allowed(host))
+    }
+
+    /// Remember that a host refuses HEAD requests, but a GET for `url` worked.
+    pub fn record_prefers_get(&self, host: &str, url: &str) {
+        self.get_hosts.lock().unwrap().insert(host.to_string());
+        if let Some(cache) = &self.cache {
+            cache.record_head_not_allowed(url);
+        }
+    }
+
+    /// Wait for a turn to send a request to a host, and get the client to send it with.
+    /// Requests to crates.io wait their turn on their own, rather than taking up a place
+    /// that a request to another host could have.
+    async fn client_for(
+        &self,
+        host: Option<&str>,
+    ) -> (Option<SemaphorePermit<'_>>, &isahc::HttpClient) {
+        match &self.crates_io {
+            Some(crates_io) if host.is_some_and(crates_io::is_crates_io) => {
+                crates_io.throttle().await;
+                (None, &crates_io.http_client)
+            }
+            _ => (Some(self.requests.acquire().await), &self.http_client),
+        }
+    }
+
+    /// Check if a host sends a `Strict-Transport-Security` header over HTTPS.
+    pub async fn has_hsts(&self, host: &str) -> bool {
+        if let Some(hsts) = self.hsts.lock().unwrap().get(host) {
+            return *hsts;
+        }
+        let hsts = {
+            let (_permit, http_client) = self.client_for(Some(host)).await;
+            match http_client
+                .head_async(&format!("https://{}/", host)[..])
+                .await
+            {
+                Ok(response) => response.headers().contains_key("strict-transport-security"),
+                Err(_) => false,
+            }
+        };
+        self.hsts.lock().unwrap().insert(host.to_string(), hsts);
+        hsts
+    }
+
+    /// Get the set of routes listed in a sitemap. The sitemap can either be XML or plain
+    /// text with one URL per line.
+    pub async fn sitemap_routes(&self, url: &str) -> Option<Arc<HashSet<String>>> {
+        let cell = self
+            .sitemaps
+            .lock()
+            .unwrap()
+            .entry(url.to_string())
+            .or_insert_with(|| Arc::new(AsyncMutex::new(None)))
+            .clone();
+        // We hold the sitemap's lock while fetching so that it's only fetched once.
+        let mut routes = cell.lock().await;
+        if let Some(routes) = &*routes {
+            return routes.clone();
+        }
+        let fetched = {
+            let (_permit, http_client) = self.client_for(host_of(url).as_deref()).await;
+            match http_client.get_async(url).await {
+                Ok(mut response) if response.status().is_success() => match response.text().await {
+                    Ok(body) => Some(Arc::new(parse_sitemap(&body))),
+                    Err(_) => None,
+                },
+                _ => None,
+            }
+        };
+        *routes = Some(fetched.clone());
+        fetched
+    }
+
+    /// Fetch a page, or get it from the ones we've already fetched. Returns `None` if the
+    /// page couldn't be fetched.
+    pub async fn page(&self, url: &str) -> Option<Arc<Page>> {
+        let cell = self
+            .pages
+            .lock()
+            .unwrap()
+            .entry(url.to_string())
+            .or_insert_with(|| Arc::new(AsyncMutex::new(None)))
+            .clone();
+        let mut page = cell.lock().await;
+        if let Some(page) = &*page {
+            return page.clone();
+        }
+        let fetched = {
+            let (_permit, http_client) = self.client_for(host_of(url).as_deref()).await;
+            match http_client.get_async(url).await {
+                Ok(mut response) if response.status().is_success() => match response.text().await {
+                    Ok(body) => {
+                        let anchors = parse_anchors(&body);
+                        Some(Arc::new(Page { body, anchors }))
+                    }
+                    Err(_) => None,
+                },
+                _ => None,
+            }
+        };
+        *page = Some(fetched.clone());
+        fetched
+    }
+}
+
+fn host_of(url: &str) -> Option<String> {
+    url.parse::<Uri>().ok()?.host().map(String::from)
+}
+
+pub fn parse_anchors(body: &str) -> HashSet<String> {
+    lazy_static! {
+        static ref ANCHOR_RE: Regex =
+            Regex::new(r#"\s(?:id|name)\s*=\s*(?:["']([^"']+)["']|([^\s"'<>=`]+))"#).unwrap();
+    }
+    let mut anchors = HashSet::new();
+    for caps in ANCHOR_RE.captures_iter(body) {
+        // The value may be quoted, or not if it doesn't have spaces in it.
+        let anchor = caps.get(1).or_else(|| caps.get(2)).unwrap().as_str();
+        // GitHub prefixes the ids of headings in rendered Markdown, but links to them
+        // use the plain id.
+        if let Some(stripped) = anchor.strip_prefix("user-content-") {
+            anchors.insert(stripped.to_string());
+        }
+        anchors.insert(anchor.to_string());
+    }
+    anchors
+}
+
+fn parse_sitemap(body: &str) -> HashSet<String> {
+    lazy_static! {
+        static ref LOC_RE: Regex = Regex::new(r"<loc>\s*([^<\s]+)\s*</loc>").unwrap();
+    }
+    let urls: Vec<&str> = if body.contains("<loc>") {
+        LOC_RE
+            .captures_iter(body)
+            .map(|caps| caps.get(1).unwrap().as_str())
+            .collect()
+    } else {
+        body.lines()
+            .map(|line| line.trim())
+            .filter(|line| !line.is_empty())
+            .collect()
+    };
+    urls.into_iter()
+        .map(|url| match url.find("#/") {
+            // The sitemap may list hash routes themselves...
+            Some(i) => normalize_route(&url[i + 1..]),
+            // ...or the equivalent paths.
+            None => match url.parse::<Uri>() {
+                Ok(uri) => normalize_route(uri.path()),
+                Err(_) => normalize_route(url),
+            },
+        })
+        .collect()
+}
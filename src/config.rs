@@ -0,0 +1,540 @@
+use std::borrow::Cow;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::fs;
+use std::path::{Component, Path, PathBuf};
+use std::str::FromStr;
+use std::time::Duration;
+
+use failure::{Error, ResultExt};
+use globset::{GlobBuilder, GlobMatcher};
+use isahc::http::Uri;
+use regex::Regex;
+use serde::de::{self, Deserializer};
+use serde::{Deserialize, Serialize, Serializer};
+
+use crate::ignores::{Date, Ignore};
+use crate::link::normalize_path;
+use crate::lint::Severity;
+use crate::maybe_pluralize;
+
+/// The name of the config file we look for in the current directory when one isn't
+/// given explicitly.
+pub const DEFAULT_CONFIG_FILE: &str = "check-links.toml";
+
+/// Settings read from a TOML config file.
+#[derive(Debug, Default, Deserialize, Serialize)]
+#[serde(default)]
+pub struct Config {
+    /// Globs for files to skip, relative to the directory being checked.
+    pub exclude: Vec<String>,
+    /// Severities for lints, which take precedence over the command line.
+    pub lints: Lints,
+    /// How to resolve links in docs translated into several languages.
+    pub i18n: I18n,
+    /// Assertions about the content of the pages that links point to.
+    #[serde(rename = "assert")]
+    pub assertions: Vec<ContentAssertion>,
+    /// Rules for the response headers of the pages that links point to.
+    #[serde(rename = "header")]
+    pub header_rules: Vec<HeaderRule>,
+    /// The `Accept` header to send for matching URLs, for servers that answer the
+    /// default one with a 406.
+    pub accept: Vec<AcceptRule>,
+    /// URL prefixes of published docs mapped to the directories they're built from, so
+    /// links to them can be checked against the source tree.
+    pub local_urls: BTreeMap<String, PathBuf>,
+    /// The base URLs of each environment the docs are deployed to, like staging and
+    /// production. A link to one is checked in all of them.
+    pub environments: BTreeMap<String, String>,
+    /// Hosts, or hosts and paths, that docs mustn't link to at all, like URL shorteners
+    /// and internal hosts.
+    pub forbid: Vec<UrlGlob>,
+    /// Service levels for our own hosts, by hostname, so that nightly runs can warn
+    /// about them being slow.
+    pub sla: BTreeMap<String, Sla>,
+    /// Hosts of URL shorteners to follow to where they go, besides the well-known ones.
+    pub shorteners: Vec<String>,
+    /// Hosts of self-hosted GitLab instances that GITLAB_TOKEN may be sent to, besides
+    /// gitlab.com and the one CI_SERVER_URL points to.
+    pub gitlab_hosts: Vec<String>,
+    /// Links to ignore rather than check, maybe only until some date.
+    #[serde(rename = "ignore")]
+    pub ignores: Vec<IgnoreRule>,
+    /// Changes to make to links before checking them, so the URL checked is the one
+    /// readers end up at, like a versioned page rather than `/latest/`.
+    #[serde(rename = "rewrite")]
+    pub rewrites: Vec<RewriteRule>,
+}
+
+#[derive(Debug, Default, Deserialize, Serialize)]
+#[serde(default)]
+pub struct Lints {
+    pub mixed_content: Option<Severity>,
+    pub protocol_relative: Option<Severity>,
+    pub javascript: Option<Severity>,
+    pub homographs: Option<Severity>,
+    pub duplicate_text: Option<Severity>,
+}
+
+/// Settings for docs trees with a directory per locale, like `docs/en` and `docs/fr`.
+#[derive(Debug, Default, Deserialize, Serialize)]
+#[serde(default)]
+pub struct I18n {
+    /// The locale to look in when a page hasn't been translated yet. Locale-aware
+    /// resolution is off unless this is set.
+    pub fallback: Option<String>,
+    /// The names of the locale directories. Defaults to any directory named like a
+    /// locale, such as `fr` or `pt-BR`.
+    pub locales: Vec<String>,
+}
+
+impl I18n {
+    fn is_locale(&self, name: &str) -> bool {
+        lazy_static! {
+            static ref LOCALE_RE: Regex = Regex::new(r"^[a-z]{2}([-_][A-Za-z]{2,4})?$").unwrap();
+        }
+        if self.locales.is_empty() {
+            LOCALE_RE.is_match(name)
+        } else {
+            self.locales.iter().any(|locale| locale == name)
+        }
+    }
+
+    /// Get the path of the fallback locale's version of a file in another locale, if
+    /// there is a fallback locale and the path is in a locale directory.
+    pub fn fallback_path(&self, path: &Path) -> Option<PathBuf> {
+        let fallback = self.fallback.as_ref()?;
+        // Resolve `..` first so that links between locales map to the right directory.
+        let path = normalize_path(path);
+        let components: Vec<Component> = path.components().collect();
+        let i = components.iter().rposition(|component| match component {
+            Component::Normal(name) => name
+                .to_str()
+                .is_some_and(|name| name != fallback && self.is_locale(name)),
+            _ => false,
+        })?;
+        let mut fallback_path = PathBuf::new();
+        for (j, component) in components.iter().enumerate() {
+            if i == j {
+                fallback_path.push(fallback);
+            } else {
+                fallback_path.push(component);
+            }
+        }
+        Some(fallback_path)
+    }
+}
+
+impl Config {
+    /// Get the name and base URL of the environment a URL is in, if any.
+    pub fn environment(&self, url: &str) -> Option<(&str, &str)> {
+        self.environments
+            .iter()
+            .filter(|(_, base)| url.starts_with(&base[..]))
+            .max_by_key(|(_, base)| base.len())
+            .map(|(name, base)| (&name[..], &base[..]))
+    }
+
+    /// Get the longest a host should take to respond, if it has an SLA.
+    pub fn max_latency(&self, host: &str) -> Option<Duration> {
+        self.sla
+            .iter()
+            .find(|(sla_host, _)| sla_host.eq_ignore_ascii_case(host))
+            .and_then(|(_, sla)| sla.max_latency)
+            .map(|latency| latency.0)
+    }
+
+    /// Get the pattern a URL is forbidden by, if any.
+    pub fn forbidden(&self, url: &str) -> Option<&UrlGlob> {
+        let uri = url.parse::<Uri>().ok()?;
+        let host = uri.host()?.to_lowercase();
+        self.forbid
+            .iter()
+            .find(|glob| glob.is_match(&host, uri.path()))
+    }
+
+    /// Get how a link is ignored, from the first rule that matches it.
+    pub fn ignore(&self, url: &str) -> Option<Ignore> {
+        self.ignores
+            .iter()
+            .find(|rule| rule.url.is_match(url))
+            .map(|rule| Ignore {
+                until: rule.until,
+                reason: rule.reason.clone(),
+            })
+    }
+
+    /// Get the URL to check for a link, if any of the rewrite rules change it. Each
+    /// matching rule applies in turn, to what the ones before it left.
+    pub fn rewrite(&self, url: &str) -> Option<String> {
+        let mut rewritten = url.to_string();
+        for rule in &self.rewrites {
+            rewritten = rule
+                .url
+                .replace_all(&rewritten, &rule.replacement)
+                .into_owned();
+        }
+        if rewritten != url {
+            Some(rewritten)
+        } else {
+            None
+        }
+    }
+
+    /// Get the `Accept` header to send for a URL, from the first rule that matches it.
+    pub fn accept(&self, url: &str) -> Option<&str> {
+        self.accept
+            .iter()
+            .find(|rule| rule.url.is_match(url))
+            .map(|rule| &rule.value[..])
+    }
+
+    /// Get the local files a URL could be published from, in the order to try them, if
+    /// it's under a prefix mapped to a local directory. The longest matching prefix wins.
+    pub fn local_paths(&self, url: &str) -> Option<Vec<PathBuf>> {
+        let (prefix, dir) = self
+            .local_urls
+            .iter()
+            .filter(|(prefix, _)| url.starts_with(&prefix[..]))
+            .max_by_key(|(prefix, _)| prefix.len())?;
+        let rest = &url[prefix.len()..];
+        let rest = rest.split(['?', '#']).next().unwrap_or_default();
+        let rest = rest.trim_start_matches('/');
+        let path = dir.join(rest);
+        // Pages are often published without their extension, or as a directory.
+        let mut paths = vec![];
+        if !rest.is_empty() && !rest.ends_with('/') {
+            paths.push(path.clone());
+            for ext in &["md", "html"] {
+                paths.push(PathBuf::from(format!("{}.{}", path.display(), ext)));
+            }
+        }
+        for index in &["index.md", "README.md", "index.html"] {
+            paths.push(path.join(index));
+        }
+        Some(paths)
+    }
+
+    /// Load the config file at `path` if given, otherwise the default config file in `dir`
+    /// if there is one, otherwise `None`.
+    pub fn find(path: Option<&Path>, dir: &Path) -> Result<Option<Self>, Error> {
+        match path {
+            Some(path) => Ok(Some(Config::load(path)?)),
+            None => {
+                let path = dir.join(DEFAULT_CONFIG_FILE);
+                if path.exists() {
+                    Ok(Some(Config::load(&path)?))
+                } else {
+                    Ok(None)
+                }
+            }
+        }
+    }
+
+    pub fn load(path: &Path) -> Result<Self, Error> {
+        let contents = fs::read_to_string(path)
+            .with_context(|_| format!("failed to read config file {}", path.display()))?;
+        let mut unknown = vec![];
+        let config = serde_ignored::deserialize(toml::Deserializer::new(&contents), |key| {
+            unknown.push(key.to_string())
+        })
+        .with_context(|_| format!("failed to parse config file {}", path.display()))?;
+        if !unknown.is_empty() {
+            return Err(failure::err_msg(format!(
+                "unknown key{} {} in config file {} (run 'check-links config validate' for details)",
+                maybe_pluralize(unknown.len()),
+                unknown.join(", "),
+                path.display()
+            )));
+        }
+        Ok(config)
+    }
+}
+
+/// Requires that the pages at matching URLs contain a string or match a regular expression.
+#[derive(Debug, Deserialize, Serialize)]
+pub struct ContentAssertion {
+    /// Pattern for the URLs this applies to.
+    pub url: Pattern,
+    /// A string the page must contain.
+    pub contains: Option<String>,
+    /// A pattern the page must match.
+    pub matches: Option<Pattern>,
+}
+
+impl ContentAssertion {
+    /// Check the body of a page, returning a description of what went wrong if it
+    /// doesn't pass.
+    pub fn check(&self, body: &str) -> Result<(), String> {
+        if let Some(contains) = &self.contains {
+            if !body.contains(&contains[..]) {
+                return Err(format!("page does not contain \"{}\"", contains));
+            }
+        }
+        if let Some(matches) = &self.matches {
+            if !matches.is_match(body) {
+                return Err(format!("page does not match /{}/", matches));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Requires that the responses for matching URLs send a header, optionally with a value
+/// matching a pattern, or that they don't send it at all.
+#[derive(Debug, Deserialize, Serialize)]
+pub struct HeaderRule {
+    /// Pattern for the URLs this applies to.
+    pub url: Pattern,
+    /// The name of the header, which is case-insensitive.
+    pub name: String,
+    /// A pattern the header's value must match.
+    pub matches: Option<Pattern>,
+    /// Whether the header must not be sent at all.
+    #[serde(default)]
+    pub absent: bool,
+}
+
+impl HeaderRule {
+    /// Check the value of the header in a response, returning a description of what went
+    /// wrong if it doesn't pass.
+    pub fn check(&self, value: Option<&str>) -> Result<(), String> {
+        match (value, self.absent) {
+            (Some(value), true) => Err(format!(
+                "response has header {}: {} but shouldn't",
+                self.name, value
+            )),
+            (None, false) => Err(format!("response is missing header {}", self.name)),
+            (Some(value), false) => match &self.matches {
+                Some(matches) if !matches.is_match(value) => Err(format!(
+                    "response header {}: {} does not match /{}/",
+                    self.name, value, matches
+                )),
+                _ => Ok(()),
+            },
+            (None, true) => Ok(()),
+        }
+    }
+}
+
+/// The `Accept` header to send in requests for matching URLs.
+#[derive(Debug, Deserialize, Serialize)]
+pub struct AcceptRule {
+    /// Pattern for the URLs this applies to.
+    pub url: Pattern,
+    /// The value of the header, like `application/json`.
+    pub value: String,
+}
+
+/// The service level a host should meet.
+#[derive(Debug, Default, Deserialize, Serialize)]
+#[serde(default)]
+pub struct Sla {
+    /// The longest the host should take to start responding, like `"1500ms"` or `"2s"`.
+    pub max_latency: Option<Latency>,
+}
+
+/// A response time that's given as a string in the config file, like `"1500ms"`.
+#[derive(Debug, Clone, Copy)]
+pub struct Latency(pub Duration);
+
+impl FromStr for Latency {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || {
+            format!(
+                "invalid latency '{}', expected something like '1500ms' or '2s'",
+                s
+            )
+        };
+        let (n, scale) = if let Some(n) = s.strip_suffix("ms") {
+            (n, 0.001)
+        } else if let Some(n) = s.strip_suffix('s') {
+            (n, 1.0)
+        } else {
+            return Err(invalid());
+        };
+        match n.trim().parse::<f64>() {
+            Ok(n) if n.is_finite() && n > 0.0 => Ok(Latency(Duration::from_secs_f64(n * scale))),
+            _ => Err(invalid()),
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for Latency {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        String::deserialize(deserializer)?
+            .parse()
+            .map_err(de::Error::custom)
+    }
+}
+
+impl Serialize for Latency {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serializer.serialize_str(&format!("{}ms", self.0.as_millis()))
+    }
+}
+
+/// Ignores the links matching a pattern.
+#[derive(Debug, Deserialize, Serialize)]
+pub struct IgnoreRule {
+    /// Pattern for the links this applies to.
+    pub url: Pattern,
+    /// The last day the links are ignored. After that they're checked again, with a
+    /// warning that the ignore has expired.
+    pub until: Option<Date>,
+    /// Why the links are ignored, like "vendor outage".
+    pub reason: Option<String>,
+}
+
+/// Rewrites the parts of links that match a pattern before they're checked.
+#[derive(Debug, Deserialize, Serialize)]
+pub struct RewriteRule {
+    /// Pattern for the parts of links to replace.
+    pub url: Pattern,
+    /// What to replace them with, which can refer to groups in the pattern like `$1`.
+    pub replacement: String,
+}
+
+/// A regular expression that's given as a string in the config file.
+#[derive(Debug)]
+pub struct Pattern(Regex);
+
+impl Pattern {
+    pub fn is_match(&self, s: &str) -> bool {
+        self.0.is_match(s)
+    }
+
+    pub fn replace_all<'a>(&self, s: &'a str, replacement: &str) -> Cow<'a, str> {
+        self.0.replace_all(s, replacement)
+    }
+}
+
+impl fmt::Display for Pattern {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0.as_str())
+    }
+}
+
+/// A glob for the hosts of URLs, like `*.internal.corp`, or for their hosts and paths,
+/// like `bit.ly/*`.
+#[derive(Debug)]
+pub struct UrlGlob {
+    glob: String,
+    matcher: GlobMatcher,
+}
+
+impl UrlGlob {
+    fn is_match(&self, host: &str, path: &str) -> bool {
+        if self.glob.contains('/') {
+            self.matcher.is_match(format!("{}{}", host, path))
+        } else {
+            self.matcher.is_match(host)
+        }
+    }
+}
+
+impl fmt::Display for UrlGlob {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.glob)
+    }
+}
+
+impl<'de> Deserialize<'de> for UrlGlob {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let glob = String::deserialize(deserializer)?;
+        let matcher = GlobBuilder::new(&glob)
+            .case_insensitive(true)
+            .build()
+            .map_err(de::Error::custom)?
+            .compile_matcher();
+        Ok(UrlGlob { glob, matcher })
+    }
+}
+
+impl Serialize for UrlGlob {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serializer.serialize_str(&self.glob)
+    }
+}
+
+impl<'de> Deserialize<'de> for Pattern {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let s = String::deserialize(deserializer)?;
+        Regex::new(&s).map(Pattern).map_err(de::Error::custom)
+    }
+}
+
+impl Serialize for Pattern {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serializer.serialize_str(self.0.as_str())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn millis(s: &str) -> u128 {
+        s.parse::<Latency>().unwrap().0.as_millis()
+    }
+
+    #[test]
+    fn parse_latency() {
+        assert_eq!(millis("1500ms"), 1500);
+        assert_eq!(millis("2s"), 2000);
+        assert_eq!(millis("0.25s"), 250);
+        assert!("2".parse::<Latency>().is_err());
+        assert!("0ms".parse::<Latency>().is_err());
+        assert!("-1s".parse::<Latency>().is_err());
+        assert!("fast".parse::<Latency>().is_err());
+    }
+
+    fn forbid(globs: &str) -> Config {
+        toml::from_str(&format!("forbid = {}", globs)).unwrap()
+    }
+
+    #[test]
+    fn forbid_hosts() {
+        let config = forbid(r#"["bit.ly", "*.internal.example.com"]"#);
+        assert!(config.forbidden("https://bit.ly/abc").is_some());
+        assert!(config.forbidden("https://BIT.LY/abc").is_some());
+        assert!(config
+            .forbidden("http://wiki.internal.example.com/page")
+            .is_some());
+        assert!(config.forbidden("https://example.com/bit.ly").is_none());
+        assert!(config.forbidden("https://notbit.ly/").is_none());
+    }
+
+    #[test]
+    fn forbid_paths() {
+        let config = forbid(r#"["example.com/private/**"]"#);
+        let glob = config.forbidden("https://example.com/private/docs/a?b=c");
+        assert_eq!(
+            glob.map(ToString::to_string).as_deref(),
+            Some("example.com/private/**")
+        );
+        assert!(config.forbidden("https://example.com/public/a").is_none());
+        assert!(config.forbidden("not a url").is_none());
+    }
+}
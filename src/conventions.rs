@@ -0,0 +1,179 @@
+use std::collections::VecDeque;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use regex::Regex;
+
+/// A documentation tool whose layout we know, so that a run without a config file can
+/// still search the files it renders and skip the site it builds.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Convention {
+    MdBook,
+    MkDocs,
+    Docusaurus,
+    Antora,
+    Sphinx,
+}
+
+impl Convention {
+    const ALL: [Convention; 5] = [
+        Convention::MdBook,
+        Convention::MkDocs,
+        Convention::Docusaurus,
+        Convention::Antora,
+        Convention::Sphinx,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Convention::MdBook => "mdBook",
+            Convention::MkDocs => "MkDocs",
+            Convention::Docusaurus => "Docusaurus",
+            Convention::Antora => "Antora",
+            Convention::Sphinx => "Sphinx",
+        }
+    }
+
+    /// The config files that give the tool away.
+    fn markers(self) -> &'static [&'static str] {
+        match self {
+            Convention::MdBook => &["book.toml"],
+            Convention::MkDocs => &["mkdocs.yml", "mkdocs.yaml"],
+            Convention::Docusaurus => &[
+                "docusaurus.config.js",
+                "docusaurus.config.ts",
+                "docusaurus.config.mjs",
+            ],
+            Convention::Antora => &["antora.yml", "antora-playbook.yml"],
+            Convention::Sphinx => &["conf.py"],
+        }
+    }
+
+    /// Globs for the files the tool renders that we don't search unless it's in use.
+    pub fn globs(self) -> &'static [&'static str] {
+        match self {
+            Convention::Docusaurus => &["*.mdx"],
+            Convention::Antora => &["*.adoc"],
+            Convention::Sphinx => &["*.rst"],
+            Convention::MdBook | Convention::MkDocs => &[],
+        }
+    }
+
+    /// Where the tool writes the site it builds, relative to its config file. What's in
+    /// there is generated from the sources, so checking it too would report every bad
+    /// link twice.
+    fn build_dirs(self, marker: &Path, contents: &str) -> Vec<String> {
+        lazy_static! {
+            static ref SITE_DIR_RE: Regex =
+                Regex::new(r#"(?m)^site_dir:\s*["']?([^"'\s#]+)"#).unwrap();
+        }
+        match self {
+            Convention::MdBook => {
+                let build_dir = toml::from_str::<toml::Value>(contents)
+                    .ok()
+                    .and_then(|book| {
+                        book.get("build")?
+                            .get("build-dir")?
+                            .as_str()
+                            .map(String::from)
+                    });
+                vec![build_dir.unwrap_or_else(|| String::from("book"))]
+            }
+            Convention::MkDocs => vec![SITE_DIR_RE
+                .captures(contents)
+                .map_or_else(|| String::from("site"), |caps| caps[1].to_string())],
+            Convention::Docusaurus => vec![String::from("build"), String::from(".docusaurus")],
+            // The playbook says where the site goes, but component descriptors don't
+            // build anything themselves.
+            Convention::Antora if marker.ends_with("antora-playbook.yml") => {
+                vec![String::from("build")]
+            }
+            Convention::Antora => vec![],
+            Convention::Sphinx => vec![String::from("_build")],
+        }
+    }
+
+    /// Check that a marker file really is the tool's config, for names as common as
+    /// `conf.py`.
+    fn is_config(self, contents: &str) -> bool {
+        match self {
+            Convention::Sphinx => contents.contains("sphinx") || contents.contains("html_theme"),
+            _ => true,
+        }
+    }
+}
+
+/// A documentation tool found in a directory being checked.
+#[derive(Debug)]
+pub struct Detected {
+    pub convention: Convention,
+    /// The config file that gave it away.
+    pub marker: PathBuf,
+    /// Globs for the directories the tool builds the site in, relative to the root, for
+    /// excluding them from the search.
+    pub excludes: Vec<String>,
+}
+
+/// Look for the config files of documentation tools in a directory and the directories
+/// up to two levels below it, where they often are, like `docs/` or `docs/source/`.
+pub fn detect(root: &Path) -> Vec<Detected> {
+    let mut detected = vec![];
+    // The directories closest to the root are searched first, since that's where a
+    // project's own docs are more likely to be than in an example.
+    let mut dirs = VecDeque::from(vec![(root.to_path_buf(), 0)]);
+    while let Some((dir, depth)) = dirs.pop_front() {
+        for convention in Convention::ALL.iter() {
+            if detected
+                .iter()
+                .any(|d: &Detected| d.convention == *convention)
+            {
+                continue;
+            }
+            for marker in convention.markers() {
+                let marker = dir.join(marker);
+                let contents = match fs::read_to_string(&marker) {
+                    Ok(contents) => contents,
+                    Err(_) => continue,
+                };
+                if !convention.is_config(&contents) {
+                    continue;
+                }
+                let relative = dir.strip_prefix(root).unwrap_or(&dir);
+                let excludes = convention
+                    .build_dirs(&marker, &contents)
+                    .iter()
+                    .map(|build_dir| {
+                        let path = relative.join(build_dir.trim_matches('/'));
+                        format!("/{}/", path.to_string_lossy().replace('\\', "/"))
+                    })
+                    .collect();
+                detected.push(Detected {
+                    convention: *convention,
+                    marker,
+                    excludes,
+                });
+                break;
+            }
+        }
+        if depth == 2 {
+            continue;
+        }
+        let mut entries: Vec<fs::DirEntry> = match fs::read_dir(&dir) {
+            Ok(entries) => entries.filter_map(Result::ok).collect(),
+            Err(_) => continue,
+        };
+        entries.sort_by_key(|entry| entry.file_name());
+        for entry in entries {
+            let name = entry.file_name();
+            let name = name.to_string_lossy();
+            // Hidden directories and dependencies aren't where the docs live.
+            if name.starts_with('.') || name == "node_modules" || name == "target" {
+                continue;
+            }
+            if entry.file_type().is_ok_and(|file_type| file_type.is_dir()) {
+                dirs.push_back((entry.path(), depth + 1));
+            }
+        }
+    }
+    detected
+}
@@ -0,0 +1,48 @@
+use std::time::{Duration, Instant};
+
+use isahc::HttpClient;
+use tokio::sync::Mutex;
+use tokio::time::delay_for;
+
+/// crates.io asks crawlers to send at most one request a second, with a user agent that
+/// says who they are, or it may block them: https://crates.io/policies#crawlers
+const MIN_INTERVAL: Duration = Duration::from_secs(1);
+
+pub const USER_AGENT: &str = concat!(
+    "check-links/",
+    env!("CARGO_PKG_VERSION"),
+    " (https://github.com/epwalsh/check-links)"
+);
+
+/// Check if a host is crates.io, as opposed to docs.rs or a mirror, which have no such
+/// policy.
+pub fn is_crates_io(host: &str) -> bool {
+    host.eq_ignore_ascii_case("crates.io") || host.eq_ignore_ascii_case("www.crates.io")
+}
+
+/// Sends the requests for links to crates.io, one at a time and no faster than it asks,
+/// apart from the requests to other hosts so that they don't wait on it.
+pub struct CratesIo {
+    pub http_client: HttpClient,
+    /// The earliest the next request can be sent.
+    next: Mutex<Instant>,
+}
+
+impl CratesIo {
+    pub fn new(http_client: HttpClient) -> Self {
+        CratesIo {
+            http_client,
+            next: Mutex::new(Instant::now()),
+        }
+    }
+
+    /// Wait until it's our turn to send a request.
+    pub async fn throttle(&self) {
+        let mut next = self.next.lock().await;
+        let now = Instant::now();
+        if *next > now {
+            delay_for(*next - now).await;
+        }
+        *next = Instant::now() + MIN_INTERVAL;
+    }
+}
@@ -0,0 +1,117 @@
+use std::collections::{HashSet, VecDeque};
+
+use regex::Regex;
+use url::Url;
+
+use crate::doc_file::HTML_LINKS;
+
+/// A link found on a page of a site.
+pub struct PageLink {
+    pub url: Url,
+    pub lnum: usize,
+    /// The column the link starts at on its line, counting characters from 1.
+    pub col: usize,
+    /// Whether the link is to a resource embedded in the page, like an image or a script.
+    pub embedded: bool,
+}
+
+/// Finds the pages of a site to check the links on, starting from one of them and
+/// following links to the others, breadth first.
+pub struct Crawler {
+    start: Url,
+    queue: VecDeque<Url>,
+    /// The pages that have been queued, so none is crawled twice.
+    queued: HashSet<String>,
+    /// The links that have been checked, since most pages share a lot of links, like
+    /// the ones in navigation bars, and each is only worth checking once.
+    checked: HashSet<String>,
+    max_pages: usize,
+    crawled: usize,
+}
+
+impl Crawler {
+    pub fn new(start: &str, max_pages: usize) -> Result<Self, url::ParseError> {
+        let start = Url::parse(start)?;
+        let mut crawler = Crawler {
+            start: start.clone(),
+            queue: VecDeque::new(),
+            queued: HashSet::new(),
+            checked: HashSet::new(),
+            max_pages,
+            crawled: 0,
+        };
+        crawler.follow(&start);
+        Ok(crawler)
+    }
+
+    /// Get the next page to crawl, until there are no more or we've crawled enough.
+    pub fn next_page(&mut self) -> Option<Url> {
+        if self.crawled >= self.max_pages {
+            return None;
+        }
+        let page = self.queue.pop_front()?;
+        self.crawled += 1;
+        Some(page)
+    }
+
+    /// Queue a link to be crawled, if it's to another page on the same site.
+    pub fn follow(&mut self, url: &Url) {
+        if url.origin() != self.start.origin() || !is_page(url) {
+            return;
+        }
+        let mut page = url.clone();
+        page.set_fragment(None);
+        if self.queued.insert(page.to_string()) {
+            self.queue.push_back(page);
+        }
+    }
+
+    /// Check if a link hasn't been found on any other page yet, so it still needs to be
+    /// checked.
+    pub fn is_new(&mut self, url: &Url) -> bool {
+        self.checked.insert(url.to_string())
+    }
+}
+
+/// Check if a URL looks like it's to a page rather than a file, like an image or a PDF.
+fn is_page(url: &Url) -> bool {
+    let name = url
+        .path_segments()
+        .and_then(|mut segments| segments.next_back())
+        .unwrap_or_default();
+    match name.rfind('.') {
+        Some(i) => {
+            let ext = name[i + 1..].to_ascii_lowercase();
+            ext == "html" || ext == "htm"
+        }
+        None => true,
+    }
+}
+
+/// Find the HTTP links on a page, resolved against the page's URL.
+pub fn links(page: &Url, body: &str) -> Vec<PageLink> {
+    lazy_static! {
+        static ref LINK_RE: Regex = Regex::new(HTML_LINKS).unwrap();
+    }
+    let mut links = vec![];
+    for (i, line) in body.lines().enumerate() {
+        for caps in LINK_RE.captures_iter(line) {
+            let (group, m) = match (1..=3).find_map(|g| caps.get(g).map(|m| (g, m))) {
+                Some(found) => found,
+                None => continue,
+            };
+            // Ampersands in attributes are usually escaped.
+            let url = match page.join(&m.as_str().trim().replace("&amp;", "&")) {
+                Ok(url) if url.scheme() == "http" || url.scheme() == "https" => url,
+                _ => continue,
+            };
+            links.push(PageLink {
+                url,
+                lnum: i + 1,
+                col: line[..m.start()].chars().count() + 1,
+                embedded: group != 1,
+            });
+        }
+    }
+    links
+}
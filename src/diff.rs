@@ -0,0 +1,188 @@
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+
+use crate::github::{repo_path, repo_root};
+use crate::link::normalize_path;
+
+/// The lines added by a unified diff, like the output of `git diff`, in each file.
+pub struct AddedLines {
+    files: HashMap<String, HashSet<usize>>,
+    /// The root of the repository the diff's paths are relative to, if we're in one.
+    root: Option<PathBuf>,
+}
+
+impl AddedLines {
+    pub fn parse(diff: &str) -> Self {
+        // Git prefixes the old and new paths with `a/` and `b/`.
+        let is_git = diff
+            .lines()
+            .any(|line| line.starts_with("diff --git ") || line.starts_with("--- a/"));
+        let mut files: HashMap<String, HashSet<usize>> = HashMap::new();
+        for_each_added_line(diff, |file, lnum| {
+            let file = match file {
+                Some(file) if is_git => file.strip_prefix("b/").unwrap_or(file),
+                Some(file) => file,
+                None => return,
+            };
+            files.entry(file.to_string()).or_default().insert(lnum);
+        });
+        AddedLines {
+            files,
+            root: repo_root(),
+        }
+    }
+
+    fn lines(&self, file: &Path) -> Option<&HashSet<usize>> {
+        let path = match &self.root {
+            Some(root) => repo_path(root, file)?,
+            None => normalize_path(file).to_string_lossy().replace('\\', "/"),
+        };
+        self.files.get(&path)
+    }
+
+    /// Check if the diff adds any lines to a file.
+    pub fn has_file(&self, file: &Path) -> bool {
+        self.lines(file).is_some()
+    }
+
+    /// Check if the diff adds a line.
+    pub fn contains(&self, file: &Path, lnum: usize) -> bool {
+        self.lines(file).is_some_and(|lines| lines.contains(&lnum))
+    }
+}
+
+/// Get the line numbers (in the new version of the file) added by the hunks of a patch
+/// for a single file.
+pub fn patch_added_lines(patch: &str) -> HashSet<usize> {
+    let mut lines = HashSet::new();
+    for_each_added_line(patch, |_, lnum| {
+        lines.insert(lnum);
+    });
+    lines
+}
+
+/// Call `f` with the file (if the diff names it) and line number of each added line in
+/// a diff. The number of lines in each hunk are counted so that the headers of the next
+/// file aren't mistaken for changes.
+fn for_each_added_line<F>(diff: &str, mut f: F)
+where
+    F: FnMut(Option<&str>, usize),
+{
+    let mut file = None;
+    let mut lnum = 0;
+    let mut old_remaining = 0;
+    let mut new_remaining = 0;
+    for line in diff.lines() {
+        if old_remaining == 0 && new_remaining == 0 {
+            if let Some(path) = line.strip_prefix("+++ ") {
+                // The path may be followed by a tab and a timestamp.
+                let path = path.split('\t').next().unwrap_or(path).trim();
+                file = if path == "/dev/null" {
+                    None
+                } else {
+                    Some(path)
+                };
+            } else if line.starts_with("@@") {
+                // Hunk headers look like `@@ -1,4 +1,5 @@`, where a missing count is 1.
+                let range = |prefix| {
+                    let range = line
+                        .split_whitespace()
+                        .find_map(|part: &str| part.strip_prefix(prefix))?;
+                    let mut parts = range.split(',');
+                    let start: usize = parts.next()?.parse().ok()?;
+                    let count: usize = match parts.next() {
+                        Some(count) => count.parse().ok()?,
+                        None => 1,
+                    };
+                    Some((start, count))
+                };
+                let (_, old_count) = range('-').unwrap_or((0, 0));
+                let (start, new_count) = range('+').unwrap_or((0, 0));
+                lnum = start;
+                old_remaining = old_count;
+                new_remaining = new_count;
+            }
+            continue;
+        }
+        if line.starts_with('+') {
+            f(file, lnum);
+            lnum += 1;
+            new_remaining = new_remaining.saturating_sub(1);
+        } else if line.starts_with('-') {
+            old_remaining = old_remaining.saturating_sub(1);
+        } else if !line.starts_with('\\') {
+            lnum += 1;
+            old_remaining = old_remaining.saturating_sub(1);
+            new_remaining = new_remaining.saturating_sub(1);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sorted(lines: HashSet<usize>) -> Vec<usize> {
+        let mut lines: Vec<usize> = lines.into_iter().collect();
+        lines.sort_unstable();
+        lines
+    }
+
+    #[test]
+    fn counts_added_lines_in_the_new_file() {
+        let patch = "\
+@@ -1,3 +1,4 @@
+ first
+-old
++new
++another
+ last
+@@ -10 +11,2 @@
+-gone
++here
++there
+";
+        assert_eq!(sorted(patch_added_lines(patch)), vec![2, 3, 11, 12]);
+    }
+
+    #[test]
+    fn stops_at_the_end_of_each_hunk() {
+        // The `+++` of the next file is past the end of the first file's hunk, so it's a
+        // header rather than an added line.
+        let diff = "\
+--- a/one.md
++++ b/one.md
+@@ -1 +1 @@
+-old
++new
+--- a/two.md
++++ b/two.md
+@@ -5,0 +6 @@
++added
+\\ No newline at end of file
+";
+        let mut found = vec![];
+        for_each_added_line(diff, |file, lnum| {
+            found.push((file.unwrap().to_string(), lnum))
+        });
+        assert_eq!(
+            found,
+            vec![(String::from("b/one.md"), 1), (String::from("b/two.md"), 6)]
+        );
+    }
+
+    #[test]
+    fn skips_deleted_files() {
+        let diff = "\
+--- a/gone.md
++++ /dev/null
+@@ -1 +0,0 @@
+-gone
+";
+        let mut found = vec![];
+        for_each_added_line(diff, |file, lnum| {
+            found.push((file.map(String::from), lnum))
+        });
+        assert!(found.is_empty());
+    }
+}
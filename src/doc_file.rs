@@ -1,62 +1,361 @@
-use std::io;
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader};
 use std::path::{Path, PathBuf};
+use std::str;
 
 use globset::{Glob, GlobSet, GlobSetBuilder};
 use grep_matcher::{Captures, Matcher};
 use grep_regex::RegexMatcher;
-use grep_searcher::sinks::UTF8;
-use grep_searcher::Searcher;
+use grep_searcher::{Searcher, SearcherBuilder, Sink, SinkContext, SinkMatch};
+use regex::Regex;
 
-use crate::link::Link;
+use crate::ignores;
+use crate::link::{LinePosition, Link};
+use crate::link_text::LinkTexts;
+use crate::tags::{Headings, Tag};
+
+/// How far from the top of a file a directive to skip it can be.
+const SKIP_DIRECTIVE_LINES: usize = 20;
+
+/// Finds the links in HTML. Links from `<a>` tags (group 1) are navigated to while
+/// `<link>` tags (group 2) and `src` attributes (group 3) point to resources embedded in
+/// the page.
+pub const HTML_LINKS: &str = r#"<a\s[^>]*?\bhref\s*=\s*["']([^"']+)["']|<link\s[^>]*?\bhref\s*=\s*["']([^"']+)["']|\ssrc\s*=\s*["']([^"']+)["']"#;
+
+/// Turns what's matched into the link to check.
+type Transform = fn(&str) -> String;
 
 pub struct DocFile {
+    globs: Vec<String>,
     glob_set: GlobSet,
     pub link_matcher: RegexMatcher,
-    match_group: usize,
+    /// The capture groups that a link could be in. The first group that participates in a
+    /// match is used.
+    match_groups: Vec<usize>,
+    /// The capture groups which correspond to resources embedded in the document, like
+    /// images or scripts, as opposed to links that are navigated to.
+    embedded_groups: Vec<usize>,
+    /// The capture groups which correspond to wiki links, which are to pages by name.
+    wiki_groups: Vec<usize>,
+    /// Functions that turn what's matched by a capture group into the link to check,
+    /// for formats where that isn't just the URL or path.
+    transforms: Vec<(usize, Transform)>,
+    /// Matches a line near the top of a file that says the file shouldn't be checked.
+    skip_directive: Option<Regex>,
+    /// Tags for every link found, like for links in doc comments.
+    tags: Vec<Tag>,
 }
 
 impl DocFile {
-    pub fn new(globs: Vec<&str>, link_pattern: &str, match_group: usize) -> Self {
+    /// Fails if one of the globs is invalid, since some of them come from the command
+    /// line.
+    pub fn new(
+        globs: Vec<&str>,
+        link_pattern: &str,
+        match_group: usize,
+    ) -> Result<Self, globset::Error> {
         let mut glob_builder = GlobSetBuilder::new();
-        for glob in globs {
-            glob_builder.add(Glob::new(glob).unwrap());
+        for glob in &globs {
+            glob_builder.add(Glob::new(glob)?);
         }
-        let glob_set = glob_builder.build().unwrap();
+        let glob_set = glob_builder.build()?;
         let link_matcher = RegexMatcher::new(link_pattern).unwrap();
-        DocFile {
+        Ok(DocFile {
+            globs: globs.into_iter().map(String::from).collect(),
             glob_set,
             link_matcher,
-            match_group,
+            match_groups: vec![match_group],
+            embedded_groups: vec![],
+            wiki_groups: vec![],
+            transforms: vec![],
+            skip_directive: None,
+            tags: vec![],
+        })
+    }
+
+    /// Look for links in any of the given capture groups.
+    pub fn with_groups(mut self, match_groups: Vec<usize>) -> Self {
+        self.match_groups = match_groups;
+        self
+    }
+
+    /// Mark links found in the given capture groups as embedded resources.
+    pub fn with_embedded_groups(mut self, embedded_groups: Vec<usize>) -> Self {
+        self.embedded_groups = embedded_groups;
+        self
+    }
+
+    /// Mark links found in the given capture groups as wiki links.
+    pub fn with_wiki_groups(mut self, wiki_groups: Vec<usize>) -> Self {
+        self.wiki_groups = wiki_groups;
+        self
+    }
+
+    /// Transform what's matched by a capture group into the link to check.
+    pub fn with_transform(mut self, group: usize, transform: Transform) -> Self {
+        self.transforms.push((group, transform));
+        self
+    }
+
+    /// Tag every link found.
+    pub fn with_tag(mut self, tag: Tag) -> Self {
+        self.tags.push(tag);
+        self
+    }
+
+    /// Skip files that have a line matching the given pattern near the top, so files
+    /// can opt out of being checked on their own.
+    pub fn with_skip_directive(mut self, pattern: &str) -> Self {
+        self.skip_directive = Some(Regex::new(pattern).unwrap());
+        self
+    }
+
+    /// Check if a file has opted out of being checked.
+    pub fn is_skipped(&self, p: &Path) -> Result<bool, io::Error> {
+        let skip_directive = match &self.skip_directive {
+            Some(skip_directive) => skip_directive,
+            None => return Ok(false),
+        };
+        let file = BufReader::new(File::open(p)?);
+        for line in file.lines().take(SKIP_DIRECTIVE_LINES) {
+            // The rest of the file will be searched as text anyway, so there's no need to
+            // fail here on what isn't.
+            match line {
+                Ok(line) if skip_directive.is_match(&line) => return Ok(true),
+                Ok(_) => {}
+                Err(_) => break,
+            }
         }
+        Ok(false)
     }
 
-    pub fn is_match<P>(&self, p: P) -> bool
-    where
-        P: AsRef<Path>,
-    {
-        self.glob_set.is_match(p)
+    /// Get the most specific of our globs that matches a path, if any do.
+    fn best_glob(&self, p: &Path) -> Option<&str> {
+        self.glob_set
+            .matches(p)
+            .into_iter()
+            .map(|i| &self.globs[i][..])
+            .max_by_key(|glob| specificity(glob))
     }
 
-    pub fn iter_links<F>(&self, p: &PathBuf, mut f: F) -> Result<(), io::Error>
+    /// Find the links in a file. Whether they're ignored by a directive, the heading
+    /// they're under, and, with `text_conflicts`, whether their text is also used for
+    /// links to somewhere else, all come from the same pass over the file.
+    pub fn iter_links<F>(
+        &self,
+        p: &PathBuf,
+        text_conflicts: bool,
+        mut f: F,
+    ) -> Result<(), io::Error>
     where
         F: FnMut(Link),
     {
-        let mut searcher = Searcher::new();
+        let mut ignores = HashMap::new();
+        let mut headings = Headings::of(p);
+        let mut link_texts = if text_conflicts {
+            LinkTexts::of(p)
+        } else {
+            None
+        };
+        let mut links = vec![];
+        let mut searcher = SearcherBuilder::new().passthru(true).build();
         searcher.search_path(
             &self.link_matcher,
             p,
-            UTF8(|lnum, line| {
-                let mut captures = self.link_matcher.new_captures().unwrap();
-                self.link_matcher
-                    .captures_iter(line.as_bytes(), &mut captures, |c| {
-                        let mat = c.get(self.match_group).unwrap();
-                        let mat = line[mat].to_string();
-                        let link = Link::new(p.clone(), lnum as usize, mat);
-                        f(link);
-                        true
-                    })?;
+            EveryLine(|lnum, line: &str, matched| {
+                let lnum = lnum as usize;
+                let text = line.trim_end_matches(['\r', '\n']);
+                if let Some(ignore) = ignores::directive(text) {
+                    ignores.insert(if matched { lnum } else { lnum + 1 }, ignore);
+                }
+                if let Some(link_texts) = &mut link_texts {
+                    link_texts.line(lnum, text);
+                }
+                if matched {
+                    let heading = headings.as_ref().and_then(Headings::current);
+                    let found = self.links_on_line(p, lnum, line, heading)?;
+                    links.extend(found);
+                }
+                if let Some(headings) = &mut headings {
+                    headings.line(text);
+                }
                 Ok(true)
             }),
-        )
+        )?;
+        let conflicts = link_texts
+            .map(|link_texts| link_texts.conflicts())
+            .unwrap_or_default();
+        for mut link in links {
+            link.ignore = ignores.get(&link.lnum).cloned();
+            link.text_conflict = link
+                .col
+                .and_then(|col| conflicts.get(&(link.lnum, col)))
+                .cloned();
+            f(link);
+        }
+        Ok(())
+    }
+
+    fn links_on_line(
+        &self,
+        p: &Path,
+        lnum: usize,
+        line: &str,
+        heading: Option<&str>,
+    ) -> Result<Vec<Link>, io::Error> {
+        let mut captures = self.link_matcher.new_captures().unwrap();
+        let mut links = vec![];
+        self.link_matcher
+            .captures_iter(line.as_bytes(), &mut captures, |c| {
+                let group = self.match_groups.iter().find(|g| c.get(**g).is_some());
+                if let Some(group) = group {
+                    let span = c.get(*group).unwrap();
+                    let mut mat = line[span].to_string();
+                    for (g, transform) in &self.transforms {
+                        if g == group {
+                            mat = transform(&mat);
+                        }
+                    }
+                    let mut link = Link::new(p.to_path_buf(), lnum, mat);
+                    link.col = Some(line[..span.start()].chars().count() + 1);
+                    link.end_col = Some(line[..span.end()].chars().count() + 1);
+                    link.embedded = self.embedded_groups.contains(group);
+                    link.wiki = self.wiki_groups.contains(group);
+                    let start = c.get(0).map_or(span.start(), |whole| whole.start());
+                    link.tags = self.tags.clone();
+                    link.tags.extend(Tag::of(p, line, start, &link.raw));
+                    link.heading = heading.map(String::from);
+                    links.push(link);
+                }
+                true
+            })?;
+        let count = links.len();
+        for (index, link) in links.iter_mut().enumerate() {
+            link.line_position = Some(LinePosition {
+                index: index + 1,
+                count,
+            });
+        }
+        Ok(links)
+    }
+}
+
+/// Hands every line of a file to a function, along with whether it matched, so that
+/// what's around the links can be found in the same pass as the links.
+struct EveryLine<F>(F);
+
+impl<F> Sink for EveryLine<F>
+where
+    F: FnMut(u64, &str, bool) -> Result<bool, io::Error>,
+{
+    type Error = io::Error;
+
+    fn matched(&mut self, _searcher: &Searcher, mat: &SinkMatch<'_>) -> Result<bool, io::Error> {
+        let line = str::from_utf8(mat.bytes())
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+        (self.0)(mat.line_number().unwrap_or_default(), line, true)
     }
+
+    fn context(
+        &mut self,
+        _searcher: &Searcher,
+        context: &SinkContext<'_>,
+    ) -> Result<bool, io::Error> {
+        // Lines without links are only looked at for what's around them, so they needn't
+        // be valid UTF-8.
+        let line = String::from_utf8_lossy(context.bytes());
+        (self.0)(context.line_number().unwrap_or_default(), &line, false)
+    }
+}
+
+/// A doc file whose globs match a path.
+pub struct DocFileMatch<'a> {
+    pub doc_file: &'a DocFile,
+    /// The glob that matched.
+    pub glob: &'a str,
+}
+
+/// Find the doc files that match a path, with the most specific match first. A file
+/// should only be searched by the first, since the others would find the same links or
+/// misread them. When globs are equally specific, the doc file listed first wins.
+pub fn matches<'a>(doc_files: &'a [DocFile], p: &Path) -> Vec<DocFileMatch<'a>> {
+    let mut matches: Vec<DocFileMatch> = doc_files
+        .iter()
+        .filter_map(|doc_file| {
+            let glob = doc_file.best_glob(p)?;
+            Some(DocFileMatch { doc_file, glob })
+        })
+        .collect();
+    // This is a stable sort, so ties stay in order.
+    matches.sort_by_key(|m| std::cmp::Reverse(specificity(m.glob)));
+    matches
+}
+
+/// How specific a glob is, going by the number of characters that aren't wildcards, so
+/// `docs/**/*.md` is more specific than `*.md`.
+fn specificity(glob: &str) -> usize {
+    glob.chars().filter(|c| !"*?[]{},!".contains(*c)).count()
+}
+
+/// Get the file for a wiki page from its name, the way GitHub wikis do: `Page Name`
+/// is in `Page-Name.md`.
+pub fn wiki_page_path(name: &str) -> String {
+    let name = name.trim();
+    if name.starts_with("http") {
+        return name.to_string();
+    }
+    let (page, section) = match name.find('#') {
+        Some(i) => (&name[..i], &name[i..]),
+        None => (name, ""),
+    };
+    format!("{}.md{}", page.trim().replace(' ', "-"), section)
+}
+
+/// Get the name of the wiki page a path is to, the other way around from
+/// `wiki_page_path`, like `Page-Name#Section` for `Page-Name.md#Section`.
+pub fn wiki_page_name(path: &str) -> String {
+    let (page, section) = match path.find('#') {
+        Some(i) => (&path[..i], &path[i..]),
+        None => (path, ""),
+    };
+    format!("{}{}", page.strip_suffix(".md").unwrap_or(page), section)
+}
+
+/// Get the target of an Org mode link like `[[file:design.org::*Goals][Goals]]`. Local
+/// files are prefixed with `file:`, and can be followed by `::` and a heading (`*Goals`)
+/// or custom id (`#goals`) to link to, which we check like Markdown sections.
+pub fn org_link_target(target: &str) -> String {
+    let target = match target.strip_prefix("file:") {
+        Some(target) => target,
+        None => return target.to_string(),
+    };
+    match target.find("::") {
+        Some(i) => {
+            let search = &target[i + 2..];
+            let section = search
+                .strip_prefix('*')
+                .or_else(|| search.strip_prefix('#'))
+                .map(|section| section.trim().replace(' ', "-"));
+            match section {
+                Some(section) => format!("{}#{}", &target[..i], section),
+                None => target[..i].to_string(),
+            }
+        }
+        None => target.to_string(),
+    }
+}
+
+/// Remove the escapes LaTeX needs in URLs, like `\%` and `\#`.
+pub fn latex_unescape(url: &str) -> String {
+    lazy_static! {
+        static ref ESCAPE_RE: Regex = Regex::new(r"\\([%#&_$~])").unwrap();
+    }
+    ESCAPE_RE.replace_all(url, "$1").into_owned()
+}
+
+/// Remove punctuation that's more likely to end the sentence a URL is in than to be
+/// part of the URL.
+pub fn trim_url(url: &str) -> String {
+    url.trim_end_matches(|c| ".,;:!?".contains(c)).to_string()
 }
@@ -0,0 +1,116 @@
+use std::env;
+use std::io;
+use std::path::Path;
+use std::process::Command;
+
+/// How to open a file at a line in the user's editor, as a command template with
+/// `{file}`, `{line}`, and `{column}` placeholders.
+#[derive(Debug, Clone)]
+pub struct Editor {
+    template: String,
+}
+
+impl Editor {
+    /// Use the given command, or else the one in `$CHECK_LINKS_EDITOR`, `$VISUAL`, or
+    /// `$EDITOR`, or else `vi`. A command without placeholders is taken to be just the
+    /// editor, and is given the file and line the way it expects them.
+    pub fn new(command: Option<&str>) -> Self {
+        let command = command
+            .map(String::from)
+            .or_else(|| env::var("CHECK_LINKS_EDITOR").ok())
+            .or_else(|| env::var("VISUAL").ok())
+            .or_else(|| env::var("EDITOR").ok())
+            .filter(|command| !command.trim().is_empty())
+            .unwrap_or_else(|| String::from("vi"));
+        let template = if command.contains("{file}") {
+            command
+        } else {
+            let program = command.split_whitespace().next().unwrap_or_default();
+            let name = Path::new(program)
+                .file_stem()
+                .and_then(|name| name.to_str())
+                .unwrap_or_default();
+            match name {
+                // VS Code and editors that share its command line.
+                "code" | "code-insiders" | "codium" | "cursor" => {
+                    format!("{} --goto {{file}}:{{line}}:{{column}}", command)
+                }
+                "subl" | "zed" => format!("{} {{file}}:{{line}}:{{column}}", command),
+                // Most terminal editors, like vi, emacs, and nano, take the line as `+N`.
+                _ => format!("{} +{{line}} {{file}}", command),
+            }
+        };
+        Editor { template }
+    }
+
+    /// Open a file at a line and column, and wait for the command to finish. For editors
+    /// that open a window, that's usually right away.
+    pub fn open(&self, file: &Path, line: usize, column: Option<usize>) -> io::Result<()> {
+        let mut words = self.command(file, line, column).into_iter();
+        let program = match words.next() {
+            Some(program) => program,
+            None => return Err(io::Error::other("no editor command")),
+        };
+        let status = Command::new(&program)
+            .args(words)
+            .status()
+            .map_err(|e| io::Error::new(e.kind(), format!("couldn't run {}: {}", program, e)))?;
+        if status.success() {
+            Ok(())
+        } else {
+            Err(io::Error::other(format!(
+                "{} exited with {}",
+                program, status
+            )))
+        }
+    }
+
+    /// Get the words of the command to open a file at a line and column. The template is
+    /// split into words before the placeholders are filled in, so paths with spaces in
+    /// them stay in one piece.
+    fn command(&self, file: &Path, line: usize, column: Option<usize>) -> Vec<String> {
+        self.template
+            .split_whitespace()
+            .map(|word| {
+                word.replace("{file}", &file.to_string_lossy())
+                    .replace("{line}", &line.to_string())
+                    .replace("{column}", &column.unwrap_or(1).to_string())
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn command(editor: &str, file: &str, column: Option<usize>) -> Vec<String> {
+        Editor::new(Some(editor)).command(Path::new(file), 12, column)
+    }
+
+    #[test]
+    fn terminal_editors_take_the_line_before_the_file() {
+        assert_eq!(command("vim", "a.md", Some(3)), ["vim", "+12", "a.md"]);
+        assert_eq!(
+            command("emacs -nw", "a.md", None),
+            ["emacs", "-nw", "+12", "a.md"]
+        );
+    }
+
+    #[test]
+    fn known_editors_are_given_the_column() {
+        assert_eq!(
+            command("/usr/bin/code --wait", "a.md", Some(3)),
+            ["/usr/bin/code", "--wait", "--goto", "a.md:12:3"]
+        );
+        assert_eq!(command("subl", "a.md", None), ["subl", "a.md:12:1"]);
+    }
+
+    #[test]
+    fn templates_are_filled_in_word_by_word() {
+        assert_eq!(
+            command("ed {file} {line}", "my docs/a b.md", None),
+            ["ed", "my docs/a b.md", "12"]
+        );
+    }
+}
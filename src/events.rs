@@ -0,0 +1,110 @@
+use std::fs::File;
+use std::io::{self, Write};
+use std::path::Path;
+use std::str::FromStr;
+
+use failure::{Error, ResultExt};
+use serde_json::{json, Value};
+
+use crate::link::Link;
+use crate::report;
+use crate::tui::Dashboard;
+
+/// How to stream progress events.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum EventFormat {
+    /// One JSON object per line.
+    Ndjson,
+}
+
+impl EventFormat {
+    pub fn variants() -> [&'static str; 1] {
+        ["ndjson"]
+    }
+}
+
+impl FromStr for EventFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "ndjson" => Ok(EventFormat::Ndjson),
+            _ => Err(format!("unknown event format '{}'", s)),
+        }
+    }
+}
+
+/// Streams an event for each step of a run, so that other programs can show progress
+/// as it happens without parsing the log.
+#[derive(Default)]
+pub struct Events {
+    out: Option<Box<dyn Write>>,
+    /// The dashboard showing the run, which follows the same events.
+    pub dashboard: Option<Dashboard>,
+}
+
+impl Events {
+    /// Stream events to a file, like `/dev/fd/3`, or to stdout.
+    pub fn open(path: Option<&Path>) -> Result<Self, Error> {
+        let out: Box<dyn Write> = match path {
+            Some(path) => Box::new(
+                File::create(path)
+                    .with_context(|_| format!("failed to open {} for events", path.display()))?,
+            ),
+            None => Box::new(io::stdout()),
+        };
+        Ok(Events {
+            out: Some(out),
+            dashboard: None,
+        })
+    }
+
+    fn emit(&mut self, event: &str, mut fields: Value) {
+        fields["event"] = json!(event);
+        let out = match &mut self.out {
+            Some(out) => out,
+            None => return,
+        };
+        // Whoever's reading the events going away shouldn't stop the check, so we just
+        // stop sending them.
+        if writeln!(out, "{}", fields)
+            .and_then(|_| out.flush())
+            .is_err()
+        {
+            self.out = None;
+        }
+    }
+
+    pub fn file_scanned(&mut self, file: &Path, links: usize) {
+        self.emit("file-scanned", json!({ "file": file, "links": links }));
+        if let Some(dashboard) = &mut self.dashboard {
+            dashboard.file_scanned();
+        }
+    }
+
+    pub fn link_found(&mut self, link: &Link) {
+        self.emit(
+            "link-found",
+            json!({
+                "file": link.file,
+                "line": link.lnum,
+                "column": link.col,
+                "url": link.raw,
+            }),
+        );
+        if let Some(dashboard) = &mut self.dashboard {
+            dashboard.link_found();
+        }
+    }
+
+    pub fn link_checked(&mut self, link: &Link) {
+        self.emit("link-checked", report::link_json(link));
+        if let Some(dashboard) = &mut self.dashboard {
+            dashboard.link_checked(link);
+        }
+    }
+
+    pub fn summary(&mut self, summary: Value) {
+        self.emit("summary", summary);
+    }
+}
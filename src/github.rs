@@ -0,0 +1,624 @@
+use std::collections::{HashMap, HashSet};
+use std::env;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::sync::Mutex;
+use std::time::Duration;
+
+use failure::{err_msg, Error};
+use isahc::config::Configurable;
+use isahc::http::Request;
+use isahc::{AsyncReadResponseExt, HttpClient};
+use serde_json::{json, Value};
+use tokio::sync::oneshot;
+use tokio::time::delay_for;
+
+use crate::diff::patch_added_lines;
+
+/// The default API endpoints. GitHub Actions sets `GITHUB_API_URL` and
+/// `GITHUB_GRAPHQL_URL` to the right ones for GitHub Enterprise Server.
+const API_URL: &str = "https://api.github.com";
+const GRAPHQL_URL: &str = "https://api.github.com/graphql";
+
+/// Marks the review comments we post, so we can find them again on later runs. The link
+/// the comment is about goes in the marker too.
+const MARKER: &str = "<!-- check-links:";
+
+/// A bad link to comment on.
+pub struct Annotation {
+    pub file: String,
+    pub line: usize,
+    pub link: String,
+    pub reason: String,
+}
+
+impl Annotation {
+    fn body(&self) -> String {
+        format!(
+            "{} {} -->\n**check-links**: `{}` {}",
+            MARKER, self.link, self.link, self.reason
+        )
+    }
+}
+
+/// What happened when annotating a pull request.
+pub struct Summary {
+    pub posted: usize,
+    pub resolved: usize,
+    /// Bad links that aren't on lines changed by the pull request.
+    pub outside_diff: usize,
+}
+
+/// A client for GitHub's REST and GraphQL APIs.
+pub struct Api {
+    client: HttpClient,
+    api_url: String,
+    graphql_url: String,
+    token: String,
+}
+
+impl Api {
+    pub fn new(token: String) -> Result<Self, Error> {
+        let client = HttpClient::builder()
+            .timeout(Duration::from_secs(30))
+            .build()?;
+        Ok(Api {
+            client,
+            api_url: env::var("GITHUB_API_URL").unwrap_or_else(|_| API_URL.to_string()),
+            graphql_url: env::var("GITHUB_GRAPHQL_URL").unwrap_or_else(|_| GRAPHQL_URL.to_string()),
+            token,
+        })
+    }
+
+    /// Send a request, returning the response even if it has errors in it.
+    async fn send(&self, method: &str, url: &str, body: Option<Value>) -> Result<Value, Error> {
+        let request = Request::builder()
+            .method(method)
+            .uri(url)
+            .header("Authorization", format!("Bearer {}", self.token))
+            .header("Accept", "application/vnd.github+json")
+            .header("User-Agent", "check-links")
+            .body(body.map(|body| body.to_string()).unwrap_or_default())?;
+        let mut response = self.client.send_async(request).await?;
+        let text = response.text().await?;
+        if !response.status().is_success() {
+            return Err(err_msg(format!(
+                "GitHub API request to {} failed with status {}: {}",
+                url,
+                response.status(),
+                text
+            )));
+        }
+        Ok(serde_json::from_str(&text)?)
+    }
+
+    async fn request(&self, method: &str, url: &str, body: Option<Value>) -> Result<Value, Error> {
+        let value = self.send(method, url, body).await?;
+        if let Some(errors) = value.get("errors") {
+            return Err(err_msg(format!("GitHub API request failed: {}", errors)));
+        }
+        Ok(value)
+    }
+
+    async fn graphql(&self, query: &str, variables: Value) -> Result<Value, Error> {
+        let body = json!({ "query": query, "variables": variables });
+        Ok(self.request("POST", &self.graphql_url, Some(body)).await?["data"].take())
+    }
+
+    /// Run a GraphQL query where parts of it are expected to fail, like looking up things
+    /// that may not exist. Those parts are null in the data that's returned.
+    pub async fn graphql_partial(&self, query: &str) -> Result<Value, Error> {
+        let body = json!({ "query": query });
+        let mut value = self.send("POST", &self.graphql_url, Some(body)).await?;
+        match value.get("data") {
+            Some(data) if data.is_object() => Ok(value["data"].take()),
+            _ => Err(err_msg(format!(
+                "GitHub API request failed: {}",
+                value.get("errors").unwrap_or(&Value::Null)
+            ))),
+        }
+    }
+}
+
+/// Posts review comments on pull requests.
+pub struct GitHub {
+    api: Api,
+    owner: String,
+    name: String,
+}
+
+impl GitHub {
+    /// `repo` should be in the form `owner/name`.
+    pub fn new(repo: &str, token: String) -> Result<Self, Error> {
+        let mut parts = repo.splitn(2, '/');
+        let (owner, name) = match (parts.next(), parts.next()) {
+            (Some(owner), Some(name)) if !owner.is_empty() && !name.is_empty() => (owner, name),
+            _ => {
+                return Err(err_msg(format!(
+                    "invalid repository '{}', expected OWNER/NAME",
+                    repo
+                )))
+            }
+        };
+        Ok(GitHub {
+            api: Api::new(token)?,
+            owner: owner.to_string(),
+            name: name.to_string(),
+        })
+    }
+
+    fn pull_url(&self, pr: u64) -> String {
+        format!(
+            "{}/repos/{}/{}/pulls/{}",
+            self.api.api_url, self.owner, self.name, pr
+        )
+    }
+
+    /// Get the lines added by a pull request in each file.
+    async fn added_lines(&self, pr: u64) -> Result<HashMap<String, HashSet<usize>>, Error> {
+        let mut files = HashMap::new();
+        for page in 1.. {
+            let url = format!("{}/files?per_page=100&page={}", self.pull_url(pr), page);
+            let value = self.api.request("GET", &url, None).await?;
+            let entries = value.as_array().cloned().unwrap_or_default();
+            for entry in &entries {
+                if let (Some(file), Some(patch)) =
+                    (entry["filename"].as_str(), entry["patch"].as_str())
+                {
+                    files.insert(file.to_string(), patch_added_lines(patch));
+                }
+            }
+            if entries.len() < 100 {
+                break;
+            }
+        }
+        Ok(files)
+    }
+
+    /// Get the unresolved review threads we started, by id, along with the file and link
+    /// each one is about.
+    async fn open_threads(&self, pr: u64) -> Result<Vec<(String, String, String)>, Error> {
+        let query = "query($owner: String!, $name: String!, $pr: Int!, $after: String) {
+  repository(owner: $owner, name: $name) {
+    pullRequest(number: $pr) {
+      reviewThreads(first: 100, after: $after) {
+        pageInfo { hasNextPage endCursor }
+        nodes { id isResolved comments(first: 1) { nodes { path body } } }
+      }
+    }
+  }
+}";
+        let mut threads = vec![];
+        let mut after = Value::Null;
+        loop {
+            let variables = json!({
+                "owner": self.owner,
+                "name": self.name,
+                "pr": pr,
+                "after": after,
+            });
+            let data = self.api.graphql(query, variables).await?;
+            let review_threads = &data["repository"]["pullRequest"]["reviewThreads"];
+            for thread in review_threads["nodes"].as_array().into_iter().flatten() {
+                if thread["isResolved"].as_bool().unwrap_or(true) {
+                    continue;
+                }
+                let comment = &thread["comments"]["nodes"][0];
+                let link = comment["body"].as_str().and_then(|body| {
+                    let rest = body.strip_prefix(MARKER)?;
+                    Some(rest[..rest.find("-->")?].trim().to_string())
+                });
+                if let (Some(id), Some(path), Some(link)) =
+                    (thread["id"].as_str(), comment["path"].as_str(), link)
+                {
+                    threads.push((id.to_string(), path.to_string(), link));
+                }
+            }
+            let page_info = &review_threads["pageInfo"];
+            if !page_info["hasNextPage"].as_bool().unwrap_or(false) {
+                break;
+            }
+            after = page_info["endCursor"].clone();
+        }
+        Ok(threads)
+    }
+
+    async fn resolve_thread(&self, id: &str) -> Result<(), Error> {
+        let query = "mutation($id: ID!) {
+  resolveReviewThread(input: { threadId: $id }) { thread { id } }
+}";
+        self.api.graphql(query, json!({ "id": id })).await?;
+        Ok(())
+    }
+
+    /// Comment on the bad links on lines added by a pull request, and resolve the
+    /// comments from earlier runs on links that have since been fixed.
+    pub async fn annotate(&self, pr: u64, annotations: &[Annotation]) -> Result<Summary, Error> {
+        let pull = self.api.request("GET", &self.pull_url(pr), None).await?;
+        let commit = pull["head"]["sha"]
+            .as_str()
+            .ok_or_else(|| err_msg("couldn't find the head commit of the pull request"))?
+            .to_string();
+        let added_lines = self.added_lines(pr).await?;
+        let threads = self.open_threads(pr).await?;
+
+        let mut summary = Summary {
+            posted: 0,
+            resolved: 0,
+            outside_diff: 0,
+        };
+        let mut commented = HashSet::new();
+        for (_, path, link) in &threads {
+            commented.insert((path.clone(), link.clone()));
+        }
+        for annotation in annotations {
+            let in_diff = match added_lines.get(&annotation.file) {
+                Some(lines) => lines.contains(&annotation.line),
+                None => false,
+            };
+            if !in_diff {
+                summary.outside_diff += 1;
+                continue;
+            }
+            if !commented.insert((annotation.file.clone(), annotation.link.clone())) {
+                continue;
+            }
+            let body = json!({
+                "body": annotation.body(),
+                "commit_id": commit,
+                "path": annotation.file,
+                "line": annotation.line,
+                "side": "RIGHT",
+            });
+            let url = format!("{}/comments", self.pull_url(pr));
+            self.api.request("POST", &url, Some(body)).await?;
+            summary.posted += 1;
+        }
+
+        let bad: HashSet<(&str, &str)> = annotations
+            .iter()
+            .map(|a| (&a.file[..], &a.link[..]))
+            .collect();
+        for (id, path, link) in &threads {
+            if !bad.contains(&(&path[..], &link[..])) {
+                self.resolve_thread(id).await?;
+                summary.resolved += 1;
+            }
+        }
+        Ok(summary)
+    }
+}
+
+/// Get the commit checked out in the git repository a directory is in.
+pub fn head_commit(dir: &Path) -> Option<String> {
+    let output = Command::new("git")
+        .arg("-C")
+        .arg(dir)
+        .args(["rev-parse", "HEAD"])
+        .output()
+        .ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    Some(String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
+
+/// Get the root of the git repository we're in.
+pub fn repo_root() -> Option<PathBuf> {
+    let output = Command::new("git")
+        .args(["rev-parse", "--show-toplevel"])
+        .output()
+        .ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    let root = String::from_utf8_lossy(&output.stdout);
+    Path::new(root.trim()).canonicalize().ok()
+}
+
+/// Get the path of a file relative to the root of the repository, which is how GitHub
+/// refers to files.
+pub fn repo_path(root: &Path, file: &Path) -> Option<String> {
+    let file = file.canonicalize().ok()?;
+    let path = file.strip_prefix(root).ok()?;
+    Some(path.to_string_lossy().replace('\\', "/"))
+}
+
+/// How long to wait for more links to check before sending a batch.
+const BATCH_DELAY: Duration = Duration::from_millis(50);
+
+/// The most things to look up in one query, which keeps us well under GitHub's limits
+/// on query complexity.
+const MAX_BATCH: usize = 50;
+
+/// Paths on github.com that aren't owners of repositories.
+const RESERVED_OWNERS: &[&str] = &[
+    "about",
+    "apps",
+    "collections",
+    "enterprise",
+    "explore",
+    "features",
+    "login",
+    "marketplace",
+    "notifications",
+    "orgs",
+    "pricing",
+    "search",
+    "settings",
+    "sponsors",
+    "topics",
+    "users",
+];
+
+/// Something on GitHub that a link points to, which we can look up through the API.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum Target {
+    Repo {
+        owner: String,
+        name: String,
+    },
+    /// An issue or a pull request. The two share numbers, so either kind of link works
+    /// for both.
+    Issue {
+        owner: String,
+        name: String,
+        number: u64,
+    },
+    /// A file or directory at a branch, tag, or commit.
+    Path {
+        owner: String,
+        name: String,
+        reference: String,
+        path: String,
+    },
+}
+
+impl Target {
+    /// Get what a github.com URL points to, if it's something we can look up.
+    pub fn parse(url: &str) -> Option<Self> {
+        let rest = [
+            "https://github.com/",
+            "https://www.github.com/",
+            "http://github.com/",
+        ]
+        .iter()
+        .find_map(|prefix| url.strip_prefix(prefix))?;
+        let rest = rest.split(['#', '?']).next().unwrap_or_default();
+        // Percent-encoded paths would have to be decoded to look them up.
+        if rest.contains('%') {
+            return None;
+        }
+        let segments: Vec<&str> = rest.split('/').filter(|s| !s.is_empty()).collect();
+        let (owner, name) = match segments[..] {
+            [owner, name, ..] if !RESERVED_OWNERS.contains(&owner) => {
+                (owner.to_string(), name.trim_end_matches(".git").to_string())
+            }
+            _ => return None,
+        };
+        match segments[2..] {
+            [] => Some(Target::Repo { owner, name }),
+            ["issues", number] | ["pull", number] => Some(Target::Issue {
+                owner,
+                name,
+                number: number.parse().ok()?,
+            }),
+            ["blob", reference, ref path @ ..] | ["tree", reference, ref path @ ..] => {
+                Some(Target::Path {
+                    owner,
+                    name,
+                    reference: reference.to_string(),
+                    path: path.join("/"),
+                })
+            }
+            _ => None,
+        }
+    }
+
+    /// The part of a query that looks this up, under the given alias.
+    fn query(&self, alias: &str) -> String {
+        let (owner, name, selection) = match self {
+            Target::Repo { owner, name } => (owner, name, String::from("id")),
+            Target::Issue {
+                owner,
+                name,
+                number,
+            } => (
+                owner,
+                name,
+                format!(
+                    "issueOrPullRequest(number: {}) {{ __typename \
+                     ... on Issue {{ state stateReason }} \
+                     ... on PullRequest {{ state merged }} }}",
+                    number
+                ),
+            ),
+            Target::Path {
+                owner,
+                name,
+                reference,
+                path,
+            } => {
+                let expression = if path.is_empty() {
+                    reference.clone()
+                } else {
+                    format!("{}:{}", reference, path)
+                };
+                (
+                    owner,
+                    name,
+                    format!("object(expression: {}) {{ __typename }}", json!(expression)),
+                )
+            }
+        };
+        format!(
+            "{}: repository(owner: {}, name: {}) {{ {} }}",
+            alias,
+            json!(owner),
+            json!(name),
+            selection
+        )
+    }
+
+    /// Get the node for this out of the result of its query, or `None` if it wasn't
+    /// found.
+    fn node<'a>(&self, result: &'a Value) -> Option<&'a Value> {
+        let node = match self {
+            Target::Repo { .. } => result,
+            Target::Issue { .. } => &result["issueOrPullRequest"],
+            Target::Path { .. } => &result["object"],
+        };
+        if node.is_null() {
+            None
+        } else {
+            Some(node)
+        }
+    }
+}
+
+/// Looks up the things that links to github.com point to through the GraphQL API,
+/// batching the lookups into as few queries as we can.
+pub struct GitHubLinks {
+    api: Api,
+    /// What we've found out about each target so far. `None` means it wasn't found.
+    results: Mutex<HashMap<Target, Option<Value>>>,
+    /// The lookups waiting to go out in the next batch.
+    pending: Mutex<Vec<(Target, oneshot::Sender<Option<Value>>)>>,
+    /// Why we gave up on the API, if we did.
+    failure: Mutex<Option<String>>,
+}
+
+impl GitHubLinks {
+    pub fn new(token: String) -> Result<Self, Error> {
+        Ok(GitHubLinks {
+            api: Api::new(token)?,
+            results: Mutex::new(HashMap::new()),
+            pending: Mutex::new(vec![]),
+            failure: Mutex::new(None),
+        })
+    }
+
+    /// Why we stopped using the API partway through, if we did. Links are checked over
+    /// HTTP from then on.
+    pub fn failure(&self) -> Option<String> {
+        self.failure.lock().unwrap().clone()
+    }
+
+    /// Look up a target. Returns its node if it exists, or `None` if it doesn't, or if
+    /// we couldn't tell because the API isn't working.
+    pub async fn lookup(&self, target: Target) -> Option<Value> {
+        if self.failure.lock().unwrap().is_some() {
+            return None;
+        }
+        if let Some(result) = self.results.lock().unwrap().get(&target) {
+            return result.clone();
+        }
+        let (tx, rx) = oneshot::channel();
+        let (leader, full) = {
+            let mut pending = self.pending.lock().unwrap();
+            pending.push((target, tx));
+            let full = if pending.len() >= MAX_BATCH {
+                Some(pending.drain(..).collect::<Vec<_>>())
+            } else {
+                None
+            };
+            (pending.len() == 1, full)
+        };
+        if let Some(batch) = full {
+            self.flush(batch).await;
+        } else if leader {
+            // Whoever starts a batch waits a moment for others to join it, then sends it
+            // with everything that's come in.
+            delay_for(BATCH_DELAY).await;
+            let batch: Vec<_> = self.pending.lock().unwrap().drain(..).collect();
+            if !batch.is_empty() {
+                self.flush(batch).await;
+            }
+        }
+        rx.await.ok().flatten()
+    }
+
+    async fn flush(&self, batch: Vec<(Target, oneshot::Sender<Option<Value>>)>) {
+        let query = format!(
+            "query {{ {} }}",
+            batch
+                .iter()
+                .enumerate()
+                .map(|(i, (target, _))| target.query(&format!("t{}", i)))
+                .collect::<Vec<_>>()
+                .join(" ")
+        );
+        let data = match self.api.graphql_partial(&query).await {
+            Ok(data) => data,
+            Err(e) => {
+                // The senders are dropped, so everyone waiting falls back to HTTP.
+                *self.failure.lock().unwrap() = Some(e.to_string());
+                return;
+            }
+        };
+        let mut results = self.results.lock().unwrap();
+        for (i, (target, tx)) in batch.into_iter().enumerate() {
+            let node = target.node(&data[format!("t{}", i)]).cloned();
+            results.insert(target, node.clone());
+            let _ = tx.send(node);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn repo(owner: &str, name: &str) -> Target {
+        Target::Repo {
+            owner: owner.to_string(),
+            name: name.to_string(),
+        }
+    }
+
+    #[test]
+    fn parse_repos() {
+        assert_eq!(
+            Target::parse("https://github.com/epwalsh/check-links"),
+            Some(repo("epwalsh", "check-links"))
+        );
+        assert_eq!(
+            Target::parse("https://www.github.com/epwalsh/check-links.git/#readme"),
+            Some(repo("epwalsh", "check-links"))
+        );
+    }
+
+    #[test]
+    fn parse_issues_and_paths() {
+        assert_eq!(
+            Target::parse("https://github.com/rust-lang/rust/pull/1234?diff=split"),
+            Some(Target::Issue {
+                owner: String::from("rust-lang"),
+                name: String::from("rust"),
+                number: 1234,
+            })
+        );
+        assert_eq!(
+            Target::parse("http://github.com/a/b/blob/main/docs/intro.md#usage"),
+            Some(Target::Path {
+                owner: String::from("a"),
+                name: String::from("b"),
+                reference: String::from("main"),
+                path: String::from("docs/intro.md"),
+            })
+        );
+    }
+
+    #[test]
+    fn parse_other_links() {
+        assert_eq!(Target::parse("https://github.com/settings/profile"), None);
+        assert_eq!(Target::parse("https://github.com/epwalsh"), None);
+        assert_eq!(Target::parse("https://github.com/a/b/issues/new"), None);
+        assert_eq!(Target::parse("https://github.com/a/b/wiki/Home"), None);
+        assert_eq!(
+            Target::parse("https://github.com/a/b/blob/main/with%20space.md"),
+            None
+        );
+        assert_eq!(Target::parse("https://gitlab.com/a/b"), None);
+    }
+}
@@ -0,0 +1,181 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::path::Path;
+use std::process::Command;
+
+/// When a link first showed up in a repository, according to its git history.
+#[derive(Clone)]
+pub enum FirstSeen {
+    /// Added by a commit, which may have been released in a tag.
+    Commit {
+        commit: String,
+        date: String,
+        tag: Option<String>,
+    },
+    /// Already there at the revision the history starts from.
+    Before(String),
+    /// Not in any commit yet.
+    Uncommitted,
+    /// In a commit, but not one the pickaxe could find it being added in, like when
+    /// it's in a file that was copied.
+    Unknown,
+}
+
+impl fmt::Display for FirstSeen {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FirstSeen::Commit { commit, date, tag } => {
+                write!(f, "first appeared in {} on {}", commit, date)?;
+                match tag {
+                    Some(tag) => write!(f, ", released in {}", tag),
+                    None => write!(f, ", not released yet"),
+                }
+            }
+            FirstSeen::Before(since) => write!(f, "already there in {}", since),
+            FirstSeen::Uncommitted => write!(f, "not committed yet"),
+            FirstSeen::Unknown => write!(f, "couldn't find when it was added"),
+        }
+    }
+}
+
+/// Digs through the git history for when links were added, remembering what it's found
+/// since the same link is often in several places.
+pub struct History {
+    /// The revision to start from, like a tag. Defaults to the first commit.
+    since: Option<String>,
+    first_seen: HashMap<String, Option<FirstSeen>>,
+}
+
+impl History {
+    pub fn new(since: Option<String>) -> Self {
+        History {
+            since,
+            first_seen: HashMap::new(),
+        }
+    }
+
+    /// Find when a link first appeared anywhere in the repository the file is in, so
+    /// links that were moved between files are traced back to where they came from.
+    /// The link should be as it's written in the file, since that's what's in the
+    /// history. Returns `None` if the file isn't in a git repository or git isn't
+    /// available.
+    pub fn first_seen(&mut self, file: &Path, link: &str) -> Option<FirstSeen> {
+        if let Some(first_seen) = self.first_seen.get(link) {
+            return first_seen.clone();
+        }
+        let dir = match file.parent() {
+            Some(dir) if dir != Path::new("") => dir,
+            _ => Path::new("."),
+        };
+        let first_seen = self.search(dir, link);
+        self.first_seen.insert(link.to_string(), first_seen.clone());
+        first_seen
+    }
+
+    fn search(&self, dir: &Path, link: &str) -> Option<FirstSeen> {
+        if let Some(since) = &self.since {
+            // `git grep` exits with 1 when there's no match, and 128 when something's
+            // wrong, like a revision that doesn't exist.
+            let status = git(dir)
+                .args(["grep", "--quiet", "--fixed-strings", "-e", link, since])
+                .status()
+                .ok()?;
+            match status.code() {
+                Some(0) => return Some(FirstSeen::Before(since.clone())),
+                Some(1) => {}
+                _ => return None,
+            }
+        }
+        // The pickaxe finds the commits that change how many times the link appears,
+        // the first of which added it.
+        let mut log = git(dir);
+        log.args([
+            "log",
+            "--reverse",
+            "--format=%H %ad",
+            "--date=short",
+            "-S",
+            link,
+        ]);
+        if let Some(since) = &self.since {
+            log.arg(format!("{}..HEAD", since));
+        }
+        let output = log.output().ok()?;
+        if !output.status.success() {
+            return None;
+        }
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        let (commit, date) = match stdout.lines().next() {
+            Some(line) => line.split_once(' ')?,
+            // It may not have been committed yet, or have been added in a way the pickaxe
+            // doesn't see.
+            None => {
+                let status = git(dir)
+                    .args(["grep", "--quiet", "--fixed-strings", "-e", link, "HEAD"])
+                    .status()
+                    .ok()?;
+                return match status.code() {
+                    Some(0) => Some(FirstSeen::Unknown),
+                    Some(1) => Some(FirstSeen::Uncommitted),
+                    _ => None,
+                };
+            }
+        };
+        Some(FirstSeen::Commit {
+            commit: commit[..commit.len().min(8)].to_string(),
+            date: date.to_string(),
+            tag: first_tag(dir, commit),
+        })
+    }
+}
+
+fn git(dir: &Path) -> Command {
+    let mut command = Command::new("git");
+    command.arg("-C").arg(dir);
+    command
+}
+
+/// Get the first tag that contains a commit, if it's been tagged yet.
+fn first_tag(dir: &Path, commit: &str) -> Option<String> {
+    let output = git(dir)
+        .args(["describe", "--contains", "--tags", commit])
+        .output()
+        .ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    tag_of_described(&String::from_utf8_lossy(&output.stdout))
+}
+
+/// Get the tag from how `git describe --contains` names a commit, like `v1.2.0~3` for
+/// three commits before the tag.
+fn tag_of_described(described: &str) -> Option<String> {
+    let tag = described.trim().split(['~', '^']).next()?;
+    if tag.is_empty() {
+        None
+    } else {
+        Some(tag.to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tags_are_read_from_described_commits() {
+        assert_eq!(tag_of_described("v1.2.0\n").as_deref(), Some("v1.2.0"));
+        assert_eq!(tag_of_described("v1.2.0~3\n").as_deref(), Some("v1.2.0"));
+        assert_eq!(
+            tag_of_described("v1.2.0~3^2~1\n").as_deref(),
+            Some("v1.2.0")
+        );
+        assert_eq!(tag_of_described("v1.2.0^0").as_deref(), Some("v1.2.0"));
+    }
+
+    #[test]
+    fn nothing_described_has_no_tag() {
+        assert_eq!(tag_of_described(""), None);
+        assert_eq!(tag_of_described("\n"), None);
+    }
+}
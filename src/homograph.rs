@@ -0,0 +1,184 @@
+/// Scripts whose letters are easily mistaken for each other in hostnames.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Script {
+    Latin,
+    Greek,
+    Cyrillic,
+    Armenian,
+    Cherokee,
+}
+
+impl Script {
+    fn of(c: char) -> Option<Self> {
+        match c as u32 {
+            0x61..=0x7a | 0x41..=0x5a | 0xc0..=0x24f | 0x1e00..=0x1eff => Some(Script::Latin),
+            0x370..=0x3ff | 0x1f00..=0x1fff => Some(Script::Greek),
+            0x400..=0x52f | 0x1c80..=0x1c8f | 0x2de0..=0x2dff | 0xa640..=0xa69f => {
+                Some(Script::Cyrillic)
+            }
+            0x530..=0x58f => Some(Script::Armenian),
+            0x13a0..=0x13ff | 0xab70..=0xabbf => Some(Script::Cherokee),
+            _ => None,
+        }
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            Script::Latin => "Latin",
+            Script::Greek => "Greek",
+            Script::Cyrillic => "Cyrillic",
+            Script::Armenian => "Armenian",
+            Script::Cherokee => "Cherokee",
+        }
+    }
+}
+
+/// Get the Latin letter a character from another script looks like, if it looks like
+/// one.
+fn latin_lookalike(c: char) -> Option<char> {
+    Some(match c {
+        // Cyrillic
+        'а' => 'a',
+        'с' => 'c',
+        'ԁ' => 'd',
+        'е' => 'e',
+        'һ' => 'h',
+        'і' => 'i',
+        'ј' => 'j',
+        'ӏ' => 'l',
+        'о' => 'o',
+        'р' => 'p',
+        'ԛ' => 'q',
+        'ѕ' => 's',
+        'у' => 'y',
+        'ԝ' => 'w',
+        'х' => 'x',
+        // Greek
+        'α' => 'a',
+        'ι' => 'i',
+        'κ' => 'k',
+        'ν' => 'v',
+        'ο' => 'o',
+        'ρ' => 'p',
+        'υ' => 'u',
+        // Armenian
+        'օ' => 'o',
+        'ս' => 'u',
+        'ց' => 'g',
+        _ => return None,
+    })
+}
+
+/// Get the host of a URL as it's written, which may not be ASCII.
+fn host(url: &str) -> Option<&str> {
+    let rest = &url[url.find("://")? + 3..];
+    let authority = rest.split(['/', '?', '#']).next()?;
+    let host = authority.rsplit('@').next()?;
+    // IPv6 addresses can't be homographs.
+    if host.starts_with('[') {
+        return None;
+    }
+    Some(host.split(':').next().unwrap_or(host))
+}
+
+/// Check if a URL's hostname could be passing itself off as another, by mixing letters
+/// from several scripts in one label, like a Cyrillic "а" in "pаypal.com", or by
+/// spelling a label entirely in letters that look like Latin ones. Returns what's wrong
+/// with it, along with the hostname's punycode form, which is what browsers show when
+/// they don't trust a hostname.
+pub fn check(url: &str) -> Option<String> {
+    let host = host(url)?;
+    // Hostnames can also be written in punycode already.
+    if host.is_ascii() && !host.to_ascii_lowercase().contains("xn--") {
+        return None;
+    }
+    let (unicode, result) = idna::domain_to_unicode(host);
+    if result.is_err() {
+        return None;
+    }
+    let punycode = idna::domain_to_ascii(&unicode).ok()?;
+    let labels: Vec<&str> = unicode.split('.').collect();
+    let has_latin_label = labels.iter().any(|label| {
+        label
+            .chars()
+            .filter_map(Script::of)
+            .all(|script| script == Script::Latin)
+            && label.chars().any(|c| c.is_alphabetic())
+    });
+    for label in &labels {
+        let mut scripts: Vec<Script> = vec![];
+        for script in label.chars().filter_map(Script::of) {
+            if !scripts.contains(&script) {
+                scripts.push(script);
+            }
+        }
+        if scripts.len() > 1 {
+            let names: Vec<&str> = scripts.iter().map(|script| script.name()).collect();
+            return Some(format!(
+                "hostname mixes {} characters: {} ({})",
+                names.join(" and "),
+                unicode,
+                punycode
+            ));
+        }
+        // A label in another script that could pass for Latin, in a hostname that's
+        // otherwise Latin, like "раураl.com" spelled in Cyrillic.
+        let letters: Vec<char> = label.chars().filter(|c| c.is_alphabetic()).collect();
+        if let [script] = scripts[..] {
+            if script != Script::Latin
+                && has_latin_label
+                && letters.iter().all(|&c| latin_lookalike(c).is_some())
+            {
+                let lookalike: String = label
+                    .chars()
+                    .map(|c| latin_lookalike(c).unwrap_or(c))
+                    .collect();
+                return Some(format!(
+                    "hostname is spelled in {} characters that look like \"{}\": {} ({})",
+                    script.name(),
+                    lookalike,
+                    unicode,
+                    punycode
+                ));
+            }
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn mixed_scripts() {
+        // A Cyrillic "а" in a Latin label.
+        assert_eq!(
+            check("https://p\u{430}ypal.com/login").as_deref(),
+            Some("hostname mixes Latin and Cyrillic characters: p\u{430}ypal.com (xn--pypal-4ve.com)")
+        );
+        // The same, already in punycode.
+        assert!(check("https://xn--pypal-4ve.com/").is_some());
+    }
+
+    #[test]
+    fn lookalike_labels() {
+        // "paypal" spelled entirely in Cyrillic.
+        let reason = check("https://\u{440}\u{430}\u{443}\u{440}\u{430}\u{4cf}.com").unwrap();
+        assert!(reason
+            .starts_with("hostname is spelled in Cyrillic characters that look like \"paypal\""));
+    }
+
+    #[test]
+    fn honest_hostnames() {
+        assert_eq!(check("https://example.com/a"), None);
+        assert_eq!(check("https://user@example.com:8080/"), None);
+        assert_eq!(check("http://[::1]/"), None);
+        // Hostnames entirely in another script, with nothing Latin to pass for.
+        assert_eq!(
+            check("https://\u{43f}\u{440}\u{438}\u{43c}\u{435}\u{440}.\u{440}\u{444}"),
+            None
+        );
+        assert_eq!(check("https://bücher.de"), None);
+    }
+}
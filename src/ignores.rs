@@ -0,0 +1,249 @@
+use std::fmt;
+use std::str::FromStr;
+
+use regex::Regex;
+use serde::de::{self, Deserializer};
+use serde::{Deserialize, Serialize, Serializer};
+
+use crate::cache;
+use crate::link::{LinkStatus, Reason};
+
+/// A day on the calendar, like `2025-12-31`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Date {
+    /// Days since 1970-01-01.
+    days: i64,
+}
+
+impl Date {
+    pub fn today() -> Self {
+        Date::from_timestamp(cache::now())
+    }
+
+    /// The day a Unix timestamp falls on, in UTC.
+    pub fn from_timestamp(secs: u64) -> Self {
+        Date {
+            days: (secs / 86400) as i64,
+        }
+    }
+
+    /// The Unix timestamp of the start of the day, in UTC.
+    pub fn timestamp(self) -> u64 {
+        self.days.max(0) as u64 * 86400
+    }
+
+    /// Split the date into year, month, and day.
+    fn civil(self) -> (i64, u32, u32) {
+        // From Howard Hinnant's `civil_from_days`.
+        let z = self.days + 719_468;
+        let era = z.div_euclid(146_097);
+        let doe = z.rem_euclid(146_097);
+        let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146_096) / 365;
+        let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+        let mp = (5 * doy + 2) / 153;
+        let day = (doy - (153 * mp + 2) / 5 + 1) as u32;
+        let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+        let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+        (year, month, day)
+    }
+
+    fn from_civil(year: i64, month: u32, day: u32) -> Self {
+        // From Howard Hinnant's `days_from_civil`.
+        let year = if month <= 2 { year - 1 } else { year };
+        let era = year.div_euclid(400);
+        let yoe = year.rem_euclid(400);
+        let mp = (month as i64 + 9) % 12;
+        let doy = (153 * mp + 2) / 5 + day as i64 - 1;
+        let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+        Date {
+            days: era * 146_097 + doe - 719_468,
+        }
+    }
+}
+
+impl FromStr for Date {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || format!("invalid date '{}', expected YYYY-MM-DD", s);
+        let mut parts = s.splitn(3, '-');
+        let mut next = || parts.next().and_then(|part| part.parse::<i64>().ok());
+        let (year, month, day) = match (next(), next(), next()) {
+            (Some(year), Some(month), Some(day)) => (year, month, day),
+            _ => return Err(invalid()),
+        };
+        if !(1..=12).contains(&month) || !(1..=31).contains(&day) {
+            return Err(invalid());
+        }
+        let date = Date::from_civil(year, month as u32, day as u32);
+        // Days past the end of the month, like February 30th, roll over.
+        if date.civil() != (year, month as u32, day as u32) {
+            return Err(invalid());
+        }
+        Ok(date)
+    }
+}
+
+impl fmt::Display for Date {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let (year, month, day) = self.civil();
+        write!(f, "{:04}-{:02}-{:02}", year, month, day)
+    }
+}
+
+impl<'de> Deserialize<'de> for Date {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        // TOML has dates of its own, so `until = 2025-12-31` works without quotes.
+        #[derive(Deserialize)]
+        #[serde(untagged)]
+        enum Repr {
+            String(String),
+            Toml(toml::value::Datetime),
+        }
+        let s = match Repr::deserialize(deserializer)? {
+            Repr::String(s) => s,
+            Repr::Toml(datetime) => datetime.to_string(),
+        };
+        s.parse().map_err(de::Error::custom)
+    }
+}
+
+impl Serialize for Date {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serializer.serialize_str(&self.to_string())
+    }
+}
+
+/// A link that shouldn't be checked, maybe only until some date, so that ignoring a
+/// link during an outage doesn't mean forgetting about it.
+#[derive(Debug, Clone, Default)]
+pub struct Ignore {
+    /// The last day the link is ignored. Ignores without one never expire.
+    pub until: Option<Date>,
+    pub reason: Option<String>,
+}
+
+impl Ignore {
+    pub fn has_expired(&self) -> bool {
+        self.until.is_some_and(|until| until < Date::today())
+    }
+
+    /// The status of a link that's still ignored.
+    pub fn status(&self) -> LinkStatus {
+        LinkStatus::Ignored(Reason::Other(match (&self.reason, self.until) {
+            (Some(reason), Some(until)) => format!("{} (until {})", reason, until),
+            (Some(reason), None) => reason.clone(),
+            (None, Some(until)) => format!("until {}", until),
+            (None, None) => String::from("no reason given"),
+        }))
+    }
+
+    /// The status of a link whose ignore has expired, now that it's been checked again.
+    /// Even a link that works gets a warning, so the ignore gets cleaned up.
+    pub fn expired_status(&self, status: LinkStatus) -> LinkStatus {
+        let lapsed = match (&self.reason, self.until) {
+            (Some(reason), Some(until)) => {
+                format!("ignore for \"{}\" expired on {}", reason, until)
+            }
+            (_, until) => format!("ignore expired on {}", until.unwrap_or_else(Date::today)),
+        };
+        match status {
+            LinkStatus::Reachable => LinkStatus::Questionable(Reason::Other(format!(
+                "{}, and the link works now",
+                lapsed
+            ))),
+            LinkStatus::Questionable(reason) => {
+                LinkStatus::Questionable(Reason::Noted(Box::new(reason), lapsed))
+            }
+            LinkStatus::Unreachable(reason) => LinkStatus::Unreachable(Some(Reason::Noted(
+                Box::new(reason.unwrap_or_else(Reason::unreachable)),
+                lapsed,
+            ))),
+            status => status,
+        }
+    }
+}
+
+/// Parse a `check-links: ignore` directive on a line, like
+/// `<!-- check-links: ignore until=2025-12-31 reason="vendor outage" -->`. A directive
+/// applies to the links on its own line, or to the links on the next line if there
+/// aren't any on its own.
+pub fn directive(line: &str) -> Option<Ignore> {
+    lazy_static! {
+        static ref DIRECTIVE_RE: Regex = Regex::new(r"check-links:\s*ignore\b(.*)").unwrap();
+        static ref ARG_RE: Regex = Regex::new(r#"(\w+)=(?:"([^"]*)"|([^\s>"]+))"#).unwrap();
+    }
+    if !line.contains("check-links:") {
+        return None;
+    }
+    let caps = DIRECTIVE_RE.captures(line)?;
+    let args = caps.get(1).map_or("", |args| args.as_str());
+    let mut ignore = Ignore::default();
+    for caps in ARG_RE.captures_iter(args) {
+        let value = caps
+            .get(2)
+            .or_else(|| caps.get(3))
+            .map_or("", |v| v.as_str());
+        match &caps[1] {
+            // A date that doesn't parse could be meant to have passed already, so the
+            // links are checked rather than ignored forever.
+            "until" => ignore.until = Some(value.parse().ok()?),
+            "reason" => ignore.reason = Some(value.to_string()),
+            _ => {}
+        }
+    }
+    Some(ignore)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_dates() {
+        let date: Date = "2025-12-31".parse().unwrap();
+        assert_eq!(date.to_string(), "2025-12-31");
+        assert_eq!("1970-01-01".parse::<Date>().unwrap().timestamp(), 0);
+        assert_eq!(
+            "2024-02-29".parse::<Date>().unwrap().to_string(),
+            "2024-02-29"
+        );
+        assert!("2025-02-29".parse::<Date>().is_err());
+        assert!("2025-13-01".parse::<Date>().is_err());
+        assert!("2025-12".parse::<Date>().is_err());
+        assert!("tomorrow".parse::<Date>().is_err());
+    }
+
+    #[test]
+    fn civil_round_trip() {
+        // Every day for a few centuries, across leap years and the turn of centuries.
+        for days in -50_000..100_000 {
+            let date = Date { days };
+            let (year, month, day) = date.civil();
+            assert_eq!(Date::from_civil(year, month, day), date);
+        }
+        assert_eq!(Date::from_timestamp(951_782_400).to_string(), "2000-02-29");
+        assert_eq!(Date::from_timestamp(951_868_799).to_string(), "2000-02-29");
+    }
+
+    #[test]
+    fn parse_directives() {
+        let ignore =
+            directive(r#"<!-- check-links: ignore until=2025-12-31 reason="vendor outage" -->"#)
+                .unwrap();
+        assert_eq!(ignore.until, Some("2025-12-31".parse().unwrap()));
+        assert_eq!(ignore.reason.as_deref(), Some("vendor outage"));
+        let ignore = directive("[a](b.md) <!-- check-links: ignore -->").unwrap();
+        assert_eq!((ignore.until, ignore.reason), (None, None));
+        // A date that doesn't parse doesn't ignore anything.
+        assert!(directive("<!-- check-links: ignore until=someday -->").is_none());
+        assert!(directive("<!-- check-links: ignored -->").is_none());
+        assert!(directive("[a](b.md)").is_none());
+    }
+}
@@ -0,0 +1,68 @@
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use regex::Regex;
+
+use crate::link::normalize_path;
+
+/// Which Markdown files are included in others with mdBook's `{{#include file.md}}`.
+/// An included fragment is rendered as part of the chapter that includes it, so its
+/// relative links resolve from the chapter's directory rather than its own.
+#[derive(Default)]
+pub struct Includes {
+    /// The files that include each fragment.
+    includers: HashMap<PathBuf, Vec<PathBuf>>,
+}
+
+impl Includes {
+    /// Find the includes in the Markdown files among the files that are searched.
+    pub fn scan(files: Vec<PathBuf>) -> Self {
+        lazy_static! {
+            // The path may be followed by line numbers or an anchor, like
+            // `{{#include file.rs:2:10}}` or `{{#include file.rs:example}}`.
+            static ref INCLUDE_RE: Regex =
+                Regex::new(r"\{\{#(?:include|rustdoc_include)\s+([^}:\s]+)[^}]*\}\}").unwrap();
+        }
+        let mut includers: HashMap<PathBuf, Vec<PathBuf>> = HashMap::new();
+        let files = files
+            .into_iter()
+            .filter(|path| path.extension().is_some_and(|ext| ext == "md"));
+        for file in files {
+            let contents = match fs::read_to_string(&file) {
+                Ok(contents) => contents,
+                Err(_) => continue,
+            };
+            let dir = file.parent().unwrap_or_else(|| Path::new("./"));
+            for caps in INCLUDE_RE.captures_iter(&contents) {
+                let fragment = normalize_path(&dir.join(&caps[1]));
+                includers
+                    .entry(fragment)
+                    .or_default()
+                    .push(normalize_path(&file));
+            }
+        }
+        Includes { includers }
+    }
+
+    /// Get the chapters a file is rendered in: the files that include it, or the files
+    /// that include those if they're fragments too. Files that aren't included anywhere
+    /// have no chapters other than themselves, so this is empty for them.
+    pub fn chapters(&self, file: &Path) -> Vec<PathBuf> {
+        let mut chapters = vec![];
+        let mut seen = HashSet::new();
+        let mut stack = vec![normalize_path(file)];
+        while let Some(file) = stack.pop() {
+            if !seen.insert(file.clone()) {
+                continue;
+            }
+            match self.includers.get(&file) {
+                Some(includers) => stack.extend(includers.iter().cloned()),
+                None if seen.len() > 1 => chapters.push(file),
+                None => {}
+            }
+        }
+        chapters.sort();
+        chapters
+    }
+}
@@ -0,0 +1,55 @@
+use std::fs::File;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+/// Pointer files are small text files, so anything bigger is real content.
+const MAX_POINTER_SIZE: u64 = 1024;
+
+const POINTER_VERSION: &str = "version https://git-lfs.github.com/spec/v1";
+
+/// Get the directory Git LFS keeps its objects in, if we're in a git repository.
+pub fn objects_dir() -> Option<PathBuf> {
+    let output = Command::new("git")
+        .args(["rev-parse", "--git-common-dir"])
+        .output()
+        .ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    let git_dir = String::from_utf8_lossy(&output.stdout);
+    Some(Path::new(git_dir.trim()).join("lfs").join("objects"))
+}
+
+/// Get the object id of the Git LFS object a file is a pointer to, if it's a pointer
+/// rather than the object's content, which is what's left in place of the content when
+/// the object hasn't been fetched.
+pub fn pointer_oid(path: &Path) -> Option<String> {
+    let file = File::open(path).ok()?;
+    if file.metadata().ok()?.len() > MAX_POINTER_SIZE {
+        return None;
+    }
+    let mut contents = String::new();
+    file.take(MAX_POINTER_SIZE)
+        .read_to_string(&mut contents)
+        .ok()?;
+    let mut lines = contents.lines();
+    if lines.next()? != POINTER_VERSION {
+        return None;
+    }
+    lines
+        .find_map(|line| line.strip_prefix("oid sha256:"))
+        .map(|oid| oid.trim().to_string())
+}
+
+/// Check if an object is in the local LFS store, meaning it's been fetched but not
+/// checked out.
+pub fn has_object(objects_dir: &Path, oid: &str) -> bool {
+    oid.len() > 4
+        && oid.is_ascii()
+        && objects_dir
+            .join(&oid[..2])
+            .join(&oid[2..4])
+            .join(oid)
+            .exists()
+}
@@ -1,49 +1,321 @@
 use std::cmp::Ordering;
 use std::fmt;
-use std::path::{Path, PathBuf};
+use std::fs::File;
+use std::io::{BufRead, BufReader};
+use std::mem;
+use std::path::{Component, Path, PathBuf};
 use std::sync::Arc;
+use std::time::{Duration, Instant, SystemTime};
 
 use grep_regex::{Error, RegexMatcherBuilder};
 use grep_searcher::sinks::UTF8;
 use grep_searcher::Searcher;
+use isahc::error::ErrorKind;
+use isahc::http::{Method, Request, Response, StatusCode, Uri};
+use isahc::{AsyncBody, ResponseExt};
 use regex::Regex;
+use tokio::time::delay_for;
+
+use crate::anchors::AnchorIndex;
+use crate::blame::Blame;
+use crate::budget;
+use crate::checker::Checker;
+use crate::config::{Config, ContentAssertion, HeaderRule};
+use crate::crates_io;
+use crate::github::Target;
+use crate::ignores::Ignore;
+use crate::lfs;
+use crate::lint;
+use crate::risk::Risk;
+use crate::submodules::Submodules;
+use crate::tags::Tag;
+
+/// The number of times we'll honor a `Retry-After` header for a single link before
+/// giving up and reporting the status code we got.
+const MAX_RETRY_AFTER_ATTEMPTS: usize = 3;
+
+/// How long to wait before sending a GET alongside the HEAD request to a flaky host.
+const RACE_DELAY: Duration = Duration::from_millis(250);
+
+/// Hosts of URL shorteners, whose links are followed to see where they go. More can be
+/// added in the config.
+const SHORTENERS: &[&str] = &[
+    "bit.ly",
+    "buff.ly",
+    "goo.gl",
+    "is.gd",
+    "lnkd.in",
+    "ow.ly",
+    "rebrand.ly",
+    "t.co",
+    "t.ly",
+    "tinyurl.com",
+];
+
+/// The most redirects to follow from a link to a URL shortener.
+const MAX_EXPANSION_HOPS: usize = 10;
 
 pub struct Link {
     pub file: PathBuf,
     pub lnum: usize,
+    /// The column the link starts at on its line, counting characters from 1, if we know
+    /// where it is.
+    pub col: Option<usize>,
+    /// The column just past the end of the link as it's written, which can be longer or
+    /// shorter than `raw`, like when escapes were removed.
+    pub end_col: Option<usize>,
     pub raw: String,
     pub kind: LinkKind,
+    /// Whether the link is to a resource embedded in the document, like an image or a
+    /// script, rather than a page that's navigated to.
+    pub embedded: bool,
+    /// Whether the link is a wiki link, like `[[Page Name]]`, which is written as the name
+    /// of the page it's to rather than a path.
+    pub wiki: bool,
+    /// Whether the link is a protocol-relative URL, like `//example.com/page`, which we
+    /// check as an HTTP link.
+    pub protocol_relative: bool,
     pub status: Option<LinkStatus>,
+    /// The response we got when checking an HTTP link.
+    pub response: Option<ResponseInfo>,
+    /// How likely the link is to break in the future, if that was assessed.
+    pub risk: Option<Risk>,
+    /// Who last changed the line the link is on, if we looked it up.
+    pub blame: Option<Blame>,
+    /// Why the request for an HTTP link failed, if it did without a response.
+    pub request_error: Option<RequestError>,
+    /// The status of the link in each environment, if it's to a page deployed to several.
+    pub environments: Vec<(String, LinkStatus)>,
+    /// Which of the links on its line the link is, to point it out along with problems.
+    pub line_position: Option<LinePosition>,
+    /// How the link is ignored by a directive next to it, if it is.
+    pub ignore: Option<Ignore>,
+    /// Where a link to a URL shortener ends up, if it's been followed there.
+    pub expanded: Option<String>,
+    /// What's wrong with the link's text, if it's also used for links to somewhere else
+    /// in the same document.
+    pub text_conflict: Option<String>,
+    /// The URL the link was checked at, if that isn't how it's written, like when it's
+    /// protocol-relative or a rewrite rule in the config changed it.
+    pub rewritten: Option<String>,
+    /// Where the link was found and what it's for.
+    pub tags: Vec<Tag>,
+    /// The heading of the section the link is in, if it's in a Markdown file.
+    pub heading: Option<String>,
 }
 
 pub enum LinkKind {
     Local,
     Http,
+    /// A `data:` URI, which holds its content inline.
+    Data,
+    /// A `javascript:` link, which runs a script instead of going anywhere.
+    Script,
+}
+
+impl LinkKind {
+    pub fn of(raw: &str) -> Self {
+        let scheme = raw.split(':').next().unwrap_or_default().to_lowercase();
+        if raw.starts_with("http") || raw.starts_with("//") {
+            LinkKind::Http
+        } else if scheme == "data" {
+            LinkKind::Data
+        } else if scheme == "javascript" {
+            LinkKind::Script
+        } else {
+            LinkKind::Local
+        }
+    }
+}
+
+/// Where a link is among the links on its line.
+pub struct LinePosition {
+    /// Which of the links on the line this is, counting from 1.
+    pub index: usize,
+    /// How many links are on the line.
+    pub count: usize,
+}
+
+/// Details of the response to an HTTP link.
+pub struct ResponseInfo {
+    pub status: u16,
+    /// Where the server redirected us to, if it did.
+    pub location: Option<String>,
+    pub last_modified: Option<SystemTime>,
+    pub etag: Option<String>,
+    /// How long the server took to start responding.
+    pub latency: Option<Duration>,
+}
+
+impl ResponseInfo {
+    fn new(response: &Response<AsyncBody>) -> Self {
+        let header = |name: &str| {
+            response
+                .headers()
+                .get(name)
+                .and_then(|value| value.to_str().ok())
+                .map(String::from)
+        };
+        ResponseInfo {
+            status: response.status().as_u16(),
+            location: header("location"),
+            last_modified: header("last-modified")
+                .and_then(|date| httpdate::parse_http_date(&date).ok()),
+            etag: header("etag"),
+            latency: response
+                .metrics()
+                .map(|metrics| metrics.transfer_start_time()),
+        }
+    }
+}
+
+/// Check a response against the header rules for its URL, returning the first violation.
+fn check_headers(url: &str, response: &Response<AsyncBody>, config: &Config) -> Option<String> {
+    config
+        .header_rules
+        .iter()
+        .filter(|rule| rule.url.is_match(url))
+        .find_map(|rule: &HeaderRule| {
+            let value = response
+                .headers()
+                .get(&rule.name[..])
+                .and_then(|value| value.to_str().ok());
+            rule.check(value).err()
+        })
+}
+
+/// The ways a request can fail without getting a response.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum RequestError {
+    Timeout,
+    NameResolution,
+    Connect,
+    Other,
+}
+
+impl From<&ErrorKind> for RequestError {
+    fn from(kind: &ErrorKind) -> Self {
+        match kind {
+            ErrorKind::Timeout => RequestError::Timeout,
+            ErrorKind::NameResolution => RequestError::NameResolution,
+            ErrorKind::ConnectionFailed => RequestError::Connect,
+            _ => RequestError::Other,
+        }
+    }
 }
 
 pub enum LinkStatus {
     Reachable,
-    Questionable(String),
-    Unreachable(Option<String>),
+    Questionable(Reason),
+    Unreachable(Option<Reason>),
+    Skipped(Reason),
+    /// A bad link that failed in a way we were told to ignore.
+    Ignored(Reason),
+}
+
+/// Why a link is bad, or wasn't checked. The reasons that need telling apart, to
+/// translate them or to sum up what went wrong in a run, have their own variants.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Reason {
+    /// The server responded with a status code that isn't a success.
+    Status(u16),
+    Timeout,
+    /// Requests to the host kept failing, so we stopped sending them.
+    HostUnhealthy,
+    /// The section a link points to isn't there. The file it was looked for in is
+    /// given if it isn't the one the link points to.
+    MissingSection {
+        section: String,
+        file: Option<PathBuf>,
+    },
+    /// A link to our own published docs with no file where it should be built from.
+    NotInLocalDocs(PathBuf),
+    /// Another reason along with a note about it, like where it applies.
+    Noted(Box<Reason>, String),
+    /// Why a link is bad in one of the environments it was checked in.
+    Environment(String, Box<Reason>),
+    Other(String),
+}
+
+impl Reason {
+    /// The reason for a link that's unreachable without a more specific one.
+    pub fn unreachable() -> Self {
+        Reason::Other(String::from("unreachable"))
+    }
+}
+
+impl From<String> for Reason {
+    fn from(reason: String) -> Self {
+        Reason::Other(reason)
+    }
+}
+
+impl fmt::Display for Reason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Reason::Status(status) => write!(f, "received status code {}", status),
+            Reason::Timeout => write!(f, "timeout error"),
+            Reason::HostUnhealthy => write!(f, "host unhealthy"),
+            Reason::MissingSection { section, file } => {
+                write!(f, "failed to resolve section #{}", section)?;
+                match file {
+                    Some(file) => write!(f, " in {}", file.display()),
+                    None => Ok(()),
+                }
+            }
+            Reason::NotInLocalDocs(path) => {
+                write!(f, "not found in local docs at {}", path.display())
+            }
+            Reason::Noted(reason, note) => write!(f, "{} ({})", reason, note),
+            Reason::Environment(name, reason) => write!(f, "{}: {}", name, reason),
+            Reason::Other(reason) => write!(f, "{}", reason),
+        }
+    }
+}
+
+impl LinkStatus {
+    /// How bad the status is, for picking the worst of several.
+    fn severity(&self) -> u8 {
+        match self {
+            LinkStatus::Reachable | LinkStatus::Ignored(_) => 0,
+            LinkStatus::Skipped(_) => 1,
+            LinkStatus::Questionable(_) => 2,
+            LinkStatus::Unreachable(_) => 3,
+        }
+    }
 }
 
 impl Link {
     pub fn new(file: PathBuf, lnum: usize, raw: String) -> Self {
-        let kind = if raw.starts_with("http") {
-            LinkKind::Http
-        } else {
-            LinkKind::Local
-        };
+        let protocol_relative = raw.starts_with("//");
+        let kind = LinkKind::of(&raw);
         Link {
             file,
             lnum,
+            col: None,
+            end_col: None,
             raw,
             kind,
+            embedded: false,
+            wiki: false,
+            protocol_relative,
             status: None,
+            response: None,
+            risk: None,
+            blame: None,
+            request_error: None,
+            line_position: None,
+            environments: vec![],
+            ignore: None,
+            expanded: None,
+            text_conflict: None,
+            rewritten: None,
+            tags: vec![],
+            heading: None,
         }
     }
 
-    fn split_section(&self) -> (Option<&str>, Option<&str>) {
+    pub fn split_section(&self) -> (Option<&str>, Option<&str>) {
         lazy_static! {
             static ref SECTION_RE: Regex = Regex::new(r"^(.*)#+([A-Za-z0-9_-]+)$").unwrap();
         }
@@ -51,7 +323,7 @@ impl Link {
             Some(caps) => {
                 let section = caps.get(2).unwrap().as_str();
                 let base = caps.get(1).unwrap().as_str();
-                if base == "" {
+                if base.is_empty() {
                     (None, Some(section))
                 } else {
                     (Some(base), Some(section))
@@ -61,106 +333,806 @@ impl Link {
         }
     }
 
-    async fn _verify(&self, http_client: Arc<isahc::HttpClient>) -> LinkStatus {
-        match self.kind {
-            LinkKind::Http => {
-                match http_client.head_async(&self.raw[..]).await {
-                    Ok(response) => {
-                        let status = response.status().as_u16();
-                        match status {
-                            200 => LinkStatus::Reachable,
-                            302 => LinkStatus::Reachable,
-                            // the resource exists but may require logging in.
-                            401 => {
-                                LinkStatus::Questionable(format!("received status code {}", status))
-                            }
-                            // ^ same
-                            403 => {
-                                LinkStatus::Questionable(format!("received status code {}", status))
-                            }
-                            // HEAD method not allowed.
-                            405 => {
-                                LinkStatus::Questionable(format!("received status code {}", status))
-                            }
-                            // resource exits, but our 'Accept-' header may not match what the server can provide.// resource exits, but our 'Accept-' header may not match what the server can provide.
-                            406 => {
-                                LinkStatus::Questionable(format!("received status code {}", status))
-                            }
-                            _ => LinkStatus::Unreachable(Some(format!(
-                                "received status code {}",
-                                status
-                            ))),
-                        }
+    /// Send a request for the link, honoring any `Retry-After` header sent back along
+    /// with a 429 or 503 response.
+    async fn send(
+        &self,
+        checker: &Checker,
+        method: Method,
+        accept: Option<&str>,
+    ) -> Result<Response<AsyncBody>, isahc::Error> {
+        // If we've seen the page before, the server can tell us it hasn't changed instead
+        // of sending the whole response again.
+        let cached = checker
+            .cache
+            .as_ref()
+            .and_then(|cache| cache.get(self.page_url()));
+        let mut attempts = 0;
+        loop {
+            let mut request = Request::builder().method(method.clone()).uri(&self.raw[..]);
+            if let Some(accept) = accept {
+                request = request.header("Accept", accept);
+            }
+            if let Some(cached) = &cached {
+                if let Some(etag) = &cached.etag {
+                    request = request.header("If-None-Match", &etag[..]);
+                }
+                if let Some(last_modified) = &cached.last_modified {
+                    request = request.header("If-Modified-Since", &last_modified[..]);
+                }
+            }
+            let http_client = match &checker.crates_io {
+                Some(crates_io) if self.host().is_some_and(|h| crates_io::is_crates_io(&h)) => {
+                    crates_io.throttle().await;
+                    &crates_io.http_client
+                }
+                _ => &checker.http_client,
+            };
+            let start = Instant::now();
+            let response = http_client.send_async(request.body(())?).await;
+            if let Some(host) = self.host() {
+                checker.host_times.record(&host, start.elapsed());
+            }
+            let response = response?;
+            let status = response.status().as_u16();
+            if (status == 429 || status == 503) && attempts < MAX_RETRY_AFTER_ATTEMPTS {
+                if let Some(wait) = retry_after(&response) {
+                    attempts += 1;
+                    delay_for(std::cmp::min(wait, checker.max_retry_wait)).await;
+                    continue;
+                }
+            }
+            return Ok(response);
+        }
+    }
+
+    /// Request the link. Usually that's a single HEAD request, falling back to a GET if
+    /// the server doesn't allow HEAD. Hosts known to refuse HEAD get a GET right away.
+    async fn request(
+        &self,
+        checker: &Checker,
+        host: Option<&str>,
+        accept: Option<&str>,
+    ) -> Result<Response<AsyncBody>, isahc::Error> {
+        if host.is_some_and(|h| checker.prefers_get(h)) {
+            return self.send(checker, Method::GET, accept).await;
+        }
+        let flaky = checker.race_flaky
+            && match (&checker.cache, host) {
+                (Some(cache), Some(host)) => cache.is_flaky_host(host),
+                _ => false,
+            };
+        let result = if flaky {
+            self.race(checker, accept).await
+        } else {
+            self.send(checker, Method::HEAD, accept).await
+        };
+        match &result {
+            Ok(response) if response.status() == StatusCode::METHOD_NOT_ALLOWED => {
+                let get_result = self.send(checker, Method::GET, accept).await;
+                if let (Some(host), Ok(response)) = (host, &get_result) {
+                    if response.status() != StatusCode::METHOD_NOT_ALLOWED {
+                        checker.record_prefers_get(host, self.page_url());
                     }
-                    Err(e) => match e {
-                        isahc::Error::Timeout => {
-                            LinkStatus::Unreachable(Some(String::from("timeout error")))
-                        }
-                        _ => LinkStatus::Unreachable(None),
-                    },
+                }
+                get_result
+            }
+            _ => result,
+        }
+    }
+
+    /// Send a HEAD request and, shortly after, a GET, and take whichever works first.
+    async fn race(
+        &self,
+        checker: &Checker,
+        accept: Option<&str>,
+    ) -> Result<Response<AsyncBody>, isahc::Error> {
+        let head = self.send(checker, Method::HEAD, accept);
+        let get = async {
+            delay_for(RACE_DELAY).await;
+            self.send(checker, Method::GET, accept).await
+        };
+        tokio::pin!(head, get);
+        tokio::select! {
+            result = &mut head => {
+                if is_success(&result) {
+                    result
+                } else {
+                    let get_result = get.await;
+                    if is_success(&get_result) { get_result } else { result }
+                }
+            }
+            result = &mut get => {
+                if is_success(&result) {
+                    result
+                } else {
+                    head.await
+                }
+            }
+        }
+    }
+
+    /// Get the host of an HTTP link.
+    pub fn host(&self) -> Option<String> {
+        let uri = self.raw.parse::<Uri>().ok()?;
+        uri.host().map(String::from)
+    }
+
+    /// Split an HTTP link into the page and the fragment, if there is one.
+    fn split_fragment(&self) -> Option<(&str, &str)> {
+        split_fragment(&self.raw)
+    }
+
+    /// Get the route from a single page app URL that uses hash routing, like
+    /// `https://example.com/#/guide/intro`.
+    fn hash_route(&self) -> Option<&str> {
+        let i = self.raw.find("#/")?;
+        Some(&self.raw[i + 1..])
+    }
+
+    async fn verify_hash_route(&self, checker: &Checker, route: &str) -> LinkStatus {
+        let sitemap = match &checker.sitemap {
+            Some(sitemap) => sitemap.clone(),
+            None => match self.raw.parse::<Uri>() {
+                Ok(uri) => format!(
+                    "{}://{}/sitemap.xml",
+                    uri.scheme_str().unwrap_or("https"),
+                    uri.authority().map(|a| a.as_str()).unwrap_or("")
+                ),
+                Err(_) => return LinkStatus::Unreachable(None),
+            },
+        };
+        match checker.sitemap_routes(&sitemap).await {
+            Some(routes) => {
+                if routes.contains(&normalize_route(route)) {
+                    LinkStatus::Reachable
+                } else {
+                    LinkStatus::Unreachable(Some(Reason::Other(format!(
+                        "route {} not found in {}",
+                        route, sitemap
+                    ))))
+                }
+            }
+            None => LinkStatus::Questionable(Reason::Other(format!(
+                "failed to fetch {} to resolve route {}",
+                sitemap, route
+            ))),
+        }
+    }
+
+    /// Get the URL of the page an HTTP link points to, without any fragment.
+    pub fn page_url(&self) -> &str {
+        page_url(&self.raw)
+    }
+
+    /// Get the URL of the page an HTTP link was checked at, which results are cached
+    /// under.
+    pub fn checked_page_url(&self) -> &str {
+        page_url(self.rewritten.as_deref().unwrap_or(&self.raw))
+    }
+
+    /// Get the link as it's written in its file, which isn't always how it's checked,
+    /// like for wiki links. It's read again from the file, since it's rarely needed.
+    pub fn source_text(&self) -> Option<String> {
+        let (col, end_col) = (self.col?, self.end_col?);
+        let line = read_line(&self.file, self.lnum)?;
+        let text: String = line
+            .chars()
+            .skip(col.checked_sub(1)?)
+            .take(end_col.checked_sub(col)?)
+            .collect();
+        if text.is_empty() {
+            None
+        } else {
+            Some(text)
+        }
+    }
+
+    /// Get where an HTTP link permanently redirects to (with a 301 or 308), keeping its
+    /// fragment, if it was checked as written.
+    pub fn permanent_redirect(&self) -> Option<String> {
+        let response = self.response.as_ref()?;
+        if self.rewritten.is_some() || (response.status != 301 && response.status != 308) {
+            return None;
+        }
+        let location = resolve_location(&self.raw, response.location.as_deref()?)?;
+        match self.split_fragment() {
+            Some((_, fragment)) if !location.contains('#') => {
+                Some(format!("{}#{}", location, fragment))
+            }
+            _ => Some(location),
+        }
+    }
+
+    async fn verify_http(&mut self, checker: &Checker, config: &Config) -> LinkStatus {
+        let host = self.host();
+        // Requests to crates.io wait their turn on their own, rather than taking up a
+        // place that a request to another host could have.
+        let permit = match &host {
+            Some(h) if checker.crates_io.is_some() && crates_io::is_crates_io(h) => None,
+            _ => Some(checker.requests.acquire().await),
+        };
+        // If this host keeps failing there's no point waiting on it again.
+        if let Some(h) = &host {
+            if checker.is_host_unhealthy(h) {
+                return LinkStatus::Skipped(Reason::HostUnhealthy);
+            }
+        }
+        let result = self
+            .request(checker, host.as_deref(), config.accept(&self.raw))
+            .await;
+        drop(permit);
+        if let Some(h) = &host {
+            checker.record_host_result(h, result.is_ok());
+        }
+        if let Err(e) = &result {
+            self.request_error = Some(RequestError::from(e.kind()));
+        }
+        let mut header_violation = None;
+        let status = match result {
+            Ok(response) => {
+                let mut info = ResponseInfo::new(&response);
+                let status = response.status().as_u16();
+                // A 304 only repeats some of the headers of the full response.
+                if status != 304 {
+                    header_violation = check_headers(&self.raw, &response, config);
+                }
+                // A 304 doesn't repeat the headers we got when the page last worked.
+                if status == 304 && info.last_modified.is_none() {
+                    info.last_modified = checker
+                        .cache
+                        .as_ref()
+                        .and_then(|cache| cache.get(self.page_url()))
+                        .and_then(|entry| entry.last_modified)
+                        .and_then(|date| httpdate::parse_http_date(&date).ok());
+                }
+                self.response = Some(info);
+                match status {
+                    200 => LinkStatus::Reachable,
+                    302 => LinkStatus::Reachable,
+                    // the page hasn't changed since it last worked.
+                    304 => LinkStatus::Reachable,
+                    // the resource exists but may require logging in.
+                    401 => LinkStatus::Questionable(Reason::Status(status)),
+                    // ^ same
+                    403 => LinkStatus::Questionable(Reason::Status(status)),
+                    // not even GET is allowed.
+                    405 => LinkStatus::Questionable(Reason::Status(status)),
+                    // resource exits, but our 'Accept-' header may not match what the server can provide.// resource exits, but our 'Accept-' header may not match what the server can provide.
+                    406 => LinkStatus::Questionable(Reason::Status(status)),
+                    _ => LinkStatus::Unreachable(Some(Reason::Status(status))),
                 }
             }
-            LinkKind::Local => {
-                let dir = match self.file.parent() {
-                    Some(d) => d,
-                    None => Path::new("./"),
+            Err(e) => match e.kind() {
+                ErrorKind::Timeout => LinkStatus::Unreachable(Some(Reason::Timeout)),
+                _ => LinkStatus::Unreachable(None),
+            },
+        };
+        // A hash route in a single page app's URL will never make it to the server, so
+        // we have to look it up separately.
+        if let LinkStatus::Reachable = status {
+            let assertions: Vec<&ContentAssertion> = config
+                .assertions
+                .iter()
+                .filter(|a| a.url.is_match(&self.raw))
+                .collect();
+            if !assertions.is_empty() {
+                let page = match checker.page(&self.raw).await {
+                    Some(page) => page,
+                    None => {
+                        return LinkStatus::Questionable(Reason::Other(String::from(
+                            "failed to fetch page to check its content",
+                        )))
+                    }
                 };
-                let (base, section) = self.split_section();
-                match section {
-                    // If no section, just check that base exists.
-                    None => match base {
-                        Some(b) => {
-                            let full_path = dir.join(Path::new(b));
-                            if full_path.exists() {
+                for assertion in assertions {
+                    if let Err(reason) = assertion.check(&page.body) {
+                        return LinkStatus::Unreachable(Some(Reason::Other(format!(
+                            "content assertion failed: {}",
+                            reason
+                        ))));
+                    }
+                }
+            }
+            if let Some(reason) = header_violation {
+                return LinkStatus::Questionable(Reason::Other(reason));
+            }
+            if let Some(reason) = self.check_latency(config) {
+                return LinkStatus::Questionable(Reason::Other(reason));
+            }
+            if let Some(snapshot) = &checker.snapshot {
+                let url = self.page_url();
+                if let Some(page) = checker.page(url).await {
+                    if let Some(reason) = snapshot.check(url, &page.body) {
+                        return LinkStatus::Questionable(Reason::Other(reason));
+                    }
+                }
+            }
+            if checker.hash_routes {
+                if let Some(route) = self.hash_route() {
+                    return self.verify_hash_route(checker, route).await;
+                }
+            }
+            if checker.remote_anchors {
+                if let Some((page, fragment)) = self.split_fragment() {
+                    return match checker.page(page).await {
+                        Some(page) => {
+                            if page.anchors.contains(fragment) {
                                 LinkStatus::Reachable
                             } else {
-                                LinkStatus::Unreachable(None)
-                            }
-                        }
-                        None => LinkStatus::Unreachable(None),
-                    },
-                    // But if there is a section...
-                    Some(s) => match base {
-                        Some(b) => {
-                            let full_path = dir.join(Path::new(b));
-                            if full_path.exists() {
-                                match self.find_section(&full_path, s) {
-                                    Ok(true) => LinkStatus::Reachable,
-                                    Ok(false) => LinkStatus::Questionable(format!(
-                                        "failed to resolve section #{}",
-                                        s
-                                    )),
-                                    Err(e) => LinkStatus::Questionable(format!(
-                                        "failed to resolve section #{} {:?}",
-                                        s, e
-                                    )),
-                                }
-                            } else {
-                                LinkStatus::Unreachable(None)
+                                LinkStatus::Questionable(Reason::MissingSection {
+                                    section: fragment.to_string(),
+                                    file: None,
+                                })
                             }
                         }
-                        None => match self.find_section(&self.file, s) {
+                        None => LinkStatus::Questionable(Reason::Other(format!(
+                            "failed to fetch page to resolve section #{}",
+                            fragment
+                        ))),
+                    };
+                }
+            }
+        }
+        status
+    }
+
+    fn verify_local(&self, checker: &Checker) -> LinkStatus {
+        let dir = match self.file.parent() {
+            Some(d) => d,
+            None => Path::new("./"),
+        };
+        let (base, section) = self.split_section();
+        match section {
+            // If no section, just check that base exists.
+            None => match base {
+                Some(b) => {
+                    let full_path = dir.join(Path::new(b));
+                    if full_path.exists() {
+                        LinkStatus::Reachable
+                    } else {
+                        LinkStatus::Unreachable(None)
+                    }
+                }
+                None => LinkStatus::Unreachable(None),
+            },
+            // But if there is a section...
+            Some(s) => match base {
+                Some(b) => {
+                    let full_path = dir.join(Path::new(b));
+                    if full_path.exists() {
+                        match self.find_section(&full_path, s, Some(&checker.anchors)) {
                             Ok(true) => LinkStatus::Reachable,
-                            Ok(false) => LinkStatus::Questionable(format!(
-                                "failed to resolve section #{}",
-                                s
-                            )),
-                            Err(e) => LinkStatus::Questionable(format!(
-                                "failed to find section #{} {:?}",
+                            Ok(false) => LinkStatus::Questionable(Reason::MissingSection {
+                                section: s.to_string(),
+                                file: None,
+                            }),
+                            Err(e) => LinkStatus::Questionable(Reason::Other(format!(
+                                "failed to resolve section #{} {:?}",
                                 s, e
-                            )),
-                        },
-                    },
+                            ))),
+                        }
+                    } else {
+                        LinkStatus::Unreachable(None)
+                    }
+                }
+                None => match self.find_section(&self.file, s, Some(&checker.anchors)) {
+                    Ok(true) => LinkStatus::Reachable,
+                    Ok(false) => LinkStatus::Questionable(Reason::MissingSection {
+                        section: s.to_string(),
+                        file: None,
+                    }),
+                    Err(e) => LinkStatus::Questionable(Reason::Other(format!(
+                        "failed to find section #{} {:?}",
+                        s, e
+                    ))),
+                },
+            },
+        }
+    }
+
+    /// Check if a missing local target exists in the fallback locale, meaning it just
+    /// hasn't been translated yet.
+    fn verify_untranslated(&self, config: &Config) -> Option<LinkStatus> {
+        let (base, _) = self.split_section();
+        let dir = self.file.parent().unwrap_or_else(|| Path::new("./"));
+        let fallback_path = config.i18n.fallback_path(&dir.join(base?))?;
+        if fallback_path.exists() {
+            Some(LinkStatus::Questionable(Reason::Other(format!(
+                "untranslated target, only found {}",
+                fallback_path.display()
+            ))))
+        } else {
+            None
+        }
+    }
+
+    /// Check a link to our own published docs against the files they're built from, if
+    /// it's under a URL prefix that's mapped to a local directory.
+    fn verify_mapped(&self, checker: &Checker, config: &Config) -> Option<LinkStatus> {
+        let paths = config.local_paths(&self.raw)?;
+        let path = match paths.iter().find(|path| path.is_file()) {
+            Some(path) => path,
+            None => {
+                return Some(LinkStatus::Unreachable(Some(Reason::NotInLocalDocs(
+                    paths[0].clone(),
+                ))))
+            }
+        };
+        let status = match self.split_fragment() {
+            Some((_, fragment)) => {
+                match self.find_section(path, fragment, Some(&checker.anchors)) {
+                    Ok(true) => LinkStatus::Reachable,
+                    _ => LinkStatus::Questionable(Reason::MissingSection {
+                        section: fragment.to_string(),
+                        file: Some(path.clone()),
+                    }),
+                }
+            }
+            None => LinkStatus::Reachable,
+        };
+        Some(status)
+    }
+
+    /// Check if a missing local target is in a submodule that hasn't been checked out,
+    /// so we can say so instead of just that it's missing.
+    fn verify_submodule(&self, checker: &Checker) -> Option<LinkStatus> {
+        let submodules = checker.submodules.as_ref()?;
+        let (base, _) = self.split_section();
+        let dir = self.file.parent().unwrap_or_else(|| Path::new("./"));
+        let submodule = submodules.containing(&dir.join(base?))?;
+        if Submodules::is_initialized(submodule) {
+            None
+        } else {
+            Some(LinkStatus::Unreachable(Some(Reason::Other(format!(
+                "submodule not initialized: {}",
+                submodules.display(submodule)
+            )))))
+        }
+    }
+
+    /// Check a link in a fragment that's included in other Markdown files from each of
+    /// the chapters it ends up in, since that's where it's rendered. Links to sections of
+    /// the same file are left alone.
+    fn verify_included(&self, checker: &Checker) -> Option<LinkStatus> {
+        self.split_section().0?;
+        let chapters = checker.includes.chapters(&self.file);
+        if chapters.is_empty() {
+            return None;
+        }
+        for chapter in chapters {
+            let link = Link::new(chapter.clone(), self.lnum, self.raw.clone());
+            match link.verify_local(checker) {
+                LinkStatus::Reachable => {}
+                LinkStatus::Questionable(reason) => {
+                    return Some(LinkStatus::Questionable(Reason::Noted(
+                        Box::new(reason),
+                        format!("when included in {}", chapter.display()),
+                    )))
+                }
+                _ => {
+                    return Some(LinkStatus::Unreachable(Some(Reason::Other(format!(
+                        "doesn't resolve from {}, which includes this file",
+                        chapter.display()
+                    )))))
                 }
             }
         }
+        Some(LinkStatus::Reachable)
+    }
+
+    /// Check if a local target is a Git LFS pointer, which exists but won't render since
+    /// the content it points to hasn't been fetched or checked out.
+    fn verify_lfs(&self, checker: &Checker) -> Option<LinkStatus> {
+        let (base, _) = self.split_section();
+        let dir = self.file.parent().unwrap_or_else(|| Path::new("./"));
+        let oid = lfs::pointer_oid(&dir.join(base?))?;
+        let fetched = checker
+            .lfs_objects
+            .as_ref()
+            .is_some_and(|objects| lfs::has_object(objects, &oid));
+        Some(LinkStatus::Unreachable(Some(Reason::Other(String::from(
+            if fetched {
+                "LFS object not checked out (run `git lfs checkout`)"
+            } else {
+                "LFS object not fetched"
+            },
+        )))))
+    }
+
+    /// Check a link to github.com through the GitHub API, if we're using it. Only links to
+    /// things the API finds are settled this way. The rest are checked over HTTP, since
+    /// the site serves some things the API doesn't find, like renamed repositories.
+    async fn verify_github(&self, checker: &Checker, config: &Config) -> Option<LinkStatus> {
+        let github = checker.github.as_ref().filter(|_| checker.github_api)?;
+        // Checks on the page itself need the page.
+        if checker.snapshot.is_some()
+            || (checker.remote_anchors && self.split_fragment().is_some())
+            || config.assertions.iter().any(|a| a.url.is_match(&self.raw))
+            || config
+                .header_rules
+                .iter()
+                .any(|r| r.url.is_match(&self.raw))
+        {
+            return None;
+        }
+        let target = Target::parse(&self.raw)?;
+        github.lookup(target).await.map(|_| LinkStatus::Reachable)
+    }
+
+    /// Check that the response came back within the SLA for the link's host, if it has
+    /// one.
+    fn check_latency(&self, config: &Config) -> Option<String> {
+        let max_latency = config.max_latency(&self.host()?)?;
+        let latency = self.response.as_ref()?.latency?;
+        if latency > max_latency {
+            Some(format!(
+                "took {} to respond, over the {} SLA",
+                budget::seconds(latency),
+                budget::seconds(max_latency)
+            ))
+        } else {
+            None
+        }
+    }
+
+    /// Check if a link goes through a URL shortener.
+    fn is_shortened(&self, config: &Config) -> bool {
+        self.host().is_some_and(|host| {
+            SHORTENERS
+                .iter()
+                .copied()
+                .chain(config.shorteners.iter().map(|s| &s[..]))
+                .any(|shortener| host.eq_ignore_ascii_case(shortener))
+        })
+    }
+
+    /// Record where a shortened link ends up, if it goes anywhere.
+    fn expand_to(&mut self, url: String) {
+        if url != self.raw {
+            self.expanded = Some(url);
+        }
+    }
+
+    /// Follow a link to a URL shortener through its redirects, and check where it ends
+    /// up, so that we can say where it really goes. Each place it goes has to be one
+    /// that links may go to and be checked on, like the link itself.
+    async fn verify_shortened(&mut self, checker: &Checker, config: &Config) -> LinkStatus {
+        let mut url = self.raw.clone();
+        for _ in 0..MAX_EXPANSION_HOPS {
+            let mut hop = Link::new(self.file.clone(), self.lnum, url.clone());
+            if let Some(glob) = config.forbidden(&url) {
+                self.expand_to(url);
+                return LinkStatus::Unreachable(Some(Reason::Other(format!(
+                    "goes to a forbidden link (matches '{}')",
+                    glob
+                ))));
+            }
+            if !checker.is_host_allowed(hop.host().as_deref()) {
+                self.expand_to(url);
+                return LinkStatus::Skipped(Reason::Other(String::from(
+                    "goes to a host not permitted in this environment",
+                )));
+            }
+            let status = hop.verify_http(checker, config).await;
+            let location = hop
+                .response
+                .as_ref()
+                .filter(|response| (300..400).contains(&response.status) && response.status != 304)
+                .and_then(|response| response.location.as_deref())
+                .and_then(|location| resolve_location(&url, location));
+            match location {
+                Some(next) => url = next,
+                None => {
+                    self.expand_to(url);
+                    return status;
+                }
+            }
+        }
+        LinkStatus::Unreachable(Some(Reason::Other(String::from("too many redirects"))))
+    }
+
+    async fn _verify(&mut self, checker: Arc<Checker>, config: Arc<Config>) -> LinkStatus {
+        match self.kind {
+            LinkKind::Http => match self.verify_mapped(&checker, &config) {
+                Some(status) => status,
+                // Links to our own docs are checked locally, so they're fine anywhere.
+                None if !checker.is_host_allowed(self.host().as_deref()) => LinkStatus::Skipped(
+                    Reason::Other(String::from("not permitted in this environment")),
+                ),
+                None => match self.verify_github(&checker, &config).await {
+                    Some(status) => status,
+                    None if self.is_shortened(&config) => {
+                        self.verify_shortened(&checker, &config).await
+                    }
+                    None => self.verify_http(&checker, &config).await,
+                },
+            },
+            LinkKind::Local => match self
+                .verify_included(&checker)
+                .unwrap_or_else(|| self.verify_local(&checker))
+            {
+                LinkStatus::Unreachable(None) => self
+                    .verify_untranslated(&config)
+                    .or_else(|| self.verify_submodule(&checker))
+                    .unwrap_or(LinkStatus::Unreachable(None)),
+                status => self.verify_lfs(&checker).unwrap_or(status),
+            },
+            LinkKind::Data => LinkStatus::Skipped(Reason::Other(String::from("data URI"))),
+            LinkKind::Script => lint::javascript(&checker, &config).unwrap_or_else(|| {
+                LinkStatus::Skipped(Reason::Other(String::from("javascript: link")))
+            }),
+        }
+    }
+
+    /// Check the page a link points to in every other environment too, recording the
+    /// status in each. Returns the worst of them, labeled with its environment.
+    async fn verify_environments(
+        &mut self,
+        name: &str,
+        base: &str,
+        status: LinkStatus,
+        checker: &Arc<Checker>,
+        config: &Arc<Config>,
+    ) -> LinkStatus {
+        let path = &self.raw[base.len()..];
+        let mut environments = vec![(name.to_string(), status)];
+        for (other, other_base) in &config.environments {
+            if other == name {
+                continue;
+            }
+            let mut link = Link::new(
+                self.file.clone(),
+                self.lnum,
+                format!("{}{}", other_base, path),
+            );
+            let status = link._verify(checker.clone(), config.clone()).await;
+            environments.push((other.clone(), status));
+        }
+        environments.sort_by(|a, b| a.0.cmp(&b.0));
+        let (worst_name, worst) = environments
+            .iter()
+            .max_by_key(|(_, status)| status.severity())
+            .unwrap();
+        let worst = match worst {
+            LinkStatus::Questionable(reason) => LinkStatus::Questionable(Reason::Environment(
+                worst_name.clone(),
+                Box::new(reason.clone()),
+            )),
+            LinkStatus::Unreachable(reason) => LinkStatus::Unreachable(Some(Reason::Environment(
+                worst_name.clone(),
+                Box::new(reason.clone().unwrap_or_else(Reason::unreachable)),
+            ))),
+            LinkStatus::Skipped(reason) => LinkStatus::Skipped(reason.clone()),
+            LinkStatus::Ignored(reason) => LinkStatus::Ignored(reason.clone()),
+            LinkStatus::Reachable => LinkStatus::Reachable,
+        };
+        self.environments = environments;
+        worst
+    }
+
+    /// Get the link with the scheme it's checked with, if it's protocol-relative.
+    fn with_scheme(&self, checker: &Checker) -> String {
+        if self.protocol_relative {
+            format!("{}:{}", checker.protocol_relative_scheme, self.raw)
+        } else {
+            self.raw.clone()
+        }
+    }
+
+    /// Get the URL of the page an HTTP link is going to be checked at, which results are
+    /// cached under, like `checked_page_url` before it's been checked. Returns `None` if
+    /// it won't be checked as an HTTP link.
+    pub fn page_url_to_check(&self, checker: &Checker, config: &Config) -> Option<String> {
+        let url = self.with_scheme(checker);
+        let url = config.rewrite(&url).unwrap_or(url);
+        match LinkKind::of(&url) {
+            LinkKind::Http => Some(page_url(&url).to_string()),
+            _ => None,
+        }
     }
 
-    pub async fn verify(&mut self, http_client: Arc<isahc::HttpClient>) {
-        self.status = Some(self._verify(http_client).await);
+    pub async fn verify(&mut self, checker: Arc<Checker>, config: Arc<Config>) {
+        let url = self.with_scheme(&checker);
+        // Forbidden links fail whether they work or not, and can't be ignored.
+        if let (LinkKind::Http, Some(glob)) = (&self.kind, config.forbidden(&url)) {
+            self.status = Some(LinkStatus::Unreachable(Some(Reason::Other(format!(
+                "forbidden link (matches '{}')",
+                glob
+            )))));
+            return;
+        }
+        let ignore = self.ignore.clone().or_else(|| config.ignore(&url));
+        if let Some(ignore) = &ignore {
+            if !ignore.has_expired() {
+                self.status = Some(ignore.status());
+                return;
+            }
+        }
+        // A protocol-relative or rewritten link is checked at the URL readers end up at,
+        // but it's still reported the way it's written.
+        let url = config.rewrite(&url).unwrap_or(url);
+        let original = if url != self.raw {
+            self.kind = LinkKind::of(&url);
+            Some(mem::replace(&mut self.raw, url))
+        } else {
+            None
+        };
+        let mut status = self._verify(checker.clone(), config.clone()).await;
+        if let LinkStatus::Reachable = status {
+            if let Some(lint_status) = lint::check(self, &checker, &config).await {
+                status = lint_status;
+            }
+        }
+        if let LinkKind::Http = self.kind {
+            status = lint::homographs(self, status, &checker, &config);
+        }
+        if let Some((name, base)) = config.environment(&self.raw) {
+            status = self
+                .verify_environments(name, base, status, &checker, &config)
+                .await;
+        }
+        // Links checked against local docs say nothing about how the site is doing.
+        let mapped = config.local_paths(&self.raw).is_some();
+        if matches!(self.kind, LinkKind::Http) && !mapped {
+            let ok = match status {
+                LinkStatus::Reachable => Some(true),
+                LinkStatus::Skipped(_) | LinkStatus::Ignored(_) => None,
+                _ => Some(false),
+            };
+            // Only links that still work are worth assessing.
+            if checker.risk_scores && ok == Some(true) {
+                let history = checker
+                    .cache
+                    .as_ref()
+                    .and_then(|cache| cache.get(self.page_url()));
+                self.risk = Some(Risk::assess(self, history.as_ref()));
+            }
+            if let (Some(cache), Some(ok)) = (&checker.cache, ok) {
+                cache.record(self.page_url(), ok);
+                if let (true, Some(response)) = (ok, &self.response) {
+                    cache.record_validators(
+                        self.page_url(),
+                        response.etag.clone(),
+                        response.last_modified.map(httpdate::fmt_http_date),
+                    );
+                }
+            }
+        }
+        if let Some(ignore) = ignore {
+            status = ignore.expired_status(status);
+        }
+        if checker
+            .ignore_status
+            .iter()
+            .any(|p| p.is_match(self, &status))
+        {
+            let reason = match status {
+                LinkStatus::Questionable(reason) | LinkStatus::Unreachable(Some(reason)) => reason,
+                _ => Reason::unreachable(),
+            };
+            status = LinkStatus::Ignored(reason);
+        }
+        if let Some(original) = original {
+            self.kind = LinkKind::of(&original);
+            self.rewritten = Some(mem::replace(&mut self.raw, original));
+        }
+        self.status = Some(status);
     }
 
-    pub fn find_section(&self, path: &Path, section: &str) -> Result<bool, Error> {
+    /// Check if a file has a section. Headings and anchors are looked up in the index if
+    /// we have one, and otherwise we search the file for the words in the section.
+    pub fn find_section(
+        &self,
+        path: &Path,
+        section: &str,
+        index: Option<&AnchorIndex>,
+    ) -> Result<bool, Error> {
+        if let Some(Ok(anchors)) = index.map(|index| index.anchors(path)) {
+            if anchors.contains(section) {
+                return Ok(true);
+            }
+        }
         let mut searcher = Searcher::new();
         let matcher = RegexMatcherBuilder::new()
             .case_insensitive(true)
@@ -180,15 +1152,122 @@ impl Link {
     }
 }
 
+/// Split a URL into the page and the fragment, if there is one.
+fn split_fragment(url: &str) -> Option<(&str, &str)> {
+    let i = url.find('#')?;
+    let fragment = &url[i + 1..];
+    if fragment.is_empty() {
+        None
+    } else {
+        Some((&url[..i], fragment))
+    }
+}
+
+/// Get the URL of the page a URL points to, without any fragment.
+fn page_url(url: &str) -> &str {
+    match split_fragment(url) {
+        Some((page, _)) => page,
+        None => url,
+    }
+}
+
+/// Check if a response settles that a link works, so there's no need to wait on another.
+fn is_success(result: &Result<Response<AsyncBody>, isahc::Error>) -> bool {
+    match result {
+        Ok(response) => response.status().is_success() || response.status().is_redirection(),
+        Err(_) => false,
+    }
+}
+
+/// Get the URL a `Location` header points to, which may be relative to the URL that
+/// was requested.
+fn resolve_location(url: &str, location: &str) -> Option<String> {
+    if location.starts_with("http://") || location.starts_with("https://") {
+        return Some(location.to_string());
+    }
+    let uri = url.parse::<Uri>().ok()?;
+    let scheme = uri.scheme_str()?;
+    if location.starts_with("//") {
+        Some(format!("{}:{}", scheme, location))
+    } else if location.starts_with('/') {
+        Some(format!("{}://{}{}", scheme, uri.authority()?, location))
+    } else {
+        None
+    }
+}
+
+/// Parse the `Retry-After` header of a response, which can either be a number of seconds
+/// or an HTTP date.
+fn retry_after(response: &Response<AsyncBody>) -> Option<Duration> {
+    let value = response.headers().get("retry-after")?.to_str().ok()?.trim();
+    match value.parse::<u64>() {
+        Ok(secs) => Some(Duration::from_secs(secs)),
+        Err(_) => {
+            let date = httpdate::parse_http_date(value).ok()?;
+            Some(
+                date.duration_since(SystemTime::now())
+                    .unwrap_or_else(|_| Duration::from_secs(0)),
+            )
+        }
+    }
+}
+
+/// Normalize a route so that `/guide/intro/`, `/guide/intro?x=1`, and `/guide/intro` are
+/// all the same.
+pub fn normalize_route(route: &str) -> String {
+    let route = route.split('?').next().unwrap();
+    let route = route.trim_end_matches('/');
+    if route.starts_with('/') {
+        route.to_string()
+    } else {
+        format!("/{}", route)
+    }
+}
+
+/// Read a line of a file, counting from 1.
+pub fn read_line(path: &Path, lnum: usize) -> Option<String> {
+    let file = File::open(path).ok()?;
+    BufReader::new(file).lines().nth(lnum.checked_sub(1)?)?.ok()
+}
+
+/// Resolve `.` and `..` in a path without touching the file system, so that paths to
+/// the same file can be compared.
+pub fn normalize_path(path: &Path) -> PathBuf {
+    let mut components: Vec<Component> = vec![];
+    for component in path.components() {
+        match component {
+            Component::ParentDir => match components.last() {
+                Some(Component::Normal(_)) => {
+                    components.pop();
+                }
+                _ => components.push(component),
+            },
+            Component::CurDir => {}
+            _ => components.push(component),
+        }
+    }
+    components.iter().collect()
+}
+
 impl fmt::Display for Link {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        write!(
-            f,
-            "{} [line {}]: {}",
-            self.file.display(),
-            self.lnum,
-            self.raw
-        )
+        match self.col {
+            Some(col) => write!(
+                f,
+                "{} [line {}, col {}]: {}",
+                self.file.display(),
+                self.lnum,
+                col,
+                self.raw
+            ),
+            None => write!(
+                f,
+                "{} [line {}]: {}",
+                self.file.display(),
+                self.lnum,
+                self.raw
+            ),
+        }
     }
 }
 
@@ -198,6 +1277,8 @@ impl Ord for Link {
             self.file.cmp(&other.file)
         } else if self.lnum != other.lnum {
             self.lnum.cmp(&other.lnum)
+        } else if self.col != other.col {
+            self.col.cmp(&other.col)
         } else {
             self.raw.cmp(&other.raw)
         }
@@ -212,8 +1293,74 @@ impl PartialOrd for Link {
 
 impl PartialEq for Link {
     fn eq(&self, other: &Self) -> bool {
-        self.file == other.file && self.lnum == other.lnum && self.raw == other.raw
+        self.file == other.file
+            && self.lnum == other.lnum
+            && self.col == other.col
+            && self.raw == other.raw
     }
 }
 
 impl Eq for Link {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn response_with_retry_after(value: &str) -> Response<AsyncBody> {
+        Response::builder()
+            .status(429)
+            .header("Retry-After", value)
+            .body(AsyncBody::empty())
+            .unwrap()
+    }
+
+    #[test]
+    fn retry_after_in_seconds() {
+        let response = response_with_retry_after(" 120 ");
+        assert_eq!(retry_after(&response), Some(Duration::from_secs(120)));
+    }
+
+    #[test]
+    fn retry_after_as_a_date() {
+        let later = SystemTime::now() + Duration::from_secs(3600);
+        let response = response_with_retry_after(&httpdate::fmt_http_date(later));
+        let wait = retry_after(&response).unwrap();
+        assert!(wait > Duration::from_secs(3500) && wait <= Duration::from_secs(3600));
+        // A date that's already passed means there's no need to wait.
+        let response = response_with_retry_after("Wed, 21 Oct 2015 07:28:00 GMT");
+        assert_eq!(retry_after(&response), Some(Duration::from_secs(0)));
+    }
+
+    #[test]
+    fn retry_after_missing_or_invalid() {
+        let response = Response::builder().body(AsyncBody::empty()).unwrap();
+        assert_eq!(retry_after(&response), None);
+        assert_eq!(retry_after(&response_with_retry_after("soon")), None);
+    }
+
+    #[test]
+    fn absolute_locations_are_kept() {
+        assert_eq!(
+            resolve_location("https://bit.ly/x", "http://example.com/a").as_deref(),
+            Some("http://example.com/a")
+        );
+    }
+
+    #[test]
+    fn relative_locations_resolve_against_the_url() {
+        assert_eq!(
+            resolve_location("https://bit.ly/x", "//example.com/a").as_deref(),
+            Some("https://example.com/a")
+        );
+        assert_eq!(
+            resolve_location("https://bit.ly:8080/x", "/y?z=1").as_deref(),
+            Some("https://bit.ly:8080/y?z=1")
+        );
+    }
+
+    #[test]
+    fn locations_relative_to_the_path_are_not_resolved() {
+        assert_eq!(resolve_location("https://bit.ly/x", "y"), None);
+        assert_eq!(resolve_location("not a url", "/y"), None);
+    }
+}
@@ -0,0 +1,109 @@
+use std::collections::HashMap;
+use std::path::Path;
+
+use regex::Regex;
+
+/// Link text that's too vague to say where a link goes, so it pointing to different
+/// places in one document is to be expected.
+const VAGUE_TEXT: &[&str] = &[
+    "here",
+    "click here",
+    "this",
+    "this link",
+    "link",
+    "more",
+    "read more",
+    "learn more",
+];
+
+/// Collects the text and destination of each link in a Markdown or HTML file, a line at a
+/// time, to find text that's used for links to different places.
+pub struct LinkTexts {
+    is_html: bool,
+    /// Where each link is, along with its text and destination.
+    links: Vec<(usize, usize, String, String)>,
+}
+
+impl LinkTexts {
+    /// Start collecting the links in a file, if it's Markdown or HTML.
+    pub fn of(path: &Path) -> Option<Self> {
+        let is_html = match path.extension().and_then(|ext| ext.to_str()) {
+            Some(ext) if ext.eq_ignore_ascii_case("md") || ext.eq_ignore_ascii_case("mdx") => false,
+            Some(ext) if ext.eq_ignore_ascii_case("html") || ext.eq_ignore_ascii_case("htm") => {
+                true
+            }
+            _ => return None,
+        };
+        Some(LinkTexts {
+            is_html,
+            links: vec![],
+        })
+    }
+
+    /// Collect the links on a line.
+    pub fn line(&mut self, lnum: usize, line: &str) {
+        lazy_static! {
+            static ref MARKDOWN_RE: Regex =
+                Regex::new(r"(!?)\[([^\[\]]+)\]\(([^()\s]+)[^()]*\)").unwrap();
+            static ref HTML_RE: Regex =
+                Regex::new(r#"(?i)<a\s[^>]*?\bhref\s*=\s*["']([^"']+)["'][^>]*>(.*?)</a>"#)
+                    .unwrap();
+            static ref TAG_RE: Regex = Regex::new(r"<[^>]*>|[`*_]").unwrap();
+        }
+        let found = if self.is_html {
+            HTML_RE
+                .captures_iter(line)
+                .map(|caps| (caps.get(1).unwrap(), caps[2].to_string()))
+                .collect::<Vec<_>>()
+        } else {
+            MARKDOWN_RE
+                .captures_iter(line)
+                // The text of an image is its alt text, which isn't what's clicked on.
+                .filter(|caps| caps[1].is_empty())
+                .map(|caps| (caps.get(3).unwrap(), caps[2].to_string()))
+                .collect()
+        };
+        for (destination, text) in found {
+            let text = TAG_RE.replace_all(&text, "");
+            let text = text.split_whitespace().collect::<Vec<_>>().join(" ");
+            if text.is_empty() || VAGUE_TEXT.contains(&&text.to_lowercase()[..]) {
+                continue;
+            }
+            let col = line[..destination.start()].chars().count() + 1;
+            self.links
+                .push((lnum, col, text, destination.as_str().to_string()));
+        }
+    }
+
+    /// Find the links whose text is also used for links to somewhere else in the same
+    /// file, like "documentation" linking to two different sites, which is usually a
+    /// copy-paste mistake. Returns what's wrong with each of them, by the line and column
+    /// the link's destination starts at.
+    pub fn conflicts(&self) -> HashMap<(usize, usize), String> {
+        let mut destinations: HashMap<String, Vec<(usize, &str)>> = HashMap::new();
+        for (lnum, _, text, destination) in &self.links {
+            let seen = destinations.entry(text.to_lowercase()).or_default();
+            let destination = destination.trim_end_matches('/');
+            if !seen.iter().any(|(_, other)| *other == destination) {
+                seen.push((*lnum, destination));
+            }
+        }
+        let mut conflicts = HashMap::new();
+        for (lnum, col, text, destination) in &self.links {
+            let destination = destination.trim_end_matches('/');
+            let other = destinations[&text.to_lowercase()]
+                .iter()
+                .find(|(_, other)| *other != destination);
+            if let Some((other_lnum, other)) = other {
+                conflicts.insert(
+                    (*lnum, *col),
+                    format!(
+                        "link text \"{}\" also links to {} on line {}",
+                        text, other, other_lnum
+                    ),
+                );
+            }
+        }
+        conflicts
+    }
+}
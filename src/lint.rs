@@ -0,0 +1,130 @@
+use std::path::Path;
+use std::str::FromStr;
+
+use serde::{Deserialize, Serialize};
+
+use crate::checker::Checker;
+use crate::config::Config;
+use crate::homograph;
+use crate::link::{Link, LinkStatus, Reason};
+use crate::tracker;
+
+/// How seriously to treat a link that violates a lint.
+#[derive(Debug, Clone, Copy, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+    #[serde(alias = "warn")]
+    Warning,
+    Error,
+}
+
+impl FromStr for Severity {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "warn" | "warning" => Ok(Severity::Warning),
+            "error" => Ok(Severity::Error),
+            _ => Err(format!(
+                "invalid severity '{}', expected 'warn' or 'error'",
+                s
+            )),
+        }
+    }
+}
+
+impl Severity {
+    pub fn status(self, reason: String) -> LinkStatus {
+        match self {
+            Severity::Warning => LinkStatus::Questionable(Reason::Other(reason)),
+            Severity::Error => LinkStatus::Unreachable(Some(Reason::Other(reason))),
+        }
+    }
+}
+
+/// Run the enabled lints against a link, returning the status for the first one that
+/// the link violates.
+pub async fn check(link: &Link, checker: &Checker, config: &Config) -> Option<LinkStatus> {
+    if let Some(severity) = config.lints.mixed_content.or(checker.mixed_content) {
+        if is_mixed_content(link, checker).await {
+            return Some(severity.status(String::from(
+                "mixed content: insecure resource embedded in an HTML document",
+            )));
+        }
+    }
+    if let Some(severity) = config.lints.protocol_relative.or(checker.protocol_relative) {
+        if link.protocol_relative {
+            return Some(severity.status(String::from(
+                "protocol-relative URL: use an explicit https:// scheme instead",
+            )));
+        }
+    }
+    if let Some(severity) = config.lints.duplicate_text.or(checker.duplicate_text) {
+        if let Some(reason) = &link.text_conflict {
+            return Some(severity.status(reason.clone()));
+        }
+    }
+    if checker.tracker_state {
+        if let Some(status) = tracker::check(link, checker, config).await {
+            return Some(status);
+        }
+    }
+    None
+}
+
+/// Get the status for a link whose hostname could be passing itself off as another, if
+/// that's checked. This applies whether or not the link works, so a broken link stays
+/// broken, with the hostname as the reason.
+pub fn homographs(
+    link: &Link,
+    status: LinkStatus,
+    checker: &Checker,
+    config: &Config,
+) -> LinkStatus {
+    let severity = match config.lints.homographs.or(checker.homographs) {
+        Some(severity) => severity,
+        None => return status,
+    };
+    let reason = match homograph::check(&link.raw) {
+        Some(reason) => reason,
+        None => return status,
+    };
+    match status {
+        LinkStatus::Unreachable(Some(other)) => LinkStatus::Unreachable(Some(Reason::Noted(
+            Box::new(Reason::Other(reason)),
+            other.to_string(),
+        ))),
+        LinkStatus::Unreachable(None) => LinkStatus::Unreachable(Some(Reason::Other(reason))),
+        _ => severity.status(reason),
+    }
+}
+
+/// Get the status for a `javascript:` link, if those are forbidden.
+pub fn javascript(checker: &Checker, config: &Config) -> Option<LinkStatus> {
+    let severity = config.lints.javascript.or(checker.javascript)?;
+    Some(severity.status(String::from("javascript: links are not allowed in docs")))
+}
+
+fn is_html(path: &Path) -> bool {
+    match path.extension().and_then(|ext| ext.to_str()) {
+        Some(ext) => ext.eq_ignore_ascii_case("html") || ext.eq_ignore_ascii_case("htm"),
+        None => false,
+    }
+}
+
+async fn is_mixed_content(link: &Link, checker: &Checker) -> bool {
+    // Protocol-relative URLs are loaded with the same scheme as the page.
+    if !link.embedded
+        || link.protocol_relative
+        || !link.raw.starts_with("http://")
+        || !is_html(&link.file)
+    {
+        return false;
+    }
+    // Browsers upgrade requests to hosts that use HSTS before they're sent, so those
+    // resources won't actually be loaded insecurely.
+    match link.host() {
+        Some(host) => !checker.has_hsts(&host).await,
+        None => true,
+    }
+}
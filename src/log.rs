@@ -2,44 +2,77 @@ use std::io;
 
 use colored::*;
 
+use crate::link::{read_line, Link};
+
+/// The most characters of a line to show in a snippet.
+const MAX_SNIPPET_WIDTH: usize = 100;
+
+/// How many characters before a link to show in a snippet when the line is too long to
+/// show all of it.
+const SNIPPET_CONTEXT: usize = 30;
+
 #[derive(Copy, Clone)]
 pub enum Level {
-    DEBUG,
-    INFO,
-    WARNING,
-    ERROR,
+    Debug,
+    Info,
+    Warning,
+    Error,
+}
+
+impl Level {
+    fn color(self) -> &'static str {
+        match self {
+            Level::Debug => "cyan",
+            Level::Info => "green",
+            Level::Warning => "yellow",
+            Level::Error => "red",
+        }
+    }
 }
 
 pub struct Logger {
     level: Level,
     color: bool,
     handle: Box<dyn io::Write>,
+    /// Whether logging is held off, while something else has the terminal.
+    quiet: bool,
 }
 
 impl Logger {
     pub fn default(verbosity: usize, color: bool) -> Self {
         let level = match verbosity {
-            0 => Level::ERROR,
-            1 => Level::WARNING,
-            2 => Level::INFO,
-            _ => Level::DEBUG,
+            0 => Level::Error,
+            1 => Level::Warning,
+            2 => Level::Info,
+            _ => Level::Debug,
         };
         let handle = Box::new(io::stdout());
         Self {
             level,
             color,
             handle,
+            quiet: false,
         }
     }
 
-    fn log(
-        &mut self,
-        level: Level,
-        prelude: &str,
-        msg: &str,
-        color: &str,
-    ) -> Result<(), io::Error> {
-        if (level as i32) >= (self.level as i32) {
+    /// Log to stderr instead of stdout, leaving stdout for a report.
+    pub fn with_stderr(mut self) -> Self {
+        self.handle = Box::new(io::stderr());
+        self
+    }
+
+    /// Hold off logging until this is called again with `false`.
+    pub fn set_quiet(&mut self, quiet: bool) {
+        self.quiet = quiet;
+    }
+
+    fn is_enabled(&self, level: Level) -> bool {
+        !self.quiet && (level as i32) >= (self.level as i32)
+    }
+
+    fn log(&mut self, level: Level, prelude: &str, msg: &str) -> Result<(), io::Error> {
+        if self.is_enabled(level) {
+            let color = level.color();
             if self.color {
                 writeln!(
                     self.handle,
@@ -55,18 +88,188 @@ impl Logger {
     }
 
     pub fn debug(&mut self, msg: &str) -> Result<(), io::Error> {
-        self.log(Level::DEBUG, "DEBU: ", msg, "cyan")
+        self.log(Level::Debug, "DEBU: ", msg)
     }
 
     pub fn info(&mut self, msg: &str) -> Result<(), io::Error> {
-        self.log(Level::INFO, "INFO: ", msg, "green")
+        self.log(Level::Info, "INFO: ", msg)
     }
 
     pub fn warn(&mut self, msg: &str) -> Result<(), io::Error> {
-        self.log(Level::WARNING, "WARN: ", msg, "yellow")
+        self.log(Level::Warning, "WARN: ", msg)
     }
 
     pub fn error(&mut self, msg: &str) -> Result<(), io::Error> {
-        self.log(Level::ERROR, "ERRO: ", msg, "red")
+        self.log(Level::Error, "ERRO: ", msg)
+    }
+
+    /// Log entries together under a heading with their count, if there are any.
+    pub fn group(
+        &mut self,
+        level: Level,
+        title: &str,
+        entries: &[String],
+    ) -> Result<(), io::Error> {
+        if entries.is_empty() || !self.is_enabled(level) {
+            return Ok(());
+        }
+        let title = format!("{} ({})", title, entries.len());
+        if self.color {
+            writeln!(self.handle, "\n{}", title.color(level.color()).bold())?;
+        } else {
+            writeln!(self.handle, "\n{}", title)?;
+        }
+        for entry in entries {
+            for line in entry.lines() {
+                writeln!(self.handle, "  {}", line)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Log the outcome of a run on its own line, no matter the verbosity.
+    pub fn verdict(&mut self, level: Level, msg: &str) -> Result<(), io::Error> {
+        if self.quiet {
+            return Ok(());
+        }
+        if self.color {
+            writeln!(self.handle, "\n{}", msg.color(level.color()).bold())
+        } else {
+            writeln!(self.handle, "\n{}", msg)
+        }
+    }
+}
+
+/// How a link fared, for marking it in the log.
+#[derive(Copy, Clone)]
+pub enum Marker {
+    Ok,
+    Bad,
+    Warning,
+    /// Skipped or ignored.
+    Passed,
+    /// Extra information about a link, rather than its result.
+    Note,
+}
+
+/// How to lay out the entry for each link.
+#[derive(Copy, Clone, Default)]
+pub struct Style {
+    /// Only use ASCII, with each entry on a single line, for terminals and screen readers
+    /// that don't get along with symbols.
+    pub ascii: bool,
+}
+
+impl Style {
+    /// Format an entry for a link along with details about it.
+    pub fn entry<D: AsRef<str>>(self, marker: Marker, subject: &str, details: &[D]) -> String {
+        format!(
+            "{} {}",
+            self.marker(marker),
+            self.describe(subject, details)
+        )
+    }
+
+    /// The symbol, or word in ASCII, that marks how a link fared.
+    pub fn marker(self, marker: Marker) -> &'static str {
+        match (marker, self.ascii) {
+            (Marker::Ok, false) => "✓",
+            (Marker::Bad, false) => "✗",
+            (Marker::Warning, false) => "⚠",
+            (Marker::Passed, false) => "○",
+            (Marker::Note, false) => " ",
+            (Marker::Ok, true) => "OK",
+            (Marker::Bad, true) => "BAD",
+            (Marker::Warning, true) => "WARN",
+            (Marker::Passed, true) => "SKIP",
+            (Marker::Note, true) => "NOTE",
+        }
+    }
+
+    /// Format something along with details about it, without marking it.
+    pub fn describe<D: AsRef<str>>(self, subject: &str, details: &[D]) -> String {
+        let mut described = subject.to_string();
+        for (i, detail) in details.iter().enumerate() {
+            described.push_str(match (self.ascii, i) {
+                (false, _) => "\n        ► ",
+                (true, 0) => " - ",
+                (true, _) => "; ",
+            });
+            described.push_str(detail.as_ref());
+        }
+        described
+    }
+
+    /// Point out which link on a line with several is the one we mean.
+    pub fn locate(self, link: &Link) -> Option<String> {
+        match &link.line_position {
+            Some(position) if position.count > 1 => Some(format!(
+                "link {} of {} on the line",
+                position.index, position.count
+            )),
+            _ => None,
+        }
+    }
+
+    /// Show the line a link is on with the link underlined, the way compilers point out
+    /// errors. Long lines are cut down to the part around the link. There's no room for
+    /// this when everything has to fit on a single line. The line is read again from the
+    /// file, since only a few links are ever bad enough to show it.
+    pub fn snippet(self, link: &Link) -> Option<String> {
+        if self.ascii {
+            return None;
+        }
+        let col = link.col?;
+        let line = read_line(&link.file, link.lnum)?;
+        // Tabs would throw off the alignment of the underline.
+        let text: Vec<char> = line.trim_end().replace('\t', " ").chars().collect();
+        // The file may have changed since the link was found in it.
+        if col > text.len() {
+            return None;
+        }
+        let indented = text.iter().take_while(|c| c.is_whitespace()).count();
+        // Underline the link as it's written, which isn't always how it's checked.
+        let len = link
+            .end_col
+            .map_or_else(|| link.raw.chars().count(), |end| end.saturating_sub(col))
+            .max(1);
+        let mut start = indented;
+        let mut end = text.len();
+        let mut offset = col.saturating_sub(1).saturating_sub(indented);
+        if end - start > MAX_SNIPPET_WIDTH {
+            start = std::cmp::max(start, (col - 1).saturating_sub(SNIPPET_CONTEXT));
+            end = std::cmp::min(end, std::cmp::max(start + MAX_SNIPPET_WIDTH, col - 1 + len));
+            offset = col - 1 - start;
+        }
+        let mut shown: String = text[start..end].iter().collect();
+        if start > indented {
+            shown.insert(0, '…');
+            offset += 1;
+        }
+        if end < text.len() {
+            shown.push('…');
+        }
+        let lnum = link.lnum.to_string();
+        let gutter = " ".repeat(lnum.len());
+        let indent = " ".repeat(10);
+        Some(format!(
+            "{}{} │ {}\n{}{} │ {}{}",
+            indent,
+            lnum,
+            shown,
+            indent,
+            gutter,
+            " ".repeat(offset),
+            "^".repeat(len)
+        ))
+    }
+
+    /// The arrow between a link and what it's changed to.
+    pub fn arrow(self) -> &'static str {
+        if self.ascii {
+            "->"
+        } else {
+            "→"
+        }
     }
 }
@@ -1,21 +1,107 @@
 #[macro_use]
 extern crate lazy_static;
 
+use std::collections::HashSet;
+use std::env;
+use std::fs;
+use std::io::{self, IsTerminal, Read};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
 use std::sync::Arc;
-use std::time::Duration;
+use std::time::{Duration, Instant};
 
 use exitfailure::ExitFailure;
+use failure::ResultExt;
+use ignore::overrides::OverrideBuilder;
 use ignore::WalkBuilder;
+use isahc::config::Configurable;
+use isahc::HttpClientBuilder;
+use structopt::clap::Shell;
 use structopt::StructOpt;
-use tokio::sync::mpsc::channel;
+use tokio::sync::mpsc::{channel, Sender};
 
+mod anchors;
+mod badge;
+mod blame;
+mod budget;
+mod cache;
+mod causes;
+mod checker;
+mod config;
+mod conventions;
+mod crates_io;
+mod crawl;
+mod diff;
 mod doc_file;
+mod editor;
+mod events;
+mod github;
+mod history;
+mod homograph;
+mod ignores;
+mod includes;
+mod lfs;
 mod link;
+mod link_text;
+mod lint;
 mod log;
+mod man;
+mod messages;
+mod moved;
+mod problem_matcher;
+mod profile;
+mod report;
+mod rewrite;
 
-use doc_file::DocFile;
-use link::LinkStatus;
-use log::Logger;
+mod risk;
+mod sample;
+mod serve;
+mod snapshot;
+mod status_filter;
+mod submodules;
+mod tags;
+mod tracker;
+mod tui;
+mod validate;
+
+use anchors::{AnchorIndex, INDEX_FILE};
+use blame::{Blamer, GracePeriod};
+use budget::Budget;
+use cache::Cache;
+use causes::Causes;
+use checker::Checker;
+use config::{Config, DEFAULT_CONFIG_FILE};
+use conventions::Convention;
+use crates_io::CratesIo;
+use crawl::Crawler;
+use diff::AddedLines;
+use doc_file::{latex_unescape, org_link_target, trim_url, wiki_page_path, DocFile, HTML_LINKS};
+use editor::Editor;
+use events::{EventFormat, Events};
+use github::{Annotation, GitHub, GitHubLinks};
+use history::History;
+use ignores::Date;
+use includes::Includes;
+use link::{Link, LinkKind, LinkStatus, Reason};
+use lint::Severity;
+use log::{Level, Logger, Marker, Style};
+use messages::{Counts, Lang};
+use problem_matcher::ProblemMatcher;
+use profile::{Profile, Settings};
+use report::{Finding, FindingSeverity, Format, RunInfo, Template};
+use risk::HIGH_RISK;
+use sample::{Coverage, Sample, SampleRate};
+use snapshot::Snapshot;
+use status_filter::StatusPattern;
+use submodules::Submodules;
+use tags::Tag;
+use tui::Dashboard;
+
+const NAME: &str = "check-links";
+const ABOUT: &str = "Check the links in your crate's documentation.";
+
+/// A comment near the top of a Markdown or HTML file that keeps it from being checked.
+const SKIP_FILE_COMMENT: &str = r"<!--\s*check-links:\s*skip-file\s*-->";
 
 #[derive(Debug, StructOpt)]
 #[structopt(
@@ -23,157 +109,1624 @@ use log::Logger;
     about = "Check the links in your crate's documentation.",
     raw(setting = "structopt::clap::AppSettings::ColoredHelp")
 )]
+struct Cli {
+    #[structopt(flatten)]
+    opt: Opt,
+
+    #[structopt(subcommand)]
+    cmd: Option<Command>,
+}
+
+/// The options for a run. They come before any subcommand, or after `check`.
+#[derive(Debug, StructOpt)]
 struct Opt {
+    /// Path to a config file (defaults to ./check-links.toml if it exists)
+    #[structopt(short = "c", long = "config", parse(from_os_str))]
+    config: Option<PathBuf>,
+
+    /// Directory to check, optionally followed by ':' and the config file to use for it
+    /// (e.g. 'docs:docs.toml'). Can be given more than once. Defaults to the current
+    /// directory
+    #[structopt(long = "root")]
+    roots: Vec<Root>,
+
+    /// Only search files matching this glob. Can be given more than once
+    #[structopt(long = "include")]
+    include: Vec<String>,
+
+    /// Also look for plain http(s) URLs anywhere in files matching these comma separated
+    /// globs (like '*.txt,*.cfg'). Files that links are normally extracted from are
+    /// still handled as usual
+    #[structopt(long = "scan-text", raw(use_delimiter = "true"))]
+    scan_text: Vec<String>,
+
+    /// Skip files matching this glob. Can be given more than once
+    #[structopt(long = "exclude-path")]
+    exclude_path: Vec<String>,
+
+    /// Only check links with one of these comma separated tags, like 'badge' or
+    /// 'doc-comment'
+    #[structopt(
+        long = "only-tag",
+        raw(use_delimiter = "true", possible_values = "&Tag::variants()")
+    )]
+    only_tag: Vec<Tag>,
+
+    /// Only check external links to these comma separated hosts (like
+    /// 'docs.internal,*.corp.example'), skipping the rest without sending any requests,
+    /// for networks that can't reach anything else
+    #[structopt(long = "allow-hosts", raw(use_delimiter = "true"))]
+    allow_hosts: Vec<String>,
+
+    /// Don't look for the config files of documentation tools like mdBook, MkDocs,
+    /// Docusaurus, Antora, and Sphinx to decide which files to search
+    #[structopt(long = "no-detect")]
+    no_detect: bool,
+
+    /// Also check the files in git submodules, which are skipped by default
+    #[structopt(long = "include-submodules")]
+    include_submodules: bool,
+
+    /// Only check links on lines added by this unified diff, like the output of `git
+    /// diff`. Use '-' to read the diff from stdin
+    #[structopt(long = "diff-from", parse(from_os_str))]
+    diff_from: Option<PathBuf>,
+
+    /// Print a man page and exit
+    #[structopt(long = "generate-man")]
+    generate_man: bool,
+
     /// Verbose mode (-v, -vv, -vvv, etc)
     #[structopt(short = "v", long = "verbose", parse(from_occurrences))]
     verbose: usize,
 
-    /// Don't log in color
+    /// Don't log in color. Setting the NO_COLOR environment variable does the same
     #[structopt(long = "no-color")]
     no_color: bool,
 
+    /// Only log ASCII characters, with each link on a single line, which reads better in
+    /// some terminals and screen readers
+    #[structopt(long = "ascii", raw(alias = "\"no-unicode\""))]
+    ascii: bool,
+
     /// Set the maximum directory depth to recurse
     #[structopt(short = "d", long = "depth")]
     depth: Option<usize>,
 
-    /// Specify the timeout for HTTP requests
-    #[structopt(short = "t", long = "timeout", default_value = "3")]
-    timeout: u64,
+    /// How to report the results: 'human' logs to the terminal, while 'gitlab-codequality'
+    /// and 'bitbucket' print a report for merge request widgets to stdout
+    #[structopt(
+        long = "format",
+        default_value = "human",
+        raw(possible_values = "&Format::variants()")
+    )]
+    format: Format,
+
+    /// Print each link with a custom template, like '{file}:{line} {status} {url}'. The
+    /// fields are {file}, {line}, {column}, {url}, {status} (ok, warning, error, skipped,
+    /// or ignored), and {reason}. Logs go to stderr
+    #[structopt(long = "template")]
+    template: Option<Template>,
+
+    /// Log to stderr as usual, and print a JSON document with every link and a summary
+    /// to stdout at the end
+    #[structopt(long = "tee-json")]
+    tee_json: bool,
+
+    /// Write a badge with the number of good and broken links to an SVG file, like
+    /// 'badges/links.svg', for the README to show
+    #[structopt(long = "badge", parse(from_os_str))]
+    badge: Option<PathBuf>,
+
+    /// Register a problem matcher with GitHub Actions and print each bad link in a line
+    /// it matches, so they show up as annotations on the files
+    #[structopt(long = "emit-problem-matcher")]
+    emit_problem_matcher: bool,
+
+    /// Log how long walking the files and verifying the links took, and the time spent
+    /// on the requests to each host, after the summary
+    #[structopt(long = "budget")]
+    budget: bool,
+
+    /// Stream an event for each file scanned and each link found and checked, followed by
+    /// the summary, for programs that show progress as it happens
+    #[structopt(long = "events", raw(possible_values = "&EventFormat::variants()"))]
+    events: Option<EventFormat>,
+
+    /// Where to stream --events to, like '/dev/fd/3' (defaults to stdout, in which case
+    /// logs go to stderr)
+    #[structopt(long = "events-file", parse(from_os_str))]
+    events_file: Option<PathBuf>,
+
+    /// Show a live dashboard in the terminal, with keys to check failing links again and
+    /// open them in $EDITOR
+    #[structopt(long = "tui")]
+    tui: bool,
+
+    /// Once the run is done, open each broken link's file at its line in the editor
+    #[structopt(long = "open-errors")]
+    open_errors: bool,
+
+    /// The command to open a file in an editor with, like 'code --goto {file}:{line}' or
+    /// just 'nvim' (defaults to $CHECK_LINKS_EDITOR, $VISUAL, or $EDITOR)
+    #[structopt(long = "editor")]
+    editor: Option<String>,
+
+    /// Only check a share of the external links, like '10%', picked by a hash of each
+    /// URL. Consecutive seeds check different links, together covering all of them
+    #[structopt(long = "sample")]
+    sample: Option<SampleRate>,
+
+    /// The seed that picks which external links to check with --sample (defaults to the
+    /// number of days since 1970, so that each nightly run checks different links)
+    #[structopt(long = "seed")]
+    seed: Option<u64>,
+
+    /// The language to log messages in (defaults to the one set by LC_ALL, LC_MESSAGES,
+    /// or LANG). Reports stay in English
+    #[structopt(long = "lang", raw(possible_values = "&Lang::variants()"))]
+    lang: Option<Lang>,
+
+    /// Use a preset combination of settings. Options given explicitly take precedence
+    #[structopt(long = "profile", raw(possible_values = "&Profile::variants()"))]
+    profile: Option<Profile>,
+
+    /// Specify the timeout for HTTP requests [default: 3]
+    #[structopt(short = "t", long = "timeout")]
+    timeout: Option<u64>,
+
+    /// Specify the timeout for connecting to a host (defaults to --timeout)
+    #[structopt(long = "connect-timeout")]
+    connect_timeout: Option<u64>,
+
+    /// Give up on a request that hasn't received any data for this many seconds
+    #[structopt(long = "read-timeout")]
+    read_timeout: Option<u64>,
+
+    /// Specify the timeout for an entire HTTP request (defaults to --timeout)
+    #[structopt(long = "total-timeout")]
+    total_timeout: Option<u64>,
+
+    /// Maximum number of seconds to wait when a server responds with a 429 or 503
+    /// and a `Retry-After` header [default: 30]
+    #[structopt(long = "max-retry-wait")]
+    max_retry_wait: Option<u64>,
+
+    /// Maximum number of HTTP requests to have in flight at once [default: 32]
+    #[structopt(long = "concurrency")]
+    concurrency: Option<usize>,
+
+    /// Stop sending requests to a host after it times out or errors this many times in
+    /// a row (0 to disable) [default: 5]
+    #[structopt(long = "host-failure-threshold")]
+    host_failure_threshold: Option<usize>,
+
+    /// For hosts that have been flaky in previous runs (requires --cache), send a GET
+    /// shortly after the HEAD request and take whichever succeeds first
+    #[structopt(long = "race-flaky")]
+    race_flaky: bool,
+
+    /// Check links to repositories, issues, pull requests, and files on github.com in
+    /// batches through the GitHub API, using the token in GITHUB_TOKEN
+    #[structopt(long = "github-api")]
+    github_api: bool,
+
+    /// Warn about links to GitHub issues closed as not planned, and to GitHub pull
+    /// requests and GitLab merge requests closed without being merged. GitHub links are
+    /// only looked up with --github-api, and GITLAB_TOKEN is used for private projects on
+    /// gitlab.com, CI_SERVER_URL, or the config's gitlab_hosts
+    #[structopt(long = "tracker-state")]
+    tracker_state: bool,
+
+    /// Exit as soon as the first bad link is found
+    #[structopt(long = "fail-fast")]
+    fail_fast: bool,
+
+    /// Check every link even if the --profile stops at the first bad one
+    #[structopt(long = "no-fail-fast", raw(conflicts_with = r#""fail_fast""#))]
+    no_fail_fast: bool,
+
+    /// Flag resources loaded over plain HTTP from HTML documents with the given severity
+    /// ('warn' or 'error')
+    #[structopt(long = "lint-mixed-content")]
+    lint_mixed_content: Option<Severity>,
+
+    /// Don't flag mixed content even if the --profile does
+    #[structopt(
+        long = "no-lint-mixed-content",
+        raw(conflicts_with = r#""lint_mixed_content""#)
+    )]
+    no_lint_mixed_content: bool,
+
+    /// Flag protocol-relative URLs (like `//example.com/page`) with the given severity
+    /// ('warn' or 'error')
+    #[structopt(long = "lint-protocol-relative")]
+    lint_protocol_relative: Option<Severity>,
+
+    /// Forbid `javascript:` links with the given severity ('warn' or 'error'). They're
+    /// skipped otherwise
+    #[structopt(long = "lint-javascript")]
+    lint_javascript: Option<Severity>,
+
+    /// Flag URLs whose hostnames mix scripts or are spelled in letters that look like
+    /// Latin ones, like a Cyrillic 'а' in 'pаypal.com', with the given severity ('warn'
+    /// or 'error')
+    #[structopt(long = "lint-homographs")]
+    lint_homographs: Option<Severity>,
+
+    /// Flag links whose text is also used for links to somewhere else in the same
+    /// Markdown or HTML file, which is usually a copy-paste mistake, with the given
+    /// severity ('warn' or 'error')
+    #[structopt(long = "lint-duplicate-text")]
+    lint_duplicate_text: Option<Severity>,
+
+    /// The scheme to check protocol-relative URLs with
+    #[structopt(
+        long = "protocol-relative-scheme",
+        default_value = "https",
+        raw(possible_values = r#"&["https", "http"]"#)
+    )]
+    protocol_relative_scheme: String,
+
+    /// Verify hash routes in links to single page apps (like `https://example.com/#/guide`)
+    /// against the site's sitemap
+    #[structopt(long = "hash-routes")]
+    hash_routes: bool,
+
+    /// The sitemap (or route manifest with one URL per line) to check hash routes against.
+    /// Defaults to `/sitemap.xml` on each link's host
+    #[structopt(long = "sitemap")]
+    sitemap: Option<String>,
+
+    /// Check that the sections linked to on remote pages exist
+    #[structopt(long = "remote-anchors")]
+    remote_anchors: bool,
+
+    /// Don't check the sections linked to on remote pages even if the --profile does
+    #[structopt(
+        long = "no-remote-anchors",
+        raw(conflicts_with = r#""remote_anchors""#)
+    )]
+    no_remote_anchors: bool,
+
+    /// Record a hash of the content of each linked page in this file, and warn when the
+    /// content has changed substantially since it was recorded
+    #[structopt(long = "snapshot", parse(from_os_str))]
+    snapshot: Option<PathBuf>,
+
+    /// Replace the recorded hashes of pages that have changed in the snapshot
+    #[structopt(long = "update-snapshot")]
+    update_snapshot: bool,
+
+    /// Keep the results of checking HTTP links in this file between runs
+    #[structopt(long = "cache", parse(from_os_str))]
+    cache: Option<PathBuf>,
+
+    /// Don't keep a cache even if the --profile does
+    #[structopt(long = "no-cache", raw(conflicts_with = r#""cache""#))]
+    no_cache: bool,
+
+    /// Don't keep the headings and anchors of local files in .check-links-anchors.json
+    /// between runs. Unchanged files are otherwise only parsed once
+    #[structopt(long = "no-index-cache")]
+    no_index_cache: bool,
+
+    /// Report links that fail but were added within this period (like '7d' or '12h') as
+    /// warnings rather than errors, since what they link to may not be published yet.
+    /// Uses `git blame` to find when each link was added
+    #[structopt(long = "grace-period")]
+    grace_period: Option<GracePeriod>,
+
+    /// Report who added each bad link, and in which commit, using `git blame`
+    #[structopt(long = "blame")]
+    blame: bool,
+
+    /// Don't fail on links that fail in these ways, given as a comma separated list of
+    /// status codes ('404'), classes of status codes ('5xx'), 'timeout', 'dns', or
+    /// 'connect'. Ignored links are still reported
+    #[structopt(long = "ignore-status", raw(use_delimiter = "true"))]
+    ignore_status: Vec<StatusPattern>,
+
+    /// Report how likely each HTTP link is to break in the future
+    #[structopt(long = "risk")]
+    risk: bool,
+}
+
+// `check` takes all of the options, but there's only ever one command, so its size
+// doesn't matter.
+#[allow(clippy::large_enum_variant)]
+#[derive(Debug, StructOpt)]
+enum Command {
+    /// Check links, which is also what happens without a subcommand. Any options can
+    /// follow it
+    #[structopt(name = "check")]
+    Check(Opt),
+    /// Crawl a site from a URL, checking the links on each of its pages and following
+    /// the ones to other pages on the same host
+    #[structopt(name = "crawl")]
+    Crawl {
+        /// The page to start from, like 'https://docs.example.com/'
+        url: String,
+
+        /// The most pages to crawl
+        #[structopt(long = "max-pages", default_value = "100")]
+        max_pages: usize,
+    },
+    /// Check URLs or paths given on the command line, without searching any files
+    #[structopt(name = "url")]
+    Url {
+        /// The links to check, like 'https://example.com' or 'README.md#usage'
+        #[structopt(raw(required = "true"))]
+        urls: Vec<String>,
+    },
+    /// Check links, then update the ones that permanently redirect (301 or 308) to
+    /// point where they redirect to
+    #[structopt(name = "fix")]
+    Fix {
+        /// Print the changes without making them
+        #[structopt(long = "dry-run")]
+        dry_run: bool,
+
+        /// Write the changes to a patch file that can be reviewed and applied with 'git
+        /// apply', instead of making them
+        #[structopt(long = "emit-patch", parse(from_os_str))]
+        emit_patch: Option<PathBuf>,
+    },
+    /// Print a report in the given --format from the JSON that --tee-json printed,
+    /// without checking the links again
+    #[structopt(name = "report")]
+    Report {
+        /// The JSON file to read, or '-' for stdin
+        #[structopt(parse(from_os_str))]
+        results: PathBuf,
+    },
+    /// Serve an HTTP API that checks links on demand, like 'GET /check?url=...'
+    #[structopt(name = "serve")]
+    Serve {
+        /// The address to listen on. Anyone who can reach it can make us send requests,
+        /// so keep it private
+        #[structopt(long = "listen", default_value = "127.0.0.1:8080")]
+        listen: String,
+    },
+    /// Print a shell completion script
+    #[structopt(name = "completions")]
+    Completions {
+        /// The shell to generate completions for
+        #[structopt(raw(possible_values = "&Shell::variants()"))]
+        shell: Shell,
+    },
+    /// Check links, then comment on the bad links on lines changed by a GitHub pull
+    /// request and resolve the comments on links that have been fixed. Needs a token in
+    /// the GITHUB_TOKEN environment variable
+    #[structopt(name = "gh-annotate")]
+    GhAnnotate {
+        /// The number of the pull request
+        #[structopt(long = "pr")]
+        pr: u64,
+
+        /// The repository the pull request is in, as OWNER/NAME (defaults to the
+        /// GITHUB_REPOSITORY environment variable)
+        #[structopt(long = "repo")]
+        repo: Option<String>,
+    },
+    /// Check only the external links that failed the last time they were checked,
+    /// according to the --cache, to see if they've been fixed
+    #[structopt(name = "recheck")]
+    Recheck,
+    /// Check links, then dig through the git history for when each bad link first
+    /// appeared, and say when it last worked according to the --cache
+    #[structopt(name = "history")]
+    History {
+        /// Only look at the history since this revision, like a tag
+        #[structopt(long = "since")]
+        since: Option<String>,
+    },
+    /// Rewrite links that start with a prefix to start with another, like after docs
+    /// move to a new domain. Links are only rewritten if the new link works
+    #[structopt(name = "rewrite")]
+    Rewrite {
+        /// The prefix of the links to rewrite, like 'https://old.docs.example.com'
+        #[structopt(long = "from")]
+        from: String,
+
+        /// What to replace the prefix with, like 'https://docs.example.com'
+        #[structopt(long = "to")]
+        to: String,
+
+        /// Print the changes without making them
+        #[structopt(long = "dry-run")]
+        dry_run: bool,
+
+        /// Write the changes to a patch file that can be reviewed and applied with 'git
+        /// apply', instead of making them
+        #[structopt(long = "emit-patch", parse(from_os_str))]
+        emit_patch: Option<PathBuf>,
+    },
+    /// Report the links to a file that's been moved, including links to its sections,
+    /// and optionally update them
+    #[structopt(name = "moved")]
+    Moved {
+        /// Where the file used to be
+        #[structopt(parse(from_os_str))]
+        old: PathBuf,
+
+        /// Where the file is now
+        #[structopt(parse(from_os_str))]
+        new: PathBuf,
+
+        /// Update the links to point to the new location
+        #[structopt(long = "rewrite")]
+        rewrite: bool,
+
+        /// Write the updates to a patch file that can be reviewed and applied with 'git
+        /// apply', instead of making them
+        #[structopt(long = "emit-patch", parse(from_os_str))]
+        emit_patch: Option<PathBuf>,
+    },
+    /// Work with config files
+    #[structopt(name = "config")]
+    Config {
+        #[structopt(subcommand)]
+        cmd: ConfigCommand,
+    },
+}
+
+#[derive(Debug, StructOpt)]
+enum ConfigCommand {
+    /// Check a config file for problems and print the effective configuration
+    #[structopt(name = "validate")]
+    Validate {
+        /// The config file to check (defaults to --config or ./check-links.toml)
+        #[structopt(parse(from_os_str))]
+        path: Option<PathBuf>,
+
+        /// Print the results as JSON
+        #[structopt(long = "json")]
+        json: bool,
+    },
+}
+
+/// Validate a config file, printing any problems along with the effective config. Returns
+/// whether the config is valid.
+fn validate_config(path: &Path, json: bool) -> Result<bool, failure::Error> {
+    let contents = fs::read_to_string(path)
+        .with_context(|_| format!("failed to read config file {}", path.display()))?;
+    let (config, diagnostics) = validate::validate(&contents);
+    if json {
+        let report = serde_json::json!({
+            "path": path,
+            "valid": config.is_some(),
+            "diagnostics": diagnostics,
+            "config": config,
+        });
+        println!("{}", serde_json::to_string_pretty(&report)?);
+    } else {
+        for diagnostic in &diagnostics {
+            eprintln!("{}:{}", path.display(), diagnostic);
+        }
+        if let Some(config) = &config {
+            println!("# Effective configuration for {}", path.display());
+            print!("{}", toml::to_string_pretty(config)?);
+        }
+    }
+    Ok(config.is_some())
+}
+
+/// A directory to check, along with the config file to use for it.
+#[derive(Debug)]
+struct Root {
+    path: PathBuf,
+    config: Option<PathBuf>,
 }
 
-fn maybe_pluralize(n: u32) -> &'static str {
-    match n {
-        1 => "",
-        _ => "s",
+impl FromStr for Root {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.find(':') {
+            Some(i) => Ok(Root {
+                path: PathBuf::from(&s[..i]),
+                config: Some(PathBuf::from(&s[i + 1..])),
+            }),
+            None => Ok(Root {
+                path: PathBuf::from(s),
+                config: None,
+            }),
+        }
+    }
+}
+
+/// Override a profile's settings with the options given on the command line.
+fn resolve_settings(opt: &Opt, settings: &mut Settings) {
+    if let Some(timeout) = opt.timeout {
+        settings.timeout = timeout;
+    }
+    if let Some(max_retry_wait) = opt.max_retry_wait {
+        settings.max_retry_wait = max_retry_wait;
+    }
+    if let Some(concurrency) = opt.concurrency {
+        settings.concurrency = concurrency;
+    }
+    if let Some(host_failure_threshold) = opt.host_failure_threshold {
+        settings.host_failure_threshold = host_failure_threshold;
+    }
+    if opt.lint_mixed_content.is_some() || opt.no_lint_mixed_content {
+        settings.mixed_content = opt.lint_mixed_content;
+    }
+    if opt.cache.is_some() || opt.no_cache {
+        settings.cache = opt.cache.clone();
+    }
+    if opt.fail_fast || opt.no_fail_fast {
+        settings.fail_fast = opt.fail_fast;
+    }
+    if opt.remote_anchors || opt.no_remote_anchors {
+        settings.remote_anchors = opt.remote_anchors;
+    }
+}
+
+/// Downgrade a bad link to a warning if it was added within the grace period.
+fn apply_grace_period(link: &mut Link, grace_period: GracePeriod, blamer: &mut Blamer) {
+    let reason = match &link.status {
+        Some(LinkStatus::Unreachable(reason)) => reason.clone(),
+        _ => return,
+    };
+    if link.blame.is_none() {
+        link.blame = blamer.blame(&link.file, link.lnum);
+    }
+    let blame = match &link.blame {
+        Some(blame) if blame.is_within(grace_period.0) => blame,
+        _ => return,
+    };
+    let added = match blame.short_commit() {
+        Some(commit) => format!("added in {} within the last {}", commit, grace_period),
+        None => String::from("not committed yet"),
+    };
+    link.status = Some(LinkStatus::Questionable(Reason::Noted(
+        Box::new(reason.unwrap_or_else(Reason::unreachable)),
+        format!("link {}, so it may not be published yet", added),
+    )));
+}
+
+/// Start building an HTTP client with the timeouts from the command line and settings.
+fn http_client_builder(opt: &Opt, settings: &Settings, metrics: bool) -> HttpClientBuilder {
+    let mut builder = isahc::HttpClient::builder()
+        .timeout(Duration::from_secs(
+            opt.total_timeout.unwrap_or(settings.timeout),
+        ))
+        .connect_timeout(Duration::from_secs(
+            opt.connect_timeout.unwrap_or(settings.timeout),
+        ))
+        .metrics(metrics);
+    if let Some(read_timeout) = opt.read_timeout {
+        // A transfer that stays below 1 byte/sec for the whole read timeout has stalled.
+        builder = builder.low_speed_timeout(1, Duration::from_secs(read_timeout));
+    }
+    builder
+}
+
+/// Walk the files under a root that are searched for links. We iterate through all
+/// non-hidden files not included in a .gitignore or excluded by the command line or
+/// the root's excludes.
+fn walk_files(
+    root: &Path,
+    opt: &Opt,
+    excludes: &[&String],
+    submodules: &Option<Arc<Submodules>>,
+) -> Result<impl Iterator<Item = PathBuf>, failure::Error> {
+    let mut overrides = OverrideBuilder::new(root);
+    for glob in &opt.include {
+        overrides.add(glob)?;
+    }
+    for glob in opt.exclude_path.iter().chain(excludes.iter().copied()) {
+        overrides.add(&format!("!{}", glob)[..])?;
+    }
+    let mut walk = WalkBuilder::new(root);
+    walk.max_depth(opt.depth).overrides(overrides.build()?);
+    // Submodules are other projects, so their links are their own business. Unless we
+    // were asked to check a submodule itself.
+    if let (false, Some(submodules)) = (opt.include_submodules, submodules) {
+        let submodules = submodules.clone();
+        let root_submodule = submodules.containing(root).map(Path::to_path_buf);
+        walk.filter_entry(move |entry| {
+            submodules.containing(entry.path()) == root_submodule.as_deref()
+        });
+    }
+    Ok(walk
+        .build()
+        .filter_map(Result::ok)
+        .filter(|x| match x.file_type() {
+            Some(file_type) => file_type.is_file(),
+            None => false,
+        })
+        .map(|x| x.into_path()))
+}
+
+/// Spawn a task that verifies a link and sends it to the receiver once it has.
+fn spawn_verify(mut link: Link, checker: &Arc<Checker>, config: &Arc<Config>, tx: &Sender<Link>) {
+    let mut tx = tx.clone();
+    let checker = checker.clone();
+    let config = config.clone();
+    tokio::spawn(async move {
+        link.verify(checker, config).await;
+        // The receiver is only gone if we've stopped early, in which case the result
+        // isn't wanted.
+        let _ = tx.send(link).await;
+    });
+}
+
+/// Get the suffix for the plural of a noun, if there isn't just one of something.
+fn maybe_pluralize<N: PartialEq + From<u8>>(n: N) -> &'static str {
+    if n == N::from(1) {
+        ""
+    } else {
+        "s"
     }
 }
 
 #[tokio::main(threaded_scheduler)]
 async fn main() -> Result<(), ExitFailure> {
-    let opt = Opt::from_args();
-    let mut logger = Logger::default(opt.verbose, !opt.no_color);
+    let cli = Cli::from_args();
+    // `check` is the default subcommand, and takes the options after it instead.
+    let (opt, cmd) = match cli.cmd {
+        Some(Command::Check(opt)) => (opt, None),
+        cmd => (cli.opt, cmd),
+    };
+    if opt.generate_man {
+        print!("{}", man::render(&Cli::clap(), NAME, ABOUT));
+        return Ok(());
+    }
+    match &cmd {
+        Some(Command::Completions { shell }) => {
+            Cli::clap().gen_completions_to(NAME, *shell, &mut io::stdout());
+            return Ok(());
+        }
+        Some(Command::Config {
+            cmd: ConfigCommand::Validate { path, json },
+        }) => {
+            let path = path
+                .clone()
+                .or_else(|| opt.config.clone())
+                .unwrap_or_else(|| PathBuf::from(DEFAULT_CONFIG_FILE));
+            if !validate_config(&path, *json)? {
+                std::process::exit(1);
+            }
+            return Ok(());
+        }
+        Some(Command::Report { results }) => {
+            let mut json = String::new();
+            if results.as_os_str() == "-" {
+                io::stdin()
+                    .read_to_string(&mut json)
+                    .context("failed to read results from stdin")?;
+            } else {
+                json = fs::read_to_string(results)
+                    .with_context(|_| format!("failed to read {}", results.display()))?;
+            }
+            let json: serde_json::Value = serde_json::from_str(&json)
+                .with_context(|_| format!("failed to parse {}", results.display()))?;
+            let (findings, n_links, run) =
+                report::from_tee_json(&json).map_err(failure::err_msg)?;
+            let report = report::render(opt.format, &findings, n_links, &run).ok_or_else(|| {
+                failure::err_msg("report needs a --format, like --format gitlab-codequality")
+            })?;
+            println!("{}", serde_json::to_string_pretty(&report)?);
+            return Ok(());
+        }
+        _ => {}
+    }
+    let github = match &cmd {
+        Some(Command::GhAnnotate { pr, repo }) => {
+            let repo = repo
+                .clone()
+                .or_else(|| env::var("GITHUB_REPOSITORY").ok())
+                .ok_or_else(|| failure::err_msg("no repository given, use --repo OWNER/NAME"))?;
+            let token = env::var("GITHUB_TOKEN").map_err(|_| {
+                failure::err_msg("GITHUB_TOKEN must be set to annotate pull requests")
+            })?;
+            let root = github::repo_root()
+                .ok_or_else(|| failure::err_msg("gh-annotate must be run in a git repository"))?;
+            Some((GitHub::new(&repo, token)?, *pr, root))
+        }
+        _ => None,
+    };
+    let mut annotations = vec![];
+    // Some commands work with the links themselves instead of checking them.
+    let collect_links = matches!(
+        cmd,
+        Some(Command::Rewrite { .. }) | Some(Command::Moved { .. })
+    );
+    let mut collected = vec![];
+    let added_lines = match &opt.diff_from {
+        Some(path) if path.as_os_str() == "-" => {
+            let mut diff = String::new();
+            io::stdin()
+                .read_to_string(&mut diff)
+                .context("failed to read diff from stdin")?;
+            Some(AddedLines::parse(&diff))
+        }
+        Some(path) => {
+            let diff = fs::read_to_string(path)
+                .with_context(|_| format!("failed to read diff {}", path.display()))?;
+            Some(AddedLines::parse(&diff))
+        }
+        None => None,
+    };
+    let mut findings = vec![];
+    let mut json_links = vec![];
+    // See https://no-color.org.
+    let no_color = opt.no_color || env::var_os("NO_COLOR").is_some_and(|v| !v.is_empty());
+    let mut logger = Logger::default(opt.verbose, !no_color);
+    let style = Style { ascii: opt.ascii };
+    // Reports, templates, and events are written to stdout, so only one of them can be.
+    let stdout_report = opt.format != Format::Human || opt.template.is_some() || opt.tee_json;
+    let stdout_events = opt.events.is_some() && opt.events_file.is_none();
+    if stdout_report && stdout_events {
+        return Err(failure::err_msg(
+            "--events can't be written to stdout along with reports or templates, use --events-file",
+        )
+        .into());
+    }
+    if opt.emit_problem_matcher && (stdout_report || stdout_events || opt.tui) {
+        return Err(failure::err_msg(
+            "--emit-problem-matcher writes to stdout, so it can't be used with reports, templates, events on stdout, or --tui",
+        )
+        .into());
+    }
+    if stdout_report || stdout_events {
+        logger = logger.with_stderr();
+    }
+    let mut events = match opt.events {
+        Some(EventFormat::Ndjson) => Events::open(opt.events_file.as_deref())?,
+        None => Events::default(),
+    };
     logger.debug(&format!("{:?}", opt)[..])?;
 
+    // Explicit options take precedence over the profile's settings.
+    let mut settings = opt.profile.map(Profile::settings).unwrap_or_default();
+    resolve_settings(&opt, &mut settings);
+    logger.debug(&format!("{:?}", settings)[..])?;
+
+    let config =
+        Arc::new(Config::find(opt.config.as_deref(), Path::new("./"))?.unwrap_or_default());
+    logger.debug(&format!("{:?}", config)[..])?;
+
+    // Each root uses the config file given for it, or the one in its directory, or else
+    // the main config.
+    let mut roots = vec![];
+    for root in &opt.roots {
+        let root_config = match Config::find(root.config.as_deref(), &root.path)? {
+            Some(root_config) => Arc::new(root_config),
+            None => config.clone(),
+        };
+        logger.debug(&format!("{}: {:?}", root.path.display(), root_config)[..])?;
+        roots.push((root.path.clone(), root_config));
+    }
+    if roots.is_empty() {
+        roots.push((PathBuf::from("./"), config.clone()));
+    }
+    let run = RunInfo::new(
+        &roots[0].0,
+        &roots
+            .iter()
+            .map(|(_, config)| &**config)
+            .collect::<Vec<_>>(),
+    );
+
     // Initialize a MPSC channel. Each link to check will get its own copy
     // of the transmitter `tx`. When the link is verified we'll send the results through
     // the channel to the receiver `rx`. Then we gather all the results and log them
     // to the terminal.
     let (tx, mut rx) = channel(100);
 
-    // We'll search all Rust and Markdown files.
-    let doc_files = vec![
+    // Documentation tools render some kinds of files we don't search otherwise, and build
+    // the site into a directory that we shouldn't search at all.
+    let mut detected = vec![];
+    if !opt.no_detect {
+        for (i, (root, _)) in roots.iter().enumerate() {
+            for d in conventions::detect(root) {
+                logger.info(
+                    &format!(
+                        "Found {} config at {}, searching it the way {} renders it",
+                        d.convention.name(),
+                        d.marker.display(),
+                        d.convention.name()
+                    )[..],
+                )?;
+                detected.push((i, d));
+            }
+        }
+    }
+    let uses = |convention| detected.iter().any(|(_, d)| d.convention == convention);
+    let mut markdown_globs = vec!["*.md"];
+    if uses(Convention::Docusaurus) {
+        markdown_globs.extend(Convention::Docusaurus.globs());
+    }
+
+    // We'll search all Rust, Markdown, Org, HTML, LaTeX, and Typst files.
+    let mut doc_files = vec![
         // Rust files.
         DocFile::new(
             vec!["*.rs"],
             r"^\s*(///|//!).*\[[^\[\]]+\]\(([^\(\)]+)\)",
             2,
-        ),
-        // Markdown files.
-        DocFile::new(vec!["*.md"], r"\[[^\[\]]+\]\(([^\(\)]+)\)", 1),
+        )?
+        .with_skip_directive(r"^\s*//!\s*check-links:\s*skip(-file)?\s*$")
+        .with_tag(Tag::DocComment),
+        // Markdown files. Wiki links like `[[Page Name]]` or `[[Link text|Page Name]]`
+        // are to `Page-Name.md`, while `[[1]](...)` is a regular link. Markdown can have
+        // HTML in it too, like `<a href="...">` or `<img src="...">` for images that need
+        // a size.
+        DocFile::new(
+            markdown_globs,
+            r#"\[[^\[\]]+\]\(([^\(\)]+)\)|\[\[(?:[^\[\]|]+\|)?([^\[\]|]+)\]\](?:[^\(]|$)|<a\s[^>]*?\bhref\s*=\s*["']([^"']+)["']|<(?:img|video|audio|source|track|iframe|embed|script)\s[^>]*?\bsrc\s*=\s*["']([^"']+)["']"#,
+            1,
+        )?
+        .with_groups(vec![1, 2, 3, 4])
+        .with_embedded_groups(vec![4])
+        .with_wiki_groups(vec![2])
+        .with_transform(2, wiki_page_path)
+        .with_skip_directive(SKIP_FILE_COMMENT),
+        // Org files. Links look like `[[https://example.com][description]]` or
+        // `[[file:design.org]]`, and other kinds of Org links are ignored.
+        DocFile::new(
+            vec!["*.org"],
+            r"\[\[((?:https?://|file:)[^\[\]]+)\](?:\[[^\[\]]*\])?\]",
+            1,
+        )?
+        .with_transform(1, org_link_target),
+        // LaTeX files.
+        DocFile::new(
+            vec!["*.tex"],
+            r"\\(?:href|url)\{([^{}]+)\}",
+            1,
+        )?
+        .with_transform(1, latex_unescape),
+        // Typst files.
+        DocFile::new(vec!["*.typ"], r#"#link\(\s*"([^"]+)""#, 1)?,
+        // HTML files.
+        DocFile::new(vec!["*.html", "*.htm"], HTML_LINKS, 1)?
+        .with_groups(vec![1, 2, 3])
+        .with_embedded_groups(vec![2, 3])
+        .with_skip_directive(SKIP_FILE_COMMENT),
     ];
-
-    // Build file iterator.
-    // We iterator through all non-hidden Rust and Markdown files not included in a .gitignore.
-    let file_iter = WalkBuilder::new("./")
-        .max_depth(opt.depth)
-        .build()
-        .filter_map(Result::ok)
-        .filter(|x| match x.file_type() {
-            Some(file_type) => file_type.is_file(),
-            None => false,
-        })
-        .map(|x| x.into_path());
+    // reStructuredText, for Sphinx. Links look like `` `text <https://example.com>`_ ``,
+    // or are hyperlink targets like `.. _name: https://example.com`. References to other
+    // targets, like `` `text <name_>`_ ``, are left alone.
+    if uses(Convention::Sphinx) {
+        doc_files.push(
+            DocFile::new(
+                Convention::Sphinx.globs().to_vec(),
+                r"`[^`<]*<([^<>`]*[^<>`_])>`__?|^\s*\.\.\s+_[^:]+:\s+(\S+)\s*$",
+                1,
+            )?
+            .with_groups(vec![1, 2]),
+        );
+    }
+    // AsciiDoc, for Antora. Links look like `https://example.com[text]` or
+    // `link:path/to/file.pdf[text]`. Antora resolves `xref:` targets from the module
+    // rather than the file, so those are left alone.
+    if uses(Convention::Antora) {
+        doc_files.push(
+            DocFile::new(
+                Convention::Antora.globs().to_vec(),
+                r"\blink:([^\s\[]+)\[|\b(https?://[^\s\[<>]+)\[",
+                1,
+            )?
+            .with_groups(vec![1, 2]),
+        );
+    }
+    // Any other text files we were asked to scan for URLs.
+    if !opt.scan_text.is_empty() {
+        let globs = opt.scan_text.iter().map(|glob| &glob[..]).collect();
+        doc_files.push(
+            DocFile::new(globs, r#"(https?://[^\s<>"'`()\[\]{}]+)"#, 1)?
+                .with_transform(1, trim_url),
+        );
+    }
 
     // Keep track of the total number of links so we know how many the receiver `rx`
     // should be expecting.
     let mut n_links = 0u32;
 
     // Configure HTTP client.
-    let http_client = Arc::new(
-        isahc::HttpClient::builder()
-            .timeout(Duration::from_secs(opt.timeout))
-            .connect_timeout(Duration::from_secs(opt.timeout))
-            .build()?,
+    // Timings are needed to check responses against the SLAs in the configs.
+    let metrics = roots.iter().any(|(_, config)| !config.sla.is_empty());
+    let http_client = http_client_builder(&opt, &settings, metrics).build()?;
+    // Links to crates.io get a client of their own, which says who we are as crates.io
+    // asks of crawlers.
+    let crates_io_client = http_client_builder(&opt, &settings, metrics)
+        .default_header("User-Agent", crates_io::USER_AGENT)
+        .build()?;
+
+    // The checker holds the HTTP client along with any state that needs to be shared
+    // between the tasks verifying links.
+    let mut checker = Checker::new(
+        http_client,
+        Duration::from_secs(settings.max_retry_wait),
+        settings.concurrency,
+        settings.host_failure_threshold,
     );
+    checker.crates_io = Some(CratesIo::new(crates_io_client));
+    checker.mixed_content = settings.mixed_content;
+    checker.javascript = opt.lint_javascript;
+    checker.homographs = opt.lint_homographs;
+    checker.duplicate_text = opt.lint_duplicate_text;
+    checker.protocol_relative = opt.lint_protocol_relative;
+    checker.protocol_relative_scheme = opt.protocol_relative_scheme.clone();
+    checker.hash_routes = opt.hash_routes;
+    checker.sitemap = opt.sitemap.clone();
+    checker.remote_anchors = settings.remote_anchors;
+    if let Some(path) = &settings.cache {
+        checker.cache = Some(Cache::load(path)?);
+    }
+    checker.race_flaky = opt.race_flaky;
+    if opt.github_api {
+        let token = env::var("GITHUB_TOKEN")
+            .map_err(|_| failure::err_msg("GITHUB_TOKEN must be set to use --github-api"))?;
+        checker.github = Some(GitHubLinks::new(token)?);
+    } else if opt.tracker_state {
+        logger.warn("Without --github-api, --tracker-state only checks GitLab merge requests")?;
+    }
+    checker.github_api = opt.github_api;
+    checker.tracker_state = opt.tracker_state;
+    if !opt.allow_hosts.is_empty() {
+        checker.allowed_hosts = Some(opt.allow_hosts.clone());
+    }
+    checker.submodules = Submodules::load().map(Arc::new);
+    checker.lfs_objects = lfs::objects_dir();
+    // Each root leaves out what its config excludes and the directories its docs are
+    // built in.
+    let root_excludes: Vec<Vec<&String>> = roots
+        .iter()
+        .enumerate()
+        .map(|(i, (_, config))| {
+            let built = detected.iter().filter(|(j, _)| *j == i);
+            config
+                .exclude
+                .iter()
+                .chain(built.flat_map(|(_, d)| d.excludes.iter()))
+                .collect()
+        })
+        .collect();
+    // Some commands check links from somewhere other than the files under the roots.
+    let searched_roots = match cmd {
+        Some(Command::Url { .. }) | Some(Command::Crawl { .. }) => &roots[..0],
+        _ => &roots[..],
+    };
+    // Includes are found in the same files that are searched, so they're all known before
+    // any links are checked.
+    let mut searched_files = vec![];
+    for (i, (root, _)) in searched_roots.iter().enumerate() {
+        searched_files.extend(walk_files(
+            root,
+            &opt,
+            &root_excludes[i],
+            &checker.submodules,
+        )?);
+    }
+    checker.includes = Includes::scan(searched_files);
+    // The index is only kept by runs that check links, so commands that work with the
+    // links themselves don't leave it behind.
+    if !opt.no_index_cache && !collect_links {
+        checker.anchors = AnchorIndex::load(Path::new(INDEX_FILE))?;
+    }
+    checker.risk_scores = opt.risk;
+    checker.ignore_status = opt.ignore_status.clone();
+    if let Some(path) = &opt.snapshot {
+        checker.snapshot = Some(Snapshot::load(path, opt.update_snapshot)?);
+    }
+    let checker = Arc::new(checker);
+
+    if let Some(Command::Serve { listen }) = &cmd {
+        logger.verdict(Level::Info, &format!("Listening on http://{}", listen)[..])?;
+        serve::serve(listen, checker, config).await?;
+        return Ok(());
+    }
+
+    let editor = Editor::new(opt.editor.as_deref());
+    if opt.tui {
+        if stdout_report || stdout_events {
+            return Err(failure::format_err!(
+                "--tui can't be used with reports, templates, or output on stdout"
+            )
+            .into());
+        }
+        events.dashboard = Some(Dashboard::start(
+            checker.clone(),
+            roots.clone(),
+            editor.clone(),
+            style,
+            !no_color,
+        )?);
+        logger.set_quiet(true);
+    }
+
+    let sample = opt.sample.map(|rate| Sample::new(rate, opt.seed));
+    if let Some(sample) = &sample {
+        logger.info(&format!("Checking external links in {}", sample)[..])?;
+        if checker.cache.is_none() {
+            logger.warn("Without --cache there's no telling when the links left out of the sample were last checked")?;
+        }
+    }
+    let mut coverage = Coverage::default();
+
+    // Rechecking picks the links to check before any of them are, so checking one
+    // doesn't change which others get checked.
+    let previously_broken = match (&cmd, &checker.cache) {
+        (Some(Command::Recheck), Some(cache)) => Some(cache.failing()),
+        (Some(Command::Recheck), None) => {
+            return Err(
+                failure::err_msg("recheck needs the results of earlier runs, use --cache").into(),
+            )
+        }
+        _ => None,
+    };
+    let mut fixed = vec![];
+    let mut history = match &cmd {
+        Some(Command::History { since }) => Some(History::new(since.clone())),
+        _ => None,
+    };
+    let mut history_links = vec![];
+
+    let mut glob_conflicts = HashSet::new();
+    let start = Instant::now();
+    for (i, (root, config)) in searched_roots.iter().enumerate() {
+        let file_iter = walk_files(root, &opt, &root_excludes[i], &checker.submodules)?;
 
-    // Now iter through all files in our `file_iter` and check if they match one of
-    // the doc files.
-    for path in file_iter {
-        for doc_file in &doc_files {
-            if doc_file.is_match(&path) {
-                logger.debug(&format!("Searching {}", path.display())[..])?;
+        // Now iter through all files in our `file_iter` and check if they match one of
+        // the doc files.
+        for path in file_iter {
+            let matches = doc_file::matches(&doc_files, &path);
+            let doc_file = match matches.first() {
+                Some(m) => m.doc_file,
+                None => {
+                    logger.debug(&format!("Ignoring {}", path.display())[..])?;
+                    continue;
+                }
+            };
+            // Overlapping globs are probably a mistake, so warn once for each pair.
+            for other in &matches[1..] {
+                if glob_conflicts.insert((matches[0].glob, other.glob)) {
+                    logger.warn(
+                        &format!(
+                            "{} matches both '{}' and '{}', so files like it are only searched \
+                             as the more specific '{}'",
+                            path.display(),
+                            matches[0].glob,
+                            other.glob,
+                            matches[0].glob
+                        )[..],
+                    )?;
+                }
+            }
+            if added_lines.as_ref().is_some_and(|a| !a.has_file(&path)) {
+                logger.debug(&format!("Skipping {} (not in the diff)", path.display())[..])?;
+                continue;
+            }
+            if doc_file.is_skipped(&path)? {
+                logger.debug(&format!("Skipping {} (check-links: skip)", path.display())[..])?;
+                continue;
+            }
+            logger.debug(&format!("Searching {}", path.display())[..])?;
+
+            // Search for links in the file. For each link found, we spawn a task that will
+            // verify the link and report the results to the channel.
+            let mut links_in_file = 0;
+            let text_conflicts = config
+                .lints
+                .duplicate_text
+                .or(checker.duplicate_text)
+                .is_some();
+            doc_file.iter_links(&path, text_conflicts, |link| {
+                if !opt.only_tag.is_empty() && !link.tags.iter().any(|t| opt.only_tag.contains(t)) {
+                    return;
+                }
+                if added_lines
+                    .as_ref()
+                    .is_some_and(|a| !a.contains(&link.file, link.lnum))
+                {
+                    return;
+                }
+                if collect_links {
+                    collected.push((link, config.clone()));
+                    return;
+                }
+                // Results are cached under the URL a link is checked at, which isn't how
+                // it's written if it's protocol-relative or rewritten by the config.
+                if let Some(previously_broken) = &previously_broken {
+                    let page_url = link.page_url_to_check(&checker, config);
+                    if !page_url.is_some_and(|url| previously_broken.contains(&url)) {
+                        return;
+                    }
+                }
+                let page_url = sample
+                    .as_ref()
+                    .and_then(|_| link.page_url_to_check(&checker, config));
+                if let (Some(sample), Some(page_url)) = (&sample, page_url) {
+                    if !sample.contains(&page_url) {
+                        coverage.record_unchecked(&page_url, checker.cache.as_ref());
+                        return;
+                    }
+                    coverage.checked += 1;
+                }
+                events.link_found(&link);
+                links_in_file += 1;
+                n_links += 1;
+                spawn_verify(link, &checker, config, &tx);
+            })?;
+            events.file_scanned(&path, links_in_file);
+        }
+    }
 
-                // Search for links in the file. For each link found, we spawn a task
-                // that will verify the link and report the results to the channel.
-                doc_file.iter_links(&path, |mut link| {
+    match &cmd {
+        Some(Command::Url { urls }) => {
+            for (i, url) in urls.iter().enumerate() {
+                let link = Link::new(PathBuf::from("<arguments>"), i + 1, url.clone());
+                events.link_found(&link);
+                n_links += 1;
+                spawn_verify(link, &checker, &config, &tx);
+            }
+        }
+        Some(Command::Crawl { url, max_pages }) => {
+            let mut crawler = Crawler::new(url, *max_pages)
+                .with_context(|_| format!("invalid URL to crawl from: {}", url))?;
+            while let Some(page) = crawler.next_page() {
+                logger.debug(&format!("Crawling {}", page)[..])?;
+                let body = match checker.page(page.as_str()).await {
+                    Some(fetched) => fetched.body.clone(),
+                    None => {
+                        logger.warn(&format!("Failed to crawl {}", page)[..])?;
+                        continue;
+                    }
+                };
+                // Each link is checked where it's first found.
+                let mut links_on_page = 0;
+                for found in crawl::links(&page, &body) {
+                    crawler.follow(&found.url);
+                    if !crawler.is_new(&found.url) {
+                        continue;
+                    }
+                    let mut link = Link::new(
+                        PathBuf::from(page.as_str()),
+                        found.lnum,
+                        found.url.to_string(),
+                    );
+                    link.col = Some(found.col);
+                    link.embedded = found.embedded;
+                    events.link_found(&link);
+                    links_on_page += 1;
                     n_links += 1;
-                    let mut tx = tx.clone();
-                    let http_client = http_client.clone();
-                    tokio::spawn(async move {
-                        link.verify(http_client).await;
-                        if tx.send(link).await.is_err() {
-                            std::process::exit(1);
-                        };
-                    });
-                })?;
-
-                break;
-            } else {
-                logger.debug(&format!("Ignoring {}", path.display())[..])?;
+                    spawn_verify(link, &checker, &config, &tx);
+                }
+                events.file_scanned(Path::new(page.as_str()), links_on_page);
             }
         }
+        _ => {}
     }
 
     // Drop transmitter so that the receiver knows it is finished.
     drop(tx);
+    let walk_time = start.elapsed();
+
+    match &cmd {
+        Some(Command::Rewrite {
+            from,
+            to,
+            dry_run,
+            emit_patch,
+        }) => {
+            let ok = rewrite::rewrite(
+                collected,
+                from,
+                to,
+                *dry_run,
+                emit_patch.as_deref(),
+                checker.clone(),
+                style,
+            )
+            .await?;
+            if let Some(cache) = &checker.cache {
+                cache.save()?;
+            }
+            if !ok {
+                std::process::exit(1);
+            }
+            return Ok(());
+        }
+        Some(Command::Moved {
+            old,
+            new,
+            rewrite,
+            emit_patch,
+        }) => {
+            let links = collected.into_iter().map(|(link, _)| link).collect();
+            if !moved::moved(links, old, new, *rewrite, emit_patch.as_deref(), style)? {
+                std::process::exit(1);
+            }
+            return Ok(());
+        }
+        _ => {}
+    }
 
     // Now loop through all the links we found and log the results to the terminal.
     let mut n_errors = 0u32;
     let mut n_warnings = 0u32;
-    while let Some(link) = rx.recv().await {
+    let mut n_skipped = 0u32;
+    let mut n_ignored = 0u32;
+    // In a terminal, the bad links are gathered up and logged together at the end so
+    // they're easy to find, while logs and reports stream them as usual.
+    let grouped = opt.format == Format::Human
+        && opt.template.is_none()
+        && !opt.tee_json
+        && !opt.tui
+        && io::stdout().is_terminal();
+    let mut broken = vec![];
+    let mut warnings = vec![];
+    let mut to_open = vec![];
+    let mut blamer = Blamer::default();
+    let mut causes = Causes::default();
+    let problem_matcher = if opt.emit_problem_matcher {
+        Some(ProblemMatcher::register()?)
+    } else {
+        None
+    };
+    let lang = opt.lang.unwrap_or_else(Lang::detect);
+    let mut failed_fast = false;
+    let mut redirects = vec![];
+    loop {
+        let next = match &mut events.dashboard {
+            Some(dashboard) => dashboard.next_link(&mut rx).await,
+            None => rx.recv().await,
+        };
+        let mut link = match next {
+            Some(link) => link,
+            None => break,
+        };
+        if let Some(grace_period) = opt.grace_period {
+            apply_grace_period(&mut link, grace_period, &mut blamer);
+        }
+        causes.add(&link);
+        let is_bad = matches!(
+            link.status,
+            Some(LinkStatus::Questionable(_)) | Some(LinkStatus::Unreachable(_))
+        );
+        let mut blame = None;
+        if opt.blame && is_bad {
+            if link.blame.is_none() {
+                link.blame = blamer.blame(&link.file, link.lnum);
+            }
+            blame = link.blame.as_ref().map(ToString::to_string);
+        }
+        let mut details = vec![];
+        let subject = link.to_string();
+        if is_bad && history.is_some() {
+            let page_url = match link.kind {
+                LinkKind::Http => Some(link.checked_page_url().to_string()),
+                _ => None,
+            };
+            // The history has the link as it's written, which isn't always how it's
+            // checked.
+            history_links.push((
+                subject.clone(),
+                link.file.clone(),
+                link.source_text().unwrap_or_else(|| link.raw.clone()),
+                page_url,
+            ));
+        }
+        if let Some((_, _, root)) = &github {
+            let reason = match &link.status {
+                Some(LinkStatus::Questionable(reason)) => Some(reason.to_string()),
+                Some(LinkStatus::Unreachable(reason)) => Some(
+                    reason
+                        .as_ref()
+                        .map_or_else(|| String::from("is unreachable"), ToString::to_string),
+                ),
+                _ => None,
+            };
+            if let (Some(reason), Some(file)) = (reason, github::repo_path(root, &link.file)) {
+                annotations.push(Annotation {
+                    file,
+                    line: link.lnum,
+                    link: link.raw.clone(),
+                    reason,
+                });
+            }
+        }
         match link.status.as_ref().unwrap() {
             LinkStatus::Reachable => {
-                logger.info(&format!("✓ {}", link)[..])?;
+                details.extend(report::rewrite(&link));
+                details.extend(report::expansion(&link));
+                logger.info(&style.entry(Marker::Ok, &subject, &details)[..])?;
+                if previously_broken.is_some() {
+                    fixed.push(subject.clone());
+                }
             }
             LinkStatus::Questionable(reason) => {
                 n_warnings += 1;
-                logger.warn(&format!("✗ {}\n        ► {}", link, reason)[..])?;
+                let finding = Finding::new(&link, &reason.to_string(), FindingSeverity::Warning);
+                if let Some(line) = problem_matcher.as_ref().and_then(|m| m.line(&finding)) {
+                    println!("{}", line);
+                }
+                findings.push(finding);
+                details.push(lang.reason(reason));
+                details.extend(report::rewrite(&link));
+                details.extend(report::expansion(&link));
+                details.extend(report::environment_columns(&link));
+                details.extend(style.locate(&link));
+                details.extend(blame);
+                let mut entry = style.entry(Marker::Warning, &subject, &details);
+                if let Some(snippet) = style.snippet(&link) {
+                    entry.push('\n');
+                    entry.push_str(&snippet);
+                }
+                if grouped {
+                    warnings.push(entry);
+                } else {
+                    logger.warn(&entry[..])?;
+                }
             }
             LinkStatus::Unreachable(reason) => {
                 n_errors += 1;
-                match reason {
-                    Some(s) => logger.error(&format!("✗ {}\n        ► {}", link, s)[..])?,
-                    None => logger.error(&format!("✗ {}", link)[..])?,
-                };
+                let message = reason.clone().unwrap_or_else(Reason::unreachable);
+                let finding = Finding::new(&link, &message.to_string(), FindingSeverity::Error);
+                if let Some(line) = problem_matcher.as_ref().and_then(|m| m.line(&finding)) {
+                    println!("{}", line);
+                }
+                findings.push(finding);
+                if opt.open_errors {
+                    to_open.push((link.file.clone(), link.lnum, link.col));
+                }
+                if let Some(reason) = reason {
+                    details.push(lang.reason(reason));
+                }
+                details.extend(report::rewrite(&link));
+                details.extend(report::expansion(&link));
+                details.extend(report::environment_columns(&link));
+                details.extend(style.locate(&link));
+                details.extend(blame);
+                let mut entry = style.entry(Marker::Bad, &subject, &details);
+                if let Some(snippet) = style.snippet(&link) {
+                    entry.push('\n');
+                    entry.push_str(&snippet);
+                }
+                if grouped {
+                    broken.push(entry);
+                } else {
+                    logger.error(&entry[..])?;
+                }
+                // There's no need to wait on the remaining links.
+                failed_fast = settings.fail_fast;
+            }
+            LinkStatus::Skipped(reason) => {
+                n_skipped += 1;
+                details.push(format!("{}: {}", lang.skipped(), lang.reason(reason)));
+                logger.warn(&style.entry(Marker::Passed, &subject, &details)[..])?;
+            }
+            LinkStatus::Ignored(reason) => {
+                n_ignored += 1;
+                findings.push(Finding::new(
+                    &link,
+                    &reason.to_string(),
+                    FindingSeverity::Info,
+                ));
+                details.push(format!("{}: {}", lang.ignored(), lang.reason(reason)));
+                logger.info(&style.entry(Marker::Passed, &subject, &details)[..])?;
             }
         };
+        if opt.tee_json {
+            json_links.push(report::link_json(&link));
+        }
+        events.link_checked(&link);
+        if let Some(template) = &opt.template {
+            println!("{}", template.render(&link));
+        }
+        if let (Some(Command::Fix { .. }), Some(new)) = (&cmd, link.permanent_redirect()) {
+            let config = roots
+                .iter()
+                .find(|(root, _)| link.file.starts_with(root))
+                .map_or_else(|| config.clone(), |(_, config)| config.clone());
+            let old = Link::new(link.file.clone(), link.lnum, link.raw.clone());
+            redirects.push((old, new, config));
+        }
+        if let Some(risk) = &link.risk {
+            let msg = style.entry(Marker::Note, &subject, &[format!("link rot risk {}", risk)]);
+            if risk.score >= HIGH_RISK {
+                logger.warn(&msg[..])?;
+            } else {
+                logger.info(&msg[..])?;
+            }
+        }
+        if failed_fast {
+            break;
+        }
+    }
+    // Stop any links still being checked from waiting to send their results.
+    drop(rx);
+
+    // The dashboard has the last word, since links may have been checked again there.
+    if let Some(dashboard) = events.dashboard.take() {
+        let outcome = dashboard.finish().await;
+        logger.set_quiet(false);
+        n_errors = outcome.errors;
+        n_warnings = outcome.warnings;
+        n_skipped = outcome.skipped;
+        n_ignored = outcome.ignored;
+        broken = outcome.broken;
+        warnings = outcome.warned;
+    }
+
+    // Links are only fixed if where they redirect to works.
+    let mut all_fixed = true;
+    if let Some(Command::Fix {
+        dry_run,
+        emit_patch,
+    }) = &cmd
+    {
+        all_fixed = rewrite::change_links(
+            redirects,
+            *dry_run,
+            emit_patch.as_deref(),
+            checker.clone(),
+            style,
+        )
+        .await?;
     }
 
-    if n_links == 0 {
-        logger.info("No links found")?;
+    let budget = Budget {
+        walk: walk_time,
+        verify: start.elapsed(),
+        hosts: checker.host_times.slowest(),
+    };
+
+    if let Some(snapshot) = &checker.snapshot {
+        snapshot.save()?;
+    }
+    if let Some(cache) = &checker.cache {
+        cache.save()?;
+    }
+    checker.anchors.save()?;
+
+    if let Some((github, pr, _)) = &github {
+        let result = github.annotate(*pr, &annotations).await?;
+        logger.warn(
+            &format!(
+                "Posted {} comment{} and resolved {} on pull request #{} ({} bad link{} outside the diff)",
+                result.posted,
+                maybe_pluralize(result.posted as u32),
+                result.resolved,
+                pr,
+                result.outside_diff,
+                maybe_pluralize(result.outside_diff as u32),
+            )[..],
+        )?;
+    }
+
+    if let Some(report) = report::render(opt.format, &findings, n_links, &run) {
+        println!("{}", serde_json::to_string_pretty(&report)?);
+    }
+    let summary_json = serde_json::json!({
+        "run": run.to_json(),
+        "links": n_links,
+        "errors": n_errors,
+        "warnings": n_warnings,
+        "skipped": n_skipped,
+        "ignored": n_ignored,
+        "causes": causes.to_json(),
+        "timings": budget.to_json(),
+        "sample": sample.as_ref().map(|_| serde_json::json!({
+            "checked": coverage.checked,
+            "unchecked": coverage.unchecked,
+            "never_checked": coverage.never_checked,
+            "oldest_check": coverage.oldest_check,
+        })),
+    });
+    if opt.tee_json {
+        let report = serde_json::json!({
+            "summary": summary_json,
+            "links": json_links,
+        });
+        println!("{}", serde_json::to_string_pretty(&report)?);
+    }
+    events.summary(summary_json);
+
+    let counts = Counts {
+        links: n_links,
+        errors: n_errors,
+        warnings: n_warnings,
+        skipped: n_skipped,
+        ignored: n_ignored,
+    };
+    let summary = lang.summary(&counts);
+    if let Some(badge) = &opt.badge {
+        badge::write(badge, &counts)?;
+    }
+
+    if sample.is_some() {
+        logger.info(&coverage.describe()[..])?;
+    }
+    if let Some(reason) = checker.github.as_ref().and_then(|github| github.failure()) {
+        logger.warn(
+            &format!(
+                "Checked links to GitHub over HTTP after the API failed: {}",
+                reason
+            )[..],
+        )?;
+    }
+    if previously_broken.is_some() {
+        let title = format!(
+            "Fixed since they last failed: {} of {}",
+            fixed.len(),
+            n_links
+        );
+        logger.verdict(Level::Info, &style.describe(&title, &fixed))?;
+    }
+    if n_errors + n_warnings > 0 {
+        let title = lang.common_causes();
+        logger.verdict(Level::Info, &style.describe(title, &causes.lines(lang)))?;
+    }
+    if let Some(history) = &mut history {
+        let mut entries = vec![];
+        for (subject, file, raw, page_url) in &history_links {
+            let mut details = vec![];
+            match history.first_seen(file, raw) {
+                Some(first_seen) => details.push(first_seen.to_string()),
+                None => details.push(String::from("not in a git repository")),
+            }
+            if let Some(page_url) = page_url {
+                details.push(match &checker.cache {
+                    Some(cache) => match cache.get(page_url).and_then(|entry| entry.last_ok) {
+                        Some(last_ok) => {
+                            format!("last worked on {}", Date::from_timestamp(last_ok))
+                        }
+                        None => String::from("never seen working"),
+                    },
+                    None => String::from("use --cache to find out when it last worked"),
+                });
+            }
+            entries.push(style.describe(subject, &details));
+        }
+        logger.group(Level::Error, "History of bad links", &entries)?;
+    }
+    if opt.budget {
+        logger.verdict(Level::Info, &style.describe("Time budget", &budget.lines()))?;
+    }
+
+    if (grouped || opt.tui) && n_links > 0 {
+        logger.group(Level::Error, lang.broken_links(), &broken)?;
+        logger.group(Level::Warning, lang.warnings(), &warnings)?;
+        let (level, marker) = if n_errors > 0 {
+            (Level::Error, Marker::Bad)
+        } else if n_warnings > 0 {
+            (Level::Warning, Marker::Warning)
+        } else {
+            (Level::Info, Marker::Ok)
+        };
+        logger.verdict(level, &style.entry::<&str>(marker, &summary, &[]))?;
+    } else if n_links == 0 {
+        logger.info(lang.no_links_found())?;
     } else if n_errors > 0 {
-        // Exit with an error code if any bad links were found.
-        logger.error(&format!(
-            "{} error{}, {} warning{} out of {} link{} found",
-            n_errors,
-            maybe_pluralize(n_errors),
-            n_warnings,
-            maybe_pluralize(n_warnings),
-            n_links,
-            maybe_pluralize(n_links)
-        ))?;
-        std::process::exit(1);
+        logger.error(&summary[..])?;
     } else {
-        logger.info(&format!(
-            "{} error{}, {} warning{} out of {} link{} found",
-            n_errors,
-            maybe_pluralize(n_errors),
-            n_warnings,
-            maybe_pluralize(n_warnings),
-            n_links,
-            maybe_pluralize(n_links)
-        ))?;
+        logger.info(&summary[..])?;
+    }
+    if failed_fast {
+        logger.error(lang.fail_fast())?;
     }
 
+    for (file, lnum, col) in &to_open {
+        if let Err(e) = editor.open(file, *lnum, *col) {
+            logger.error(&format!("Failed to open {}: {}", file.display(), e)[..])?;
+            break;
+        }
+    }
+
+    if let Some(problem_matcher) = problem_matcher {
+        problem_matcher.unregister();
+    }
+
+    // Exit with an error code if any bad links were found, or couldn't be fixed.
+    if n_errors > 0 || !all_fixed {
+        std::process::exit(1);
+    }
     Ok(())
 }
@@ -0,0 +1,49 @@
+use structopt::clap::App;
+
+/// Escape text for roff, which treats backslashes specially, along with periods and
+/// apostrophes at the start of a line.
+fn escape(line: &str) -> String {
+    let line = line.replace('\\', "\\\\").replace('-', "\\-");
+    if line.starts_with('.') || line.starts_with('\'') {
+        format!("\\&{}", line)
+    } else {
+        line
+    }
+}
+
+/// Render a man page for the app from its help message. Each section of the help
+/// message (like `USAGE:` or `OPTIONS:`) becomes a section in the man page.
+pub fn render(app: &App, name: &str, about: &str) -> String {
+    let mut help = vec![];
+    app.write_help(&mut help).unwrap();
+    let help = String::from_utf8_lossy(&help);
+
+    let mut page = format!(
+        ".TH {} 1 \"\" \"{} {}\"\n.SH NAME\n{} \\- {}\n",
+        name.to_uppercase(),
+        name,
+        env!("CARGO_PKG_VERSION"),
+        name,
+        escape(about),
+    );
+    // The name, version, and description come before the first section, and we've
+    // already covered those.
+    let mut in_section = false;
+    for line in help.lines() {
+        let trimmed = line.trim_end();
+        if !trimmed.starts_with(' ') && trimmed.ends_with(':') {
+            if in_section {
+                page.push_str(".fi\n");
+            }
+            page.push_str(&format!(".SH {}\n.nf\n", trimmed.trim_end_matches(':')));
+            in_section = true;
+        } else if in_section {
+            page.push_str(&escape(trimmed));
+            page.push('\n');
+        }
+    }
+    if in_section {
+        page.push_str(".fi\n");
+    }
+    page
+}
@@ -0,0 +1,211 @@
+use std::env;
+use std::str::FromStr;
+
+use crate::causes::Cause;
+use crate::link::Reason;
+use crate::maybe_pluralize;
+
+/// The language to log messages in.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Lang {
+    En,
+    Ja,
+}
+
+impl Lang {
+    pub fn variants() -> [&'static str; 2] {
+        ["en", "ja"]
+    }
+
+    /// Pick a language from the locale environment variables, falling back to English
+    /// for locales we don't have messages for.
+    pub fn detect() -> Self {
+        ["LC_ALL", "LC_MESSAGES", "LANG"]
+            .iter()
+            .filter_map(|var| env::var(var).ok())
+            .find(|value| !value.is_empty())
+            .and_then(|locale| {
+                // Locales look like 'ja_JP.UTF-8'.
+                let lang = locale.split(['_', '.', '-']).next()?;
+                lang.parse().ok()
+            })
+            .unwrap_or(Lang::En)
+    }
+
+    /// The summary of a run, like "1 error, 0 warnings out of 10 links found".
+    pub fn summary(self, counts: &Counts) -> String {
+        match self {
+            Lang::En => {
+                let mut summary = format!(
+                    "{} error{}, {} warning{}",
+                    counts.errors,
+                    maybe_pluralize(counts.errors),
+                    counts.warnings,
+                    maybe_pluralize(counts.warnings),
+                );
+                if counts.skipped > 0 {
+                    summary.push_str(&format!(", {} skipped", counts.skipped));
+                }
+                if counts.ignored > 0 {
+                    summary.push_str(&format!(", {} ignored", counts.ignored));
+                }
+                summary.push_str(&format!(
+                    " out of {} link{} found",
+                    counts.links,
+                    maybe_pluralize(counts.links)
+                ));
+                summary
+            }
+            Lang::Ja => {
+                let mut summary =
+                    format!("エラー {} 件、警告 {} 件", counts.errors, counts.warnings);
+                if counts.skipped > 0 {
+                    summary.push_str(&format!("、スキップ {} 件", counts.skipped));
+                }
+                if counts.ignored > 0 {
+                    summary.push_str(&format!("、無視 {} 件", counts.ignored));
+                }
+                summary.push_str(&format!("（検出したリンク {} 件中）", counts.links));
+                summary
+            }
+        }
+    }
+
+    pub fn no_links_found(self) -> &'static str {
+        match self {
+            Lang::En => "No links found",
+            Lang::Ja => "リンクが見つかりませんでした",
+        }
+    }
+
+    pub fn fail_fast(self) -> &'static str {
+        match self {
+            Lang::En => "Stopping at the first error (--fail-fast)",
+            Lang::Ja => "最初のエラーで停止しました (--fail-fast)",
+        }
+    }
+
+    pub fn broken_links(self) -> &'static str {
+        match self {
+            Lang::En => "Broken links",
+            Lang::Ja => "リンク切れ",
+        }
+    }
+
+    pub fn warnings(self) -> &'static str {
+        match self {
+            Lang::En => "Warnings",
+            Lang::Ja => "警告",
+        }
+    }
+
+    pub fn common_causes(self) -> &'static str {
+        match self {
+            Lang::En => "Most common causes",
+            Lang::Ja => "主な原因",
+        }
+    }
+
+    pub fn cause(self, cause: Cause) -> &'static str {
+        match (self, cause) {
+            (Lang::En, Cause::Dns) => "DNS lookup failed",
+            (Lang::En, Cause::Connection) => "couldn't connect",
+            (Lang::En, Cause::Timeout) => "timed out",
+            (Lang::En, Cause::NotFound) => "not found (404 or 410)",
+            (Lang::En, Cause::ServerError) => "server error (5xx)",
+            (Lang::En, Cause::OtherStatus) => "other status codes",
+            (Lang::En, Cause::MissingAnchor) => "missing section",
+            (Lang::En, Cause::MissingFile) => "missing local file",
+            (Lang::En, Cause::Other) => "other",
+            (Lang::Ja, Cause::Dns) => "DNS の名前解決に失敗",
+            (Lang::Ja, Cause::Connection) => "接続できません",
+            (Lang::Ja, Cause::Timeout) => "タイムアウト",
+            (Lang::Ja, Cause::NotFound) => "見つかりません (404 または 410)",
+            (Lang::Ja, Cause::ServerError) => "サーバーエラー (5xx)",
+            (Lang::Ja, Cause::OtherStatus) => "その他のステータスコード",
+            (Lang::Ja, Cause::MissingAnchor) => "セクションが見つかりません",
+            (Lang::Ja, Cause::MissingFile) => "ローカルファイルが見つかりません",
+            (Lang::Ja, Cause::Other) => "その他",
+        }
+    }
+
+    pub fn skipped(self) -> &'static str {
+        match self {
+            Lang::En => "skipped",
+            Lang::Ja => "スキップ",
+        }
+    }
+
+    pub fn ignored(self) -> &'static str {
+        match self {
+            Lang::En => "ignored",
+            Lang::Ja => "無視",
+        }
+    }
+
+    /// Translate the reason a link is bad. Reasons we don't have a translation for are
+    /// left in English.
+    pub fn reason(self, reason: &Reason) -> String {
+        match (self, reason) {
+            (Lang::En, reason) => reason.to_string(),
+            (Lang::Ja, Reason::Status(status)) => {
+                format!("ステータスコード {} が返されました", status)
+            }
+            (Lang::Ja, Reason::Timeout) => String::from("タイムアウトしました"),
+            (Lang::Ja, Reason::HostUnhealthy) => String::from("ホストが応答しません"),
+            (
+                Lang::Ja,
+                Reason::MissingSection {
+                    section,
+                    file: None,
+                },
+            ) => {
+                format!("セクション #{} が見つかりません", section)
+            }
+            (
+                Lang::Ja,
+                Reason::MissingSection {
+                    section,
+                    file: Some(file),
+                },
+            ) => {
+                format!(
+                    "{} にセクション #{} が見つかりません",
+                    file.display(),
+                    section
+                )
+            }
+            (Lang::Ja, Reason::Noted(reason, note)) => {
+                format!("{} ({})", self.reason(reason), note)
+            }
+            (Lang::Ja, Reason::Environment(name, reason)) => {
+                format!("{}: {}", name, self.reason(reason))
+            }
+            (Lang::Ja, reason) if *reason == Reason::unreachable() => {
+                String::from("到達できません")
+            }
+            (Lang::Ja, reason) => reason.to_string(),
+        }
+    }
+}
+
+impl FromStr for Lang {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match &s.to_lowercase()[..] {
+            "en" | "c" | "posix" => Ok(Lang::En),
+            "ja" => Ok(Lang::Ja),
+            _ => Err(format!("unsupported language '{}'", s)),
+        }
+    }
+}
+
+/// The number of links with each result in a run.
+pub struct Counts {
+    pub links: u32,
+    pub errors: u32,
+    pub warnings: u32,
+    pub skipped: u32,
+    pub ignored: u32,
+}
@@ -0,0 +1,196 @@
+use std::path::{Component, Path, PathBuf};
+
+use failure::Error;
+
+use crate::link::{normalize_path, Link, LinkKind};
+use crate::log::{Marker, Style};
+use crate::maybe_pluralize;
+use crate::rewrite::{apply_edits, write_patch, Edit};
+
+/// Get a relative path from a directory to a file. Both should be normalized and
+/// relative to the same place.
+fn relative_path(dir: &Path, file: &Path) -> PathBuf {
+    let dir: Vec<Component> = dir.components().collect();
+    let file: Vec<Component> = file.components().collect();
+    let common = dir
+        .iter()
+        .zip(file.iter())
+        .take_while(|(a, b)| a == b)
+        .count();
+    let mut path = PathBuf::new();
+    for _ in common..dir.len() {
+        path.push("..");
+    }
+    for component in &file[common..] {
+        path.push(component);
+    }
+    path
+}
+
+/// Report the local links that point to a file that's been moved from `old` to `new`,
+/// along with what they should be changed to, and make the changes if `rewrite` is
+/// set, or write them to a patch file. Returns whether every link's section can be
+/// found in the new file, and every link that was to be changed was.
+pub fn moved(
+    links: Vec<Link>,
+    old: &Path,
+    new: &Path,
+    rewrite: bool,
+    patch: Option<&Path>,
+    style: Style,
+) -> Result<bool, Error> {
+    let old = normalize_path(old);
+    let new = normalize_path(new);
+    let mut edits = vec![];
+    let mut n_by_hand = 0;
+    let mut all_found = true;
+    for link in links {
+        if !matches!(link.kind, LinkKind::Local) {
+            continue;
+        }
+        let (base, section) = link.split_section();
+        let base = match base {
+            Some(base) => base,
+            None => continue,
+        };
+        let dir = normalize_path(link.file.parent().unwrap_or_else(|| Path::new("")));
+        if normalize_path(&dir.join(base)) != old {
+            continue;
+        }
+        // Wiki links are to pages by name, which a path can't stand in for, so they're
+        // left to be changed by hand.
+        if link.wiki {
+            n_by_hand += 1;
+            let details = ["not rewritten: wiki links are to pages by name, not paths"];
+            println!("{}", style.entry(Marker::Bad, &link.to_string(), &details));
+            continue;
+        }
+        let mut target = relative_path(&dir, &new)
+            .to_string_lossy()
+            .replace('\\', "/");
+        if let Some(section) = section {
+            target = format!("{}#{}", target, section);
+        }
+        let mut details = vec![target.clone()];
+        // Anchors are only worth checking once the file is actually in its new place.
+        if let Some(section) = section {
+            if new.exists() && !link.find_section(&new, section, None).unwrap_or(false) {
+                all_found = false;
+                details.push(format!(
+                    "section #{} not found in {}",
+                    section,
+                    new.display()
+                ));
+            }
+        }
+        println!("{}", style.describe(&link.to_string(), &details));
+        edits.push(Edit::new(&link, target));
+    }
+    all_found &= n_by_hand == 0;
+    if let Some(patch) = patch {
+        let applied = write_patch(&edits, patch)?;
+        applied.print_problems(style);
+        let n_unchanged = n_by_hand + applied.unmatched.len();
+        println!(
+            "Wrote a patch rewriting {} line{} to {} ({} link{} not rewritten)",
+            applied.n_changed,
+            maybe_pluralize(applied.n_changed),
+            patch.display(),
+            n_unchanged,
+            maybe_pluralize(n_unchanged)
+        );
+        all_found &= applied.unmatched.is_empty();
+    } else if rewrite {
+        let applied = apply_edits(&edits);
+        applied.print_problems(style);
+        let n_unchanged = n_by_hand + applied.unmatched.len();
+        println!(
+            "Rewrote {} line{} ({} link{} not rewritten, {} file{} skipped)",
+            applied.n_changed,
+            maybe_pluralize(applied.n_changed),
+            n_unchanged,
+            maybe_pluralize(n_unchanged),
+            applied.skipped.len(),
+            maybe_pluralize(applied.skipped.len())
+        );
+        all_found &= applied.unmatched.is_empty() && applied.skipped.is_empty();
+    } else {
+        let n_links = edits.len() + n_by_hand;
+        println!(
+            "{} link{} to {}",
+            n_links,
+            maybe_pluralize(n_links),
+            old.display()
+        );
+    }
+    Ok(all_found)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use std::process;
+
+    use super::*;
+
+    /// A link as it's found on a line, where it's written as `written`.
+    fn link(file: &Path, line: &str, written: &str, raw: &str) -> Link {
+        let start = line.find(written).unwrap() + 1;
+        let mut link = Link::new(file.to_path_buf(), 1, raw.to_string());
+        link.col = Some(start);
+        link.end_col = Some(start + written.len());
+        link
+    }
+
+    #[test]
+    fn relative_paths_go_up_and_back_down() {
+        assert_eq!(
+            relative_path(Path::new("docs/api"), Path::new("guide/intro.md")),
+            Path::new("../../guide/intro.md")
+        );
+        assert_eq!(
+            relative_path(Path::new("docs"), Path::new("docs/intro.md")),
+            Path::new("intro.md")
+        );
+    }
+
+    #[test]
+    fn links_are_rewritten_where_they_are_written() {
+        let dir = std::env::temp_dir().join(format!("check-links-moved-{}", process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let md = dir.join("index.md");
+        let org = dir.join("notes.org");
+        let md_line = "[[Old Page]] and [x](Old-Page.md#Goals)\n";
+        let org_line = "[[file:Old-Page.md::*Goals][g]]\n";
+        fs::write(&md, md_line).unwrap();
+        fs::write(&org, org_line).unwrap();
+        let mut wiki = link(&md, md_line, "Old Page", "Old-Page.md");
+        wiki.wiki = true;
+        let links = vec![
+            wiki,
+            link(&md, md_line, "Old-Page.md#Goals", "Old-Page.md#Goals"),
+            link(
+                &org,
+                org_line,
+                "file:Old-Page.md::*Goals",
+                "Old-Page.md#Goals",
+            ),
+        ];
+        let all_found = moved(
+            links,
+            &dir.join("Old-Page.md"),
+            &dir.join("guide/New.md"),
+            true,
+            None,
+            Style::default(),
+        )
+        .unwrap();
+        let md_contents = fs::read_to_string(&md).unwrap();
+        let org_contents = fs::read_to_string(&org).unwrap();
+        fs::remove_dir_all(&dir).unwrap();
+        // The wiki link is left to be changed by hand.
+        assert!(!all_found);
+        assert_eq!(md_contents, "[[Old Page]] and [x](guide/New.md#Goals)\n");
+        assert_eq!(org_contents, "[[file:guide/New.md::*Goals][g]]\n");
+    }
+}
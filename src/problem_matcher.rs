@@ -0,0 +1,72 @@
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+
+use failure::{Error, ResultExt};
+use serde_json::json;
+
+use crate::report::{Finding, FindingSeverity};
+
+/// Identifies our matcher to GitHub Actions, for removing it again.
+const OWNER: &str = "check-links";
+
+/// A GitHub Actions problem matcher for the lines printed by `line`, so that each bad
+/// link shows up as an annotation on its file without a separate action to set it up.
+pub struct ProblemMatcher {
+    path: PathBuf,
+}
+
+impl ProblemMatcher {
+    /// Write the matcher to the runner's temporary directory and tell GitHub Actions to
+    /// start using it.
+    pub fn register() -> Result<Self, Error> {
+        let dir = env::var_os("RUNNER_TEMP")
+            .map(PathBuf::from)
+            .unwrap_or_else(env::temp_dir);
+        let path = dir.join("check-links-problem-matcher.json");
+        let matcher = json!({
+            "problemMatcher": [{
+                "owner": OWNER,
+                "pattern": [{
+                    "regexp": r"^check-links: (error|warning): (.+?):(\d+):(?:(\d+):)? (.+)$",
+                    "severity": 1,
+                    "file": 2,
+                    "line": 3,
+                    "column": 4,
+                    "message": 5,
+                }],
+            }],
+        });
+        fs::write(&path, serde_json::to_string_pretty(&matcher)?)
+            .with_context(|_| format!("failed to write problem matcher to {}", path.display()))?;
+        println!("::add-matcher::{}", path.display());
+        Ok(ProblemMatcher { path })
+    }
+
+    /// Format a bad link the way the matcher expects. Ignored links aren't problems, so
+    /// they're left out.
+    pub fn line(&self, finding: &Finding) -> Option<String> {
+        let severity = match finding.severity {
+            FindingSeverity::Error => "error",
+            FindingSeverity::Warning => "warning",
+            FindingSeverity::Info => return None,
+        };
+        let column = finding
+            .column
+            .map(|column| format!("{}:", column))
+            .unwrap_or_default();
+        // The matcher only looks at one line at a time.
+        let message = finding.message.replace(['\r', '\n'], " ");
+        Some(format!(
+            "check-links: {}: {}:{}:{} {}: {}",
+            severity, finding.file, finding.line, column, finding.link, message
+        ))
+    }
+
+    /// Tell GitHub Actions to stop using the matcher, so it doesn't pick up lines from
+    /// later steps.
+    pub fn unregister(self) {
+        println!("::remove-matcher owner={}::", OWNER);
+        let _ = fs::remove_file(&self.path);
+    }
+}
@@ -0,0 +1,107 @@
+use std::path::PathBuf;
+use std::str::FromStr;
+
+use crate::lint::Severity;
+
+/// The file results are cached in for the profiles that keep a cache.
+const CACHE_FILE: &str = ".check-links-cache.json";
+
+/// A preset bundling settings that work well together for a particular situation.
+#[derive(Debug, Clone, Copy)]
+pub enum Profile {
+    /// Quick feedback: short timeouts, no waiting to retry, and stop at the first error.
+    Fast,
+    /// Check as much as possible: long timeouts, patient retries, and remote anchors.
+    Thorough,
+    /// Reliable results without hammering hosts, with a cache that can be kept between
+    /// builds.
+    Ci,
+    /// Day to day use while editing docs.
+    Local,
+}
+
+impl Profile {
+    pub fn variants() -> [&'static str; 4] {
+        ["fast", "thorough", "ci", "local"]
+    }
+
+    pub fn settings(self) -> Settings {
+        match self {
+            Profile::Fast => Settings {
+                timeout: 2,
+                max_retry_wait: 0,
+                concurrency: 64,
+                host_failure_threshold: 2,
+                fail_fast: true,
+                ..Settings::default()
+            },
+            Profile::Thorough => Settings {
+                timeout: 10,
+                max_retry_wait: 60,
+                concurrency: 16,
+                host_failure_threshold: 0,
+                remote_anchors: true,
+                mixed_content: Some(Severity::Warning),
+                ..Settings::default()
+            },
+            Profile::Ci => Settings {
+                timeout: 5,
+                max_retry_wait: 30,
+                concurrency: 16,
+                cache: Some(PathBuf::from(CACHE_FILE)),
+                mixed_content: Some(Severity::Error),
+                ..Settings::default()
+            },
+            Profile::Local => Settings {
+                max_retry_wait: 5,
+                cache: Some(PathBuf::from(CACHE_FILE)),
+                mixed_content: Some(Severity::Warning),
+                ..Settings::default()
+            },
+        }
+    }
+}
+
+impl FromStr for Profile {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "fast" => Ok(Profile::Fast),
+            "thorough" => Ok(Profile::Thorough),
+            "ci" => Ok(Profile::Ci),
+            "local" => Ok(Profile::Local),
+            _ => Err(format!("unknown profile '{}'", s)),
+        }
+    }
+}
+
+/// The settings a profile controls. Options given explicitly on the command line take
+/// precedence over these.
+#[derive(Debug)]
+pub struct Settings {
+    pub timeout: u64,
+    pub max_retry_wait: u64,
+    pub concurrency: usize,
+    pub host_failure_threshold: usize,
+    pub fail_fast: bool,
+    pub remote_anchors: bool,
+    pub mixed_content: Option<Severity>,
+    pub cache: Option<PathBuf>,
+}
+
+impl Default for Settings {
+    /// The settings used when no profile is given.
+    fn default() -> Self {
+        Settings {
+            timeout: 3,
+            max_retry_wait: 30,
+            concurrency: 32,
+            host_failure_threshold: 5,
+            fail_fast: false,
+            remote_anchors: false,
+            mixed_content: None,
+            cache: None,
+        }
+    }
+}
@@ -0,0 +1,497 @@
+use std::path::Path;
+use std::str::FromStr;
+
+use serde_json::{json, Value};
+
+use crate::cache;
+use crate::config::Config;
+use crate::github::head_commit;
+use crate::ignores::Date;
+use crate::link::{Link, LinkStatus, Reason};
+use crate::snapshot::fnv1a;
+
+/// How to report the results.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Format {
+    /// Log each link to the terminal.
+    Human,
+    /// GitLab's Code Quality report, for merge request widgets.
+    GitlabCodequality,
+    /// A Bitbucket Code Insights report along with its annotations.
+    Bitbucket,
+}
+
+impl Format {
+    pub fn variants() -> [&'static str; 3] {
+        ["human", "gitlab-codequality", "bitbucket"]
+    }
+}
+
+impl FromStr for Format {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "human" => Ok(Format::Human),
+            "gitlab-codequality" => Ok(Format::GitlabCodequality),
+            "bitbucket" => Ok(Format::Bitbucket),
+            _ => Err(format!("unknown format '{}'", s)),
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum FindingSeverity {
+    Error,
+    Warning,
+    /// Bad links that were ignored, which are reported for reference.
+    Info,
+}
+
+/// A bad link to include in a report.
+pub struct Finding {
+    pub file: String,
+    pub line: usize,
+    pub column: Option<usize>,
+    pub link: String,
+    pub message: String,
+    pub severity: FindingSeverity,
+}
+
+impl Finding {
+    pub fn new(link: &Link, message: &str, severity: FindingSeverity) -> Self {
+        let file = link.file.to_string_lossy();
+        Finding {
+            file: file.strip_prefix("./").unwrap_or(&file).to_string(),
+            line: link.lnum,
+            column: link.col,
+            link: link.raw.clone(),
+            message: message.to_string(),
+            severity,
+        }
+    }
+
+    /// Identifies the finding across runs, so that platforms can tell which problems are
+    /// new and which have been fixed.
+    fn fingerprint(&self) -> String {
+        let key = format!("{}\n{}", self.file, self.link);
+        format!("{:016x}", fnv1a(key.as_bytes()))
+    }
+
+    fn description(&self) -> String {
+        format!("Bad link {}: {}", self.link, self.message)
+    }
+}
+
+/// What a run checked and with what, so that reports from different runs can be told
+/// apart when comparing them later.
+pub struct RunInfo {
+    pub version: String,
+    /// A hash of the effective configs, so that runs with the same settings are easy to
+    /// spot even when the config files were formatted differently.
+    pub config_hash: String,
+    /// The commit checked out in the repository being checked, if it's in one.
+    pub commit: Option<String>,
+    /// When the run started, as a Unix timestamp.
+    pub started_at: u64,
+}
+
+impl RunInfo {
+    pub fn new(dir: &Path, configs: &[&Config]) -> Self {
+        let configs: Vec<String> = configs
+            .iter()
+            .map(|config| toml::to_string(config).unwrap_or_default())
+            .collect();
+        RunInfo {
+            version: env!("CARGO_PKG_VERSION").to_string(),
+            config_hash: format!("{:016x}", fnv1a(configs.join("\n").as_bytes())),
+            commit: head_commit(dir),
+            started_at: cache::now(),
+        }
+    }
+
+    pub fn to_json(&self) -> Value {
+        json!({
+            "version": self.version,
+            "config_hash": self.config_hash,
+            "commit": self.commit,
+            "started_at": timestamp(self.started_at),
+        })
+    }
+}
+
+/// Format a Unix timestamp like `2025-01-31T12:00:00Z`.
+fn timestamp(secs: u64) -> String {
+    let time = secs % 86400;
+    format!(
+        "{}T{:02}:{:02}:{:02}Z",
+        Date::from_timestamp(secs),
+        time / 3600,
+        time / 60 % 60,
+        time % 60
+    )
+}
+
+/// Parse a timestamp formatted by `timestamp`.
+fn parse_timestamp(s: &str) -> Option<u64> {
+    let (date, time) = s.strip_suffix('Z')?.split_once('T')?;
+    let mut parts = time.splitn(3, ':').map(|part| part.parse::<u64>().ok());
+    let (hours, minutes, seconds) = (parts.next()??, parts.next()??, parts.next()??);
+    Some(date.parse::<Date>().ok()?.timestamp() + hours * 3600 + minutes * 60 + seconds)
+}
+
+/// Read back the findings, number of links, and run info from the JSON printed by
+/// `--tee-json`, so a report can be rendered from an earlier run.
+pub fn from_tee_json(json: &Value) -> Result<(Vec<Finding>, u32, RunInfo), String> {
+    let summary = &json["summary"];
+    let links = json["links"]
+        .as_array()
+        .ok_or("expected the JSON printed by --tee-json, with a list of links")?;
+    let run = &summary["run"];
+    let run = RunInfo {
+        version: run["version"].as_str().unwrap_or("unknown").to_string(),
+        config_hash: run["config_hash"].as_str().unwrap_or_default().to_string(),
+        commit: run["commit"].as_str().map(String::from),
+        started_at: run["started_at"]
+            .as_str()
+            .and_then(parse_timestamp)
+            .unwrap_or_default(),
+    };
+    let mut findings = vec![];
+    for link in links {
+        let severity = match link["status"].as_str() {
+            Some("error") => FindingSeverity::Error,
+            Some("warning") => FindingSeverity::Warning,
+            Some("ignored") => FindingSeverity::Info,
+            Some(_) => continue,
+            None => return Err(format!("link without a status: {}", link)),
+        };
+        let file = link["file"].as_str().unwrap_or_default();
+        findings.push(Finding {
+            file: file.strip_prefix("./").unwrap_or(file).to_string(),
+            line: link["line"].as_u64().unwrap_or_default() as usize,
+            column: link["column"].as_u64().map(|col| col as usize),
+            link: link["url"].as_str().unwrap_or_default().to_string(),
+            message: link["reason"].as_str().unwrap_or("unreachable").to_string(),
+            severity,
+        });
+    }
+    let n_links = summary["links"].as_u64().unwrap_or(links.len() as u64) as u32;
+    Ok((findings, n_links, run))
+}
+
+/// Render a report in a machine readable format. Returns `None` for the human format,
+/// since each link is logged as it's checked instead.
+pub fn render(format: Format, findings: &[Finding], n_links: u32, run: &RunInfo) -> Option<Value> {
+    match format {
+        Format::Human => None,
+        // Code Quality reports are just a list of issues, with nowhere to put the rest.
+        Format::GitlabCodequality => Some(gitlab_codequality(findings)),
+        Format::Bitbucket => Some(bitbucket(findings, n_links, run)),
+    }
+}
+
+fn gitlab_codequality(findings: &[Finding]) -> Value {
+    let issues: Vec<Value> = findings
+        .iter()
+        .map(|finding| {
+            json!({
+                "type": "issue",
+                "check_name": "check-links",
+                "description": finding.description(),
+                "categories": ["Bug Risk"],
+                "severity": match finding.severity {
+                    FindingSeverity::Error => "major",
+                    FindingSeverity::Warning => "minor",
+                    FindingSeverity::Info => "info",
+                },
+                "fingerprint": finding.fingerprint(),
+                "location": match finding.column {
+                    Some(column) => json!({
+                        "path": finding.file,
+                        "positions": { "begin": { "line": finding.line, "column": column } },
+                    }),
+                    None => json!({
+                        "path": finding.file,
+                        "lines": { "begin": finding.line },
+                    }),
+                },
+            })
+        })
+        .collect();
+    Value::Array(issues)
+}
+
+fn bitbucket(findings: &[Finding], n_links: u32, run: &RunInfo) -> Value {
+    let count = |severity| findings.iter().filter(|f| f.severity == severity).count();
+    let n_errors = count(FindingSeverity::Error);
+    let n_warnings = count(FindingSeverity::Warning);
+    let n_ignored = count(FindingSeverity::Info);
+    let annotations: Vec<Value> = findings
+        .iter()
+        .map(|finding| {
+            json!({
+                "external_id": finding.fingerprint(),
+                "annotation_type": "BUG",
+                "summary": finding.description(),
+                "severity": match finding.severity {
+                    FindingSeverity::Error => "HIGH",
+                    FindingSeverity::Warning => "MEDIUM",
+                    FindingSeverity::Info => "LOW",
+                },
+                "result": if finding.severity == FindingSeverity::Info { "IGNORED" } else { "FAILED" },
+                "path": finding.file,
+                "line": finding.line,
+            })
+        })
+        .collect();
+    json!({
+        "report": {
+            "title": "check-links",
+            "details": format!("{} bad links out of {} found", n_errors + n_warnings, n_links),
+            "report_type": "TEST",
+            "reporter": "check-links",
+            "result": if n_errors > 0 { "FAILED" } else { "PASSED" },
+            "data": [
+                { "title": "Errors", "type": "NUMBER", "value": n_errors },
+                { "title": "Warnings", "type": "NUMBER", "value": n_warnings },
+                { "title": "Ignored", "type": "NUMBER", "value": n_ignored },
+                { "title": "Links", "type": "NUMBER", "value": n_links },
+                { "title": "Version", "type": "TEXT", "value": run.version },
+                { "title": "Commit", "type": "TEXT", "value": run.commit.as_deref().unwrap_or("unknown") },
+                { "title": "Config hash", "type": "TEXT", "value": run.config_hash },
+                { "title": "Started", "type": "DATE", "value": run.started_at * 1000 },
+            ],
+        },
+        "annotations": annotations,
+    })
+}
+
+/// A custom format for printing each link, like `{file}:{line} {status} {url}`.
+#[derive(Debug, Clone)]
+pub struct Template(Vec<Piece>);
+
+#[derive(Debug, Clone)]
+enum Piece {
+    Text(String),
+    Field(Field),
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Field {
+    File,
+    Line,
+    Column,
+    Url,
+    Status,
+    Reason,
+}
+
+impl FromStr for Template {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut pieces = vec![];
+        let mut text = String::new();
+        let mut chars = s.chars().peekable();
+        while let Some(c) = chars.next() {
+            match c {
+                // `{{` and `}}` are literal braces.
+                '{' if chars.peek() == Some(&'{') => {
+                    chars.next();
+                    text.push('{');
+                }
+                '}' if chars.peek() == Some(&'}') => {
+                    chars.next();
+                    text.push('}');
+                }
+                '{' => {
+                    let mut name = String::new();
+                    loop {
+                        match chars.next() {
+                            Some('}') => break,
+                            Some(c) => name.push(c),
+                            None => return Err(format!("unclosed field '{{{}'", name)),
+                        }
+                    }
+                    let field = match &name[..] {
+                        "file" => Field::File,
+                        "line" => Field::Line,
+                        "column" => Field::Column,
+                        "url" => Field::Url,
+                        "status" => Field::Status,
+                        "reason" => Field::Reason,
+                        _ => {
+                            return Err(format!(
+                                "unknown field '{{{}}}', expected one of {{file}}, {{line}}, \
+                                 {{column}}, {{url}}, {{status}}, or {{reason}}",
+                                name
+                            ))
+                        }
+                    };
+                    if !text.is_empty() {
+                        pieces.push(Piece::Text(text.split_off(0)));
+                    }
+                    pieces.push(Piece::Field(field));
+                }
+                _ => text.push(c),
+            }
+        }
+        if !text.is_empty() {
+            pieces.push(Piece::Text(text));
+        }
+        Ok(Template(pieces))
+    }
+}
+
+/// Get the name of a link's status along with the reason for it, if there is one.
+fn status_fields(link: &Link) -> (&'static str, Option<&Reason>) {
+    match &link.status {
+        Some(status) => status_name(status),
+        None => ("ok", None),
+    }
+}
+
+fn status_name(status: &LinkStatus) -> (&'static str, Option<&Reason>) {
+    match status {
+        LinkStatus::Reachable => ("ok", None),
+        LinkStatus::Questionable(reason) => ("warning", Some(reason)),
+        LinkStatus::Unreachable(reason) => ("error", reason.as_ref()),
+        LinkStatus::Skipped(reason) => ("skipped", Some(reason)),
+        LinkStatus::Ignored(reason) => ("ignored", Some(reason)),
+    }
+}
+
+/// Summarize a link's status in each environment, like `production: ok | staging:
+/// error`, if it was checked in several.
+pub fn environment_columns(link: &Link) -> Option<String> {
+    if link.environments.is_empty() {
+        return None;
+    }
+    let columns: Vec<String> = link
+        .environments
+        .iter()
+        .map(|(name, status)| format!("{}: {}", name, status_name(status).0))
+        .collect();
+    Some(columns.join(" | "))
+}
+
+/// Say where a link to a URL shortener really goes, if we followed it.
+pub fn expansion(link: &Link) -> Option<String> {
+    link.expanded
+        .as_ref()
+        .map(|expanded| format!("expands to {}", expanded))
+}
+
+/// Say what URL a link was checked at, if that isn't how it's written.
+pub fn rewrite(link: &Link) -> Option<String> {
+    link.rewritten
+        .as_ref()
+        .map(|rewritten| format!("checked as {}", rewritten))
+}
+
+/// Get the JSON for a link in a JSON report.
+pub fn link_json(link: &Link) -> Value {
+    let (status, reason) = status_fields(link);
+    let mut json = json!({
+        "file": link.file,
+        "line": link.lnum,
+        "column": link.col,
+        "url": link.raw,
+        "status": status,
+        "reason": reason.map(ToString::to_string),
+    });
+    if !link.tags.is_empty() {
+        let tags: Vec<String> = link.tags.iter().map(ToString::to_string).collect();
+        json["tags"] = json!(tags);
+    }
+    if let Some(heading) = &link.heading {
+        json["heading"] = json!(heading);
+    }
+    if let Some(rewritten) = &link.rewritten {
+        json["checked_as"] = json!(rewritten);
+    }
+    if let Some(expanded) = &link.expanded {
+        json["expanded"] = json!(expanded);
+    }
+    if !link.environments.is_empty() {
+        let environments: serde_json::Map<String, Value> = link
+            .environments
+            .iter()
+            .map(|(name, status)| {
+                let (status, reason) = status_name(status);
+                let reason = reason.map(ToString::to_string);
+                (name.clone(), json!({ "status": status, "reason": reason }))
+            })
+            .collect();
+        json["environments"] = Value::Object(environments);
+    }
+    json
+}
+
+impl Template {
+    pub fn render(&self, link: &Link) -> String {
+        let (status, reason) = status_fields(link);
+        let mut rendered = String::new();
+        for piece in &self.0 {
+            match piece {
+                Piece::Text(text) => rendered.push_str(text),
+                Piece::Field(Field::File) => rendered.push_str(&link.file.to_string_lossy()),
+                Piece::Field(Field::Line) => rendered.push_str(&link.lnum.to_string()),
+                Piece::Field(Field::Column) => {
+                    rendered.push_str(&link.col.map(|col| col.to_string()).unwrap_or_default())
+                }
+                Piece::Field(Field::Url) => rendered.push_str(&link.raw),
+                Piece::Field(Field::Status) => rendered.push_str(status),
+                Piece::Field(Field::Reason) => {
+                    rendered.push_str(&reason.map(ToString::to_string).unwrap_or_default())
+                }
+            }
+        }
+        rendered
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use super::*;
+
+    fn render(template: &str, link: &Link) -> String {
+        template.parse::<Template>().unwrap().render(link)
+    }
+
+    #[test]
+    fn template_fields() {
+        let mut link = Link::new(
+            PathBuf::from("docs/a.md"),
+            12,
+            String::from("https://a.b/c"),
+        );
+        link.col = Some(5);
+        link.status = Some(LinkStatus::Unreachable(Some(Reason::Status(404))));
+        assert_eq!(
+            render("{file}:{line}:{column} {status} {url} ({reason})", &link),
+            "docs/a.md:12:5 error https://a.b/c (received status code 404)"
+        );
+    }
+
+    #[test]
+    fn template_missing_values_are_empty() {
+        let link = Link::new(PathBuf::from("a.md"), 1, String::from("b.md"));
+        assert_eq!(render("[{column}] {status} [{reason}]", &link), "[] ok []");
+    }
+
+    #[test]
+    fn template_escaped_braces() {
+        let link = Link::new(PathBuf::from("a.md"), 1, String::from("b.md"));
+        assert_eq!(render("{{{url}}}", &link), "{b.md}");
+    }
+
+    #[test]
+    fn template_errors() {
+        assert!("{url".parse::<Template>().is_err());
+        assert!("{path}".parse::<Template>().is_err());
+    }
+}
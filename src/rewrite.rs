@@ -0,0 +1,651 @@
+use std::cmp::Reverse;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::fs::{self, File};
+use std::io::{self, Write};
+use std::iter;
+use std::path::{Path, PathBuf};
+use std::process;
+use std::str;
+use std::sync::Arc;
+
+use failure::{Error, ResultExt};
+
+use crate::checker::Checker;
+use crate::config::Config;
+use crate::doc_file::{wiki_page_name, wiki_page_path};
+use crate::link::{Link, LinkKind, LinkStatus, Reason};
+use crate::log::{Marker, Style};
+use crate::maybe_pluralize;
+
+/// A change to make to a link on a line of a file.
+pub struct Edit {
+    pub file: PathBuf,
+    pub lnum: usize,
+    /// The columns the link starts at and ends just before on its line, counting
+    /// characters from 1, if we know where it is.
+    pub cols: Option<(usize, usize)>,
+    /// Whether the link is a wiki link, which is written as the name of the page it's to.
+    pub wiki: bool,
+    pub old: String,
+    pub new: String,
+}
+
+impl Edit {
+    /// Change a link to a new one, wherever it's written on its line.
+    pub fn new(link: &Link, new: String) -> Self {
+        Edit {
+            file: link.file.clone(),
+            lnum: link.lnum,
+            cols: link.col.zip(link.end_col),
+            wiki: link.wiki,
+            old: link.raw.clone(),
+            new,
+        }
+    }
+
+    /// Find what to replace on the line the link is on, as a range of bytes, and what to
+    /// replace it with. Links aren't always written the way they're checked, like an Org
+    /// link to `file:design.org::*Goals` for `design.org#Goals`, so only the part of the
+    /// link before a section that's kept is replaced, and it has to be found where the
+    /// link is written.
+    fn replacement(&self, line: &str) -> Option<(usize, usize, String)> {
+        let (col, end_col) = self.cols?;
+        let start = byte_offset(line, col)?;
+        let written = line.get(start..byte_offset(line, end_col)?)?;
+        let (old, new, kept_section) = match (self.old.split_once('#'), self.new.split_once('#')) {
+            (Some((old, section)), Some((new, new_section)))
+                if section == new_section && !old.is_empty() =>
+            {
+                (old, new, true)
+            }
+            _ => (&self.old[..], &self.new[..], false),
+        };
+        if self.wiki {
+            let name = if kept_section {
+                written.split('#').next()?
+            } else {
+                written
+            };
+            if wiki_page_path(name) != old {
+                return None;
+            }
+            Some((start, start + name.len(), wiki_page_name(new)))
+        } else {
+            let i = written.find(old)?;
+            Some((start + i, start + i + old.len(), new.to_string()))
+        }
+    }
+}
+
+/// Get the byte offset of a column in a line, counting characters from 1.
+fn byte_offset(line: &str, col: usize) -> Option<usize> {
+    line.char_indices()
+        .map(|(i, _)| i)
+        .chain(iter::once(line.len()))
+        .nth(col.checked_sub(1)?)
+}
+
+/// Lines of context around the changes in a patch.
+const PATCH_CONTEXT: usize = 3;
+
+/// A file with edits made to it. Files are edited as bytes, so line endings and text
+/// that isn't UTF-8 come through untouched.
+struct EditedFile<'a> {
+    file: &'a Path,
+    contents: Vec<u8>,
+    old_lines: Vec<Vec<u8>>,
+    new_lines: Vec<Vec<u8>>,
+    /// The indices of the lines that changed.
+    changed: BTreeSet<usize>,
+    /// The edits to links that weren't where they were expected to be.
+    unmatched: Vec<&'a Edit>,
+}
+
+/// What came of making edits, or of writing them to a patch.
+pub struct Applied<'a> {
+    /// The number of lines that were changed.
+    pub n_changed: usize,
+    /// The edits to links that weren't where they were expected to be, so weren't made.
+    pub unmatched: Vec<&'a Edit>,
+    /// Why each file that was skipped couldn't be edited.
+    pub skipped: Vec<Error>,
+}
+
+impl Applied<'_> {
+    /// Print each edit that wasn't made, and why each file that was skipped couldn't be
+    /// edited.
+    pub fn print_problems(&self, style: Style) {
+        for edit in &self.unmatched {
+            let subject = match edit.cols {
+                Some((col, _)) => format!(
+                    "{} [line {}, col {}]: {} {} {}",
+                    edit.file.display(),
+                    edit.lnum,
+                    col,
+                    edit.old,
+                    style.arrow(),
+                    edit.new
+                ),
+                None => format!(
+                    "{} [line {}]: {} {} {}",
+                    edit.file.display(),
+                    edit.lnum,
+                    edit.old,
+                    style.arrow(),
+                    edit.new
+                ),
+            };
+            let details = ["not rewritten: couldn't find where the link is written"];
+            println!("{}", style.entry(Marker::Bad, &subject, &details));
+        }
+        for e in &self.skipped {
+            let causes: Vec<String> = e.iter_chain().skip(1).map(|c| c.to_string()).collect();
+            println!("{}", style.entry(Marker::Bad, &e.to_string(), &causes));
+        }
+    }
+}
+
+/// Make edits to the contents of the files they're in, without writing them. A file that
+/// can't be read gets an error in its place.
+fn edit_files(edits: &[Edit]) -> Vec<Result<EditedFile<'_>, Error>> {
+    let mut by_file: BTreeMap<&PathBuf, BTreeMap<usize, Vec<&Edit>>> = BTreeMap::new();
+    for edit in edits {
+        by_file
+            .entry(&edit.file)
+            .or_default()
+            .entry(edit.lnum)
+            .or_default()
+            .push(edit);
+    }
+    by_file
+        .into_iter()
+        .map(|(file, by_line)| edit_file(file, by_line))
+        .collect()
+}
+
+fn edit_file<'a>(
+    file: &'a Path,
+    by_line: BTreeMap<usize, Vec<&'a Edit>>,
+) -> Result<EditedFile<'a>, Error> {
+    let contents = fs::read(file).with_context(|_| format!("failed to read {}", file.display()))?;
+    let old_lines: Vec<Vec<u8>> = contents
+        .split_inclusive(|&b| b == b'\n')
+        .map(Vec::from)
+        .collect();
+    let mut new_lines = old_lines.clone();
+    let mut changed = BTreeSet::new();
+    let mut unmatched = vec![];
+    for (lnum, edits) in by_line {
+        let line = match old_lines.get(lnum - 1) {
+            Some(line) => line,
+            None => {
+                unmatched.extend(edits);
+                continue;
+            }
+        };
+        let (new_line, missed) = edit_line(line, &edits);
+        unmatched.extend(missed);
+        if new_line != *line {
+            new_lines[lnum - 1] = new_line;
+            changed.insert(lnum - 1);
+        }
+    }
+    Ok(EditedFile {
+        file,
+        contents,
+        old_lines,
+        new_lines,
+        changed,
+        unmatched,
+    })
+}
+
+/// Make all the edits to a line in one pass, each one where its link is written on the
+/// line. Returns the edited line along with the edits whose links weren't there. Where
+/// two edits overlap, the one that starts first wins.
+fn edit_line<'a>(line: &[u8], edits: &[&'a Edit]) -> (Vec<u8>, Vec<&'a Edit>) {
+    // Lines with links on them are always UTF-8, since that's how the links were found.
+    let text = match str::from_utf8(line) {
+        Ok(text) => text,
+        Err(_) => return (line.to_vec(), edits.to_vec()),
+    };
+    let mut unmatched = vec![];
+    let mut replacements = vec![];
+    for &edit in edits {
+        match edit.replacement(text) {
+            Some(replacement) => replacements.push(replacement),
+            None => unmatched.push(edit),
+        }
+    }
+    replacements.sort_by_key(|&(start, end, _)| (start, Reverse(end)));
+    let mut edited = Vec::with_capacity(line.len());
+    let mut pos = 0;
+    for (start, end, new) in replacements {
+        if start < pos {
+            continue;
+        }
+        edited.extend_from_slice(&line[pos..start]);
+        edited.extend_from_slice(new.as_bytes());
+        pos = end;
+    }
+    edited.extend_from_slice(&line[pos..]);
+    (edited, unmatched)
+}
+
+/// Apply edits to the files they're in, carrying on past files that can't be edited.
+pub fn apply_edits(edits: &[Edit]) -> Applied<'_> {
+    let mut applied = Applied {
+        n_changed: 0,
+        unmatched: vec![],
+        skipped: vec![],
+    };
+    for edited in edit_files(edits) {
+        let result = edited.and_then(|edited| {
+            if !edited.changed.is_empty() {
+                write_atomically(edited.file, &edited.new_lines.concat(), &edited.contents)?;
+            }
+            Ok(edited)
+        });
+        match result {
+            Ok(edited) => {
+                applied.n_changed += edited.changed.len();
+                applied.unmatched.extend(edited.unmatched);
+            }
+            Err(e) => applied.skipped.push(e),
+        }
+    }
+    applied
+}
+
+/// Replace the contents of a file, as long as it still has the contents we read from it.
+/// The new contents go to a temporary file next to it, which is then renamed over it,
+/// so the file is never left half-written if we're interrupted.
+fn write_atomically(path: &Path, contents: &[u8], expected: &[u8]) -> Result<(), Error> {
+    let current = fs::read(path).with_context(|_| format!("failed to read {}", path.display()))?;
+    if current != expected {
+        return Err(failure::format_err!(
+            "{} changed while it was being edited, so it was left alone",
+            path.display()
+        ));
+    }
+    let name = path.file_name().unwrap_or_default().to_string_lossy();
+    let temp = path.with_file_name(format!(".{}.check-links-{}", name, process::id()));
+    let result = (|| -> io::Result<()> {
+        let mut file = File::create(&temp)?;
+        file.write_all(contents)?;
+        file.sync_all()?;
+        fs::set_permissions(&temp, fs::metadata(path)?.permissions())?;
+        fs::rename(&temp, path)
+    })();
+    if result.is_err() {
+        let _ = fs::remove_file(&temp);
+    }
+    result.with_context(|_| format!("failed to write {}", path.display()))?;
+    Ok(())
+}
+
+/// Write the edits to a patch file as a unified diff, for `git apply`, instead of making
+/// them. Fails if any of the files can't be read.
+pub fn write_patch<'a>(edits: &'a [Edit], path: &Path) -> Result<Applied<'a>, Error> {
+    let mut patch = vec![];
+    let mut applied = Applied {
+        n_changed: 0,
+        unmatched: vec![],
+        skipped: vec![],
+    };
+    for edited in edit_files(edits) {
+        let edited = edited?;
+        applied.unmatched.extend(&edited.unmatched);
+        if edited.changed.is_empty() {
+            continue;
+        }
+        applied.n_changed += edited.changed.len();
+        let name = edited.file.strip_prefix("./").unwrap_or(edited.file);
+        patch.extend_from_slice(
+            format!("--- a/{}\n+++ b/{}\n", name.display(), name.display()).as_bytes(),
+        );
+        for (start, end) in hunks(&edited.changed, edited.old_lines.len()) {
+            // Edits only ever change lines in place, so both sides have as many lines.
+            patch.extend_from_slice(
+                format!(
+                    "@@ -{},{} +{},{} @@\n",
+                    start + 1,
+                    end - start,
+                    start + 1,
+                    end - start
+                )
+                .as_bytes(),
+            );
+            let mut i = start;
+            while i < end {
+                if !edited.changed.contains(&i) {
+                    push_patch_line(&mut patch, b' ', &edited.old_lines[i]);
+                    i += 1;
+                    continue;
+                }
+                let run_end = (i..end)
+                    .find(|j| !edited.changed.contains(j))
+                    .unwrap_or(end);
+                for line in &edited.old_lines[i..run_end] {
+                    push_patch_line(&mut patch, b'-', line);
+                }
+                for line in &edited.new_lines[i..run_end] {
+                    push_patch_line(&mut patch, b'+', line);
+                }
+                i = run_end;
+            }
+        }
+    }
+    fs::write(path, patch).with_context(|_| format!("failed to write {}", path.display()))?;
+    Ok(applied)
+}
+
+/// Group changed lines into hunks with some context around them, as the ranges of
+/// lines each hunk covers.
+fn hunks(changed: &BTreeSet<usize>, n_lines: usize) -> Vec<(usize, usize)> {
+    let mut hunks: Vec<(usize, usize)> = vec![];
+    for &i in changed {
+        let start = i.saturating_sub(PATCH_CONTEXT);
+        let end = (i + 1 + PATCH_CONTEXT).min(n_lines);
+        match hunks.last_mut() {
+            Some(last) if start <= last.1 => last.1 = end,
+            _ => hunks.push((start, end)),
+        }
+    }
+    hunks
+}
+
+fn push_patch_line(patch: &mut Vec<u8>, prefix: u8, line: &[u8]) {
+    patch.push(prefix);
+    patch.extend_from_slice(line);
+    if !line.ends_with(b"\n") {
+        patch.extend_from_slice(b"\n\\ No newline at end of file\n");
+    }
+}
+
+/// Rewrite links that start with `from` to start with `to` instead, as long as the
+/// rewritten links work. With `dry_run`, only print what would change.
+pub async fn rewrite(
+    links: Vec<(Link, Arc<Config>)>,
+    from: &str,
+    to: &str,
+    dry_run: bool,
+    patch: Option<&Path>,
+    checker: Arc<Checker>,
+    style: Style,
+) -> Result<bool, Error> {
+    let changes = links
+        .into_iter()
+        .filter(|(link, _)| link.raw.starts_with(from))
+        .map(|(link, config)| {
+            let new = format!("{}{}", to, &link.raw[from.len()..]);
+            (link, new, config)
+        })
+        .collect();
+    change_links(changes, dry_run, patch, checker, style).await
+}
+
+/// What a new link is checked as. Links other than HTTP links are resolved from the file
+/// they're in, so they're only the same link within that file.
+fn target_key(link: &Link, new: &str) -> (Option<PathBuf>, String) {
+    let file = match LinkKind::of(new) {
+        LinkKind::Http => None,
+        _ => Some(link.file.clone()),
+    };
+    (file, new.to_string())
+}
+
+/// Change each link to the new link paired with it, as long as the new link works. With
+/// `dry_run`, only print what would change. Returns whether all of them were changed.
+pub async fn change_links(
+    changes: Vec<(Link, String, Arc<Config>)>,
+    dry_run: bool,
+    patch: Option<&Path>,
+    checker: Arc<Checker>,
+    style: Style,
+) -> Result<bool, Error> {
+    // Check each new link once, no matter how many times it appears.
+    let mut handles = HashMap::new();
+    for (link, new, config) in &changes {
+        let key = target_key(link, new);
+        if handles.contains_key(&key) {
+            continue;
+        }
+        let mut target = Link::new(link.file.clone(), link.lnum, new.clone());
+        let checker = checker.clone();
+        let config = config.clone();
+        let handle = tokio::spawn(async move {
+            target.verify(checker, config).await;
+            target.status
+        });
+        handles.insert(key, handle);
+    }
+    let mut statuses = HashMap::new();
+    for (key, handle) in handles {
+        statuses.insert(key, handle.await?);
+    }
+
+    let mut edits = vec![];
+    let mut n_broken = 0;
+    for (link, new, _) in changes {
+        match &statuses[&target_key(&link, &new)] {
+            Some(LinkStatus::Reachable) => {
+                println!("{} {} {}", link, style.arrow(), new);
+                edits.push(Edit::new(&link, new));
+            }
+            status => {
+                n_broken += 1;
+                let reason = match status {
+                    Some(LinkStatus::Questionable(reason))
+                    | Some(LinkStatus::Unreachable(Some(reason)))
+                    | Some(LinkStatus::Skipped(reason))
+                    | Some(LinkStatus::Ignored(reason)) => reason.clone(),
+                    _ => Reason::unreachable(),
+                };
+                let subject = format!("{} {} {}", link, style.arrow(), new);
+                let details = [format!("not rewritten: {}", reason)];
+                println!("{}", style.entry(Marker::Bad, &subject, &details));
+            }
+        }
+    }
+
+    if dry_run {
+        println!(
+            "Would rewrite {} link{} ({} not rewritten)",
+            edits.len(),
+            maybe_pluralize(edits.len()),
+            n_broken
+        );
+        return Ok(n_broken == 0);
+    }
+    let applied = match patch {
+        Some(patch) => write_patch(&edits, patch)?,
+        None => apply_edits(&edits),
+    };
+    applied.print_problems(style);
+    let n_broken = n_broken + applied.unmatched.len();
+    match patch {
+        Some(patch) => println!(
+            "Wrote a patch rewriting {} line{} to {} ({} link{} not rewritten)",
+            applied.n_changed,
+            maybe_pluralize(applied.n_changed),
+            patch.display(),
+            n_broken,
+            maybe_pluralize(n_broken)
+        ),
+        None => println!(
+            "Rewrote {} line{} ({} link{} not rewritten, {} file{} skipped)",
+            applied.n_changed,
+            maybe_pluralize(applied.n_changed),
+            n_broken,
+            maybe_pluralize(n_broken),
+            applied.skipped.len(),
+            maybe_pluralize(applied.skipped.len())
+        ),
+    }
+    Ok(n_broken == 0 && applied.skipped.is_empty())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+
+    /// An edit to a link that's written as `written` on a line.
+    fn edit(line: &str, written: &str, old: &str, new: &str) -> Edit {
+        let start = line[..line.find(written).unwrap()].chars().count() + 1;
+        Edit {
+            file: PathBuf::from("README.md"),
+            lnum: 1,
+            cols: Some((start, start + written.chars().count())),
+            wiki: false,
+            old: old.to_string(),
+            new: new.to_string(),
+        }
+    }
+
+    fn edited(line: &str, edits: &[Edit]) -> (String, usize) {
+        let edits: Vec<&Edit> = edits.iter().collect();
+        let (edited, unmatched) = edit_line(line.as_bytes(), &edits);
+        (String::from_utf8(edited).unwrap(), unmatched.len())
+    }
+
+    #[test]
+    fn edits_links_where_they_are_written() {
+        let line = "[a](http://a.io) and <http://a.io>. é [b](http://a.io/docs)\n";
+        let edits = [
+            edit(line, "http://a.io)", "http://a.io", "https://a.io"),
+            edit(
+                line,
+                "http://a.io/docs",
+                "http://a.io/docs",
+                "https://a.io/docs",
+            ),
+        ];
+        assert_eq!(
+            edited(line, &edits),
+            (
+                String::from("[a](https://a.io) and <http://a.io>. é [b](https://a.io/docs)\n"),
+                0
+            )
+        );
+    }
+
+    #[test]
+    fn edits_keep_what_the_link_was_trimmed_from() {
+        let line = "See http://a.io.\n";
+        let edits = [edit(line, "http://a.io.", "http://a.io", "https://a.io")];
+        assert_eq!(
+            edited(line, &edits),
+            (String::from("See https://a.io.\n"), 0)
+        );
+    }
+
+    #[test]
+    fn edits_org_links() {
+        let line = "[[file:Old-Page.org::*Our Goals][goals]]\n";
+        let written = "file:Old-Page.org::*Our Goals";
+        let edits = [edit(
+            line,
+            written,
+            "Old-Page.org#Our-Goals",
+            "guide/New.org#Our-Goals",
+        )];
+        assert_eq!(
+            edited(line, &edits),
+            (
+                String::from("[[file:guide/New.org::*Our Goals][goals]]\n"),
+                0
+            )
+        );
+    }
+
+    #[test]
+    fn edits_wiki_links() {
+        let line = "[[Old Page]] and [[text|Old Page#Intro]]\n";
+        let mut edits = [
+            edit(line, "Old Page", "Old-Page.md", "New-Page.md"),
+            edit(
+                line,
+                "Old Page#Intro",
+                "Old-Page.md#Intro",
+                "New-Page.md#Intro",
+            ),
+        ];
+        for edit in &mut edits {
+            edit.wiki = true;
+        }
+        assert_eq!(
+            edited(line, &edits),
+            (
+                String::from("[[New-Page]] and [[text|New-Page#Intro]]\n"),
+                0
+            )
+        );
+    }
+
+    #[test]
+    fn edits_that_match_nothing_are_returned() {
+        let line = "[a](http://a.io)\n";
+        let mut edits = [
+            edit(line, "http://a.io", "http://b.io", "https://b.io"),
+            edit(line, "http://a.io", "http://a.io", "https://a.io"),
+        ];
+        edits[1].cols = None;
+        assert_eq!(edited(line, &edits), (String::from(line), 2));
+    }
+
+    fn lines(lines: &[usize]) -> BTreeSet<usize> {
+        lines.iter().cloned().collect()
+    }
+
+    #[test]
+    fn hunks_have_context_around_changes() {
+        assert_eq!(hunks(&lines(&[10]), 30), vec![(7, 14)]);
+    }
+
+    #[test]
+    fn hunks_stop_at_the_ends_of_the_file() {
+        assert_eq!(hunks(&lines(&[1]), 3), vec![(0, 3)]);
+        assert_eq!(hunks(&lines(&[0, 29]), 30), vec![(0, 4), (26, 30)]);
+    }
+
+    #[test]
+    fn hunks_with_overlapping_context_are_merged() {
+        assert_eq!(hunks(&lines(&[5, 11]), 30), vec![(2, 15)]);
+        assert_eq!(hunks(&lines(&[5, 13]), 30), vec![(2, 9), (10, 17)]);
+    }
+
+    #[tokio::test]
+    async fn relative_links_are_checked_from_each_file() {
+        let dir = std::env::temp_dir().join(format!("check-links-rewrite-{}", process::id()));
+        fs::create_dir_all(dir.join("docs")).unwrap();
+        fs::write(dir.join("New.md"), "").unwrap();
+        let files = [dir.join("index.md"), dir.join("docs").join("index.md")];
+        let mut changes = vec![];
+        for file in &files {
+            fs::write(file, "[page](Old.md)\n").unwrap();
+            let mut link = Link::new(file.clone(), 1, String::from("Old.md"));
+            link.col = Some(8);
+            link.end_col = Some(14);
+            changes.push((link, String::from("New.md"), Arc::new(Config::default())));
+        }
+        let checker = Arc::new(Checker::new(
+            isahc::HttpClient::new().unwrap(),
+            Duration::from_secs(0),
+            1,
+            0,
+        ));
+        let changed = change_links(changes, false, None, checker, Style::default())
+            .await
+            .unwrap();
+        let contents: Vec<String> = files
+            .iter()
+            .map(|file| fs::read_to_string(file).unwrap())
+            .collect();
+        fs::remove_dir_all(&dir).unwrap();
+        assert!(!changed);
+        assert_eq!(contents, ["[page](New.md)\n", "[page](Old.md)\n"]);
+    }
+}
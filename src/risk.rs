@@ -0,0 +1,125 @@
+use std::fmt;
+use std::time::{Duration, SystemTime};
+
+use crate::cache::CacheEntry;
+use crate::link::Link;
+
+/// Risk scores at or above this are worth bringing to the user's attention.
+pub const HIGH_RISK: u32 = 50;
+
+/// Hosts that are unlikely to disappear or reorganize their URLs.
+const STABLE_HOSTS: &[&str] = &[
+    "crates.io",
+    "docs.rs",
+    "doc.rust-lang.org",
+    "rust-lang.org",
+    "github.com",
+    "gitlab.com",
+    "wikipedia.org",
+    "developer.mozilla.org",
+    "w3.org",
+    "ietf.org",
+    "python.org",
+    "readthedocs.io",
+];
+
+/// Hosts that serve pages for individual people, which tend to go away with them.
+const PERSONAL_HOSTS: &[&str] = &[
+    "github.io",
+    "gitlab.io",
+    "blogspot.com",
+    "wordpress.com",
+    "medium.com",
+    "substack.com",
+    "tumblr.com",
+    "netlify.app",
+    "herokuapp.com",
+];
+
+const YEAR: Duration = Duration::from_secs(365 * 24 * 60 * 60);
+
+/// An estimate of how likely a link is to break in the future, from 0 to 100.
+pub struct Risk {
+    pub score: u32,
+    pub reasons: Vec<String>,
+}
+
+fn host_matches(host: &str, domains: &[&str]) -> bool {
+    domains
+        .iter()
+        .any(|domain| host == *domain || host.ends_with(&format!(".{}", domain)))
+}
+
+impl Risk {
+    /// Assess the risk of an HTTP link from the response we got and what we know about it
+    /// from previous runs.
+    pub fn assess(link: &Link, history: Option<&CacheEntry>) -> Self {
+        let mut score = 0;
+        let mut reasons = vec![];
+
+        if link.raw.starts_with("http://") {
+            score += 20;
+            reasons.push(String::from("plain HTTP"));
+        }
+
+        let host = link.host().unwrap_or_default();
+        if host_matches(&host, PERSONAL_HOSTS) || link.raw.contains("/~") {
+            score += 20;
+            reasons.push(String::from("personal site"));
+        } else if !host_matches(&host, STABLE_HOSTS) {
+            score += 5;
+        }
+
+        if let Some(response) = &link.response {
+            if response.status >= 300 && response.status < 400 && response.status != 304 {
+                // Moving to a different host is a bigger change than moving within one.
+                let same_host = match &response.location {
+                    Some(location) => !location.starts_with("http") || location.contains(&host),
+                    None => true,
+                };
+                if same_host {
+                    score += 15;
+                    reasons.push(String::from("redirects"));
+                } else {
+                    score += 25;
+                    reasons.push(String::from("redirects to another host"));
+                }
+            }
+            if let Some(last_modified) = response.last_modified {
+                let age = SystemTime::now()
+                    .duration_since(last_modified)
+                    .unwrap_or_default();
+                let years = age.as_secs() / YEAR.as_secs();
+                if years >= 2 {
+                    score += if years >= 5 { 20 } else { 10 };
+                    reasons.push(format!("not modified in {} years", years));
+                }
+            }
+        }
+
+        if let Some(history) = history {
+            if history.checks > 0 && history.failures > 0 {
+                score += 40 * history.failures / history.checks;
+                reasons.push(format!(
+                    "failed {} of {} previous checks",
+                    history.failures, history.checks
+                ));
+            }
+        }
+
+        Risk {
+            score: std::cmp::min(score, 100),
+            reasons,
+        }
+    }
+}
+
+impl fmt::Display for Risk {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}/100", self.score)?;
+        if !self.reasons.is_empty() {
+            write!(f, " ({})", self.reasons.join(", "))?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,160 @@
+use std::fmt;
+use std::str::FromStr;
+
+use crate::cache::{now, Cache};
+use crate::maybe_pluralize;
+use crate::snapshot::fnv1a;
+
+const DAY: u64 = 24 * 60 * 60;
+
+/// The share of external links to check in a run, as a percentage.
+#[derive(Debug, Clone, Copy)]
+pub struct SampleRate(u64);
+
+impl FromStr for SampleRate {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let percent = s.trim_end_matches('%');
+        match percent.parse() {
+            Ok(percent) if (1..=100).contains(&percent) => Ok(SampleRate(percent)),
+            _ => Err(format!(
+                "invalid sample '{}', expected a percentage like '10%'",
+                s
+            )),
+        }
+    }
+}
+
+/// A reproducible subset of the external links. The links are split into slices by a
+/// hash of their URL, and the seed picks the slice, so the same seed always checks the
+/// same links and consecutive seeds cover all of them. Each slice is `1/n` of the links,
+/// the closest such share to the sample rate that's at least as big.
+pub struct Sample {
+    slices: u64,
+    slice: u64,
+    seed: u64,
+}
+
+impl Sample {
+    /// Seeds default to the number of days since the Unix epoch, so that nightly runs
+    /// each check the next slice.
+    pub fn new(rate: SampleRate, seed: Option<u64>) -> Self {
+        let slices = 100 / rate.0;
+        let seed = seed.unwrap_or_else(|| now() / DAY);
+        Sample {
+            slices,
+            slice: seed % slices,
+            seed,
+        }
+    }
+
+    /// Check if a URL is in this run's sample.
+    pub fn contains(&self, url: &str) -> bool {
+        fnv1a(url.as_bytes()) % self.slices == self.slice
+    }
+}
+
+impl fmt::Display for Sample {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "slice {} of {} (seed {})",
+            self.slice + 1,
+            self.slices,
+            self.seed
+        )
+    }
+}
+
+/// The external links that were left out of a run, and how long it's been since they
+/// were last checked according to the cache.
+#[derive(Default)]
+pub struct Coverage {
+    pub checked: u32,
+    pub unchecked: u32,
+    /// Links left out of this run that haven't been checked in any run.
+    pub never_checked: u32,
+    /// When the link that's gone longest without being checked was last checked, in
+    /// seconds since the Unix epoch.
+    pub oldest_check: Option<u64>,
+}
+
+impl Coverage {
+    pub fn record_unchecked(&mut self, url: &str, cache: Option<&Cache>) {
+        self.unchecked += 1;
+        match cache.and_then(|cache| cache.get(url)?.last_checked) {
+            Some(last_checked) => {
+                self.oldest_check = Some(match self.oldest_check {
+                    Some(oldest) => oldest.min(last_checked),
+                    None => last_checked,
+                });
+            }
+            None => self.never_checked += 1,
+        }
+    }
+
+    /// Describe how up to date the links left out of the run are.
+    pub fn describe(&self) -> String {
+        let mut description = format!(
+            "{} external link{} checked, {} left for other runs",
+            self.checked,
+            maybe_pluralize(self.checked),
+            self.unchecked,
+        );
+        if self.never_checked > 0 {
+            description.push_str(&format!(
+                ", {} of which {} never been checked",
+                self.never_checked,
+                if self.never_checked == 1 {
+                    "has"
+                } else {
+                    "have"
+                },
+            ));
+        } else if let Some(oldest) = self.oldest_check {
+            let days = now().saturating_sub(oldest) / DAY + 1;
+            if days == 1 {
+                description.push_str(", all of which were checked within the last day");
+            } else {
+                description.push_str(&format!(
+                    ", all of which were checked within the last {} days",
+                    days
+                ));
+            }
+        }
+        description
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_rate() {
+        assert_eq!("10%".parse::<SampleRate>().unwrap().0, 10);
+        assert_eq!("100".parse::<SampleRate>().unwrap().0, 100);
+        assert!("0%".parse::<SampleRate>().is_err());
+        assert!("101%".parse::<SampleRate>().is_err());
+        assert!("half".parse::<SampleRate>().is_err());
+    }
+
+    #[test]
+    fn consecutive_seeds_cover_every_link() {
+        // 30% rounds up to a third of the links in each run.
+        let rate: SampleRate = "30%".parse().unwrap();
+        let samples: Vec<Sample> = (0..3).map(|seed| Sample::new(rate, Some(seed))).collect();
+        for i in 0..100 {
+            let url = format!("https://example.com/{}", i);
+            let n = samples
+                .iter()
+                .filter(|sample| sample.contains(&url))
+                .count();
+            assert_eq!(n, 1, "{} is in {} samples", url, n);
+        }
+        // The same seed checks the same links.
+        let again = Sample::new(rate, Some(3));
+        assert_eq!(again.slice, samples[0].slice);
+    }
+}
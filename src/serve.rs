@@ -0,0 +1,114 @@
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use failure::{Error, ResultExt};
+use serde_json::{json, Value};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+use url::Url;
+
+use crate::checker::Checker;
+use crate::config::Config;
+use crate::link::Link;
+use crate::report;
+
+/// The most of a request we'll read. Only the request line matters.
+const MAX_REQUEST_SIZE: usize = 8192;
+
+/// Answer requests to check links until we're stopped. `GET /check?url=...` checks the
+/// link and answers with the same JSON `--tee-json` has for it.
+pub async fn serve(listen: &str, checker: Arc<Checker>, config: Arc<Config>) -> Result<(), Error> {
+    let mut listener = TcpListener::bind(listen)
+        .await
+        .with_context(|_| format!("failed to listen on {}", listen))?;
+    loop {
+        let (stream, _) = listener.accept().await?;
+        let checker = checker.clone();
+        let config = config.clone();
+        tokio::spawn(async move {
+            // There's no one to tell if the client went away.
+            let _ = handle(stream, checker, config).await;
+        });
+    }
+}
+
+async fn handle(
+    mut stream: TcpStream,
+    checker: Arc<Checker>,
+    config: Arc<Config>,
+) -> Result<(), Error> {
+    let mut request = vec![];
+    let mut buf = [0; 1024];
+    while !request.windows(4).any(|w| w == b"\r\n\r\n") && request.len() < MAX_REQUEST_SIZE {
+        let n = stream.read(&mut buf).await?;
+        if n == 0 {
+            break;
+        }
+        request.extend_from_slice(&buf[..n]);
+    }
+    let request = String::from_utf8_lossy(&request);
+    let (status, body) = respond(request.lines().next().unwrap_or_default(), checker, config).await;
+    let body = serde_json::to_string(&body)?;
+    let response = format!(
+        "HTTP/1.1 {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        status,
+        body.len(),
+        body
+    );
+    stream.write_all(response.as_bytes()).await?;
+    Ok(())
+}
+
+/// Get the status and JSON body of the response to a request, from its first line,
+/// like `GET /check?url=https%3A%2F%2Fexample.com HTTP/1.1`.
+async fn respond(
+    request_line: &str,
+    checker: Arc<Checker>,
+    config: Arc<Config>,
+) -> (&'static str, Value) {
+    let mut parts = request_line.split(' ');
+    let (method, target) = match (parts.next(), parts.next()) {
+        (Some(method), Some(target)) => (method, target),
+        _ => return error("400 Bad Request", "malformed request"),
+    };
+    if method != "GET" {
+        return error("405 Method Not Allowed", "only GET is supported");
+    }
+    let target = match Url::parse("http://localhost").and_then(|base| base.join(target)) {
+        Ok(target) => target,
+        Err(_) => return error("400 Bad Request", "malformed request"),
+    };
+    if target.path() != "/check" {
+        return error("404 Not Found", "not found, try GET /check?url=...");
+    }
+    let url = match target.query_pairs().find(|(name, _)| name == "url") {
+        Some((_, url)) => url.into_owned(),
+        None => return error("400 Bad Request", "missing url parameter"),
+    };
+    // Anything else would be a path on our file system.
+    if !url.starts_with("http://") && !url.starts_with("https://") {
+        return error(
+            "400 Bad Request",
+            "only http and https links can be checked",
+        );
+    }
+    let mut link = Link::new(PathBuf::new(), 0, url);
+    link.verify(checker.clone(), config).await;
+    if let Some(cache) = &checker.cache {
+        if let Err(e) = cache.save() {
+            eprintln!("{}", e);
+        }
+    }
+    let mut json = report::link_json(&link);
+    // There's no file that the link was found in.
+    if let Some(fields) = json.as_object_mut() {
+        fields.remove("file");
+        fields.remove("line");
+        fields.remove("column");
+    }
+    ("200 OK", json)
+}
+
+fn error(status: &'static str, message: &str) -> (&'static str, Value) {
+    (status, json!({ "error": message }))
+}
@@ -0,0 +1,142 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use failure::{Error, ResultExt};
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+
+/// The number of bits (out of 64) that have to differ between two content hashes for us
+/// to consider the content substantially changed.
+const CHANGED_BITS: u32 = 12;
+
+/// Hashes of the content of linked pages, recorded so that later runs can tell when a
+/// page's content has changed even though its URL hasn't.
+pub struct Snapshot {
+    path: PathBuf,
+    /// Whether to replace the recorded hash when a page has changed.
+    update: bool,
+    entries: Mutex<BTreeMap<String, Entry>>,
+}
+
+#[derive(Clone, Deserialize, Serialize)]
+struct Entry {
+    /// Hex encoded similarity hash of the page's content.
+    hash: String,
+    /// When the hash was recorded, in seconds since the Unix epoch.
+    recorded: u64,
+}
+
+impl Snapshot {
+    /// Load a snapshot file, or start a new one if it doesn't exist yet.
+    pub fn load(path: &Path, update: bool) -> Result<Self, Error> {
+        let entries = if path.exists() {
+            let contents = fs::read_to_string(path)
+                .with_context(|_| format!("failed to read snapshot {}", path.display()))?;
+            serde_json::from_str(&contents)
+                .with_context(|_| format!("failed to parse snapshot {}", path.display()))?
+        } else {
+            BTreeMap::new()
+        };
+        Ok(Snapshot {
+            path: path.to_path_buf(),
+            update,
+            entries: Mutex::new(entries),
+        })
+    }
+
+    /// Compare the content of a page to what was recorded before, returning a reason if
+    /// it's changed substantially. Pages we haven't seen before are recorded.
+    pub fn check(&self, url: &str, body: &str) -> Option<String> {
+        let hash = content_hash(body);
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs();
+        let entry = Entry {
+            hash: format!("{:016x}", hash),
+            recorded: now,
+        };
+        let mut entries = self.entries.lock().unwrap();
+        let previous = match entries.get(url) {
+            Some(previous) => previous.clone(),
+            None => {
+                entries.insert(url.to_string(), entry);
+                return None;
+            }
+        };
+        let previous_hash = u64::from_str_radix(&previous.hash, 16).unwrap_or(0);
+        if (previous_hash ^ hash).count_ones() < CHANGED_BITS {
+            return None;
+        }
+        if self.update {
+            entries.insert(url.to_string(), entry);
+        }
+        let recorded = UNIX_EPOCH + Duration::from_secs(previous.recorded);
+        Some(format!(
+            "content changed since snapshot was recorded on {}",
+            httpdate::fmt_http_date(recorded)
+        ))
+    }
+
+    pub fn save(&self) -> Result<(), Error> {
+        let entries = self.entries.lock().unwrap();
+        let contents = serde_json::to_string_pretty(&*entries)?;
+        fs::write(&self.path, contents)
+            .with_context(|_| format!("failed to write snapshot {}", self.path.display()))?;
+        Ok(())
+    }
+}
+
+/// Get the main text content of an HTML page, ignoring markup, scripts, and navigation.
+fn main_content(body: &str) -> String {
+    lazy_static! {
+        static ref MAIN_RE: Regex =
+            Regex::new(r"(?is)<(main|article)[\s>].*</(main|article)>").unwrap();
+        static ref BOILERPLATE_RE: Regex = Regex::new(
+            r"(?is)<(script|style|nav|header|footer)[\s>].*?</(script|style|nav|header|footer)>"
+        )
+        .unwrap();
+        static ref TAG_RE: Regex = Regex::new(r"(?s)<[^>]*>").unwrap();
+    }
+    let body = match MAIN_RE.find(body) {
+        Some(m) => m.as_str(),
+        None => body,
+    };
+    let body = BOILERPLATE_RE.replace_all(body, " ");
+    TAG_RE.replace_all(&body, " ").to_lowercase()
+}
+
+/// Compute a similarity hash (simhash) of a page's main content. Unlike a regular hash,
+/// pages with mostly the same content end up with hashes that differ in only a few bits.
+fn content_hash(body: &str) -> u64 {
+    let content = main_content(body);
+    let words: Vec<&str> = content.split_whitespace().collect();
+    let mut weights = [0i64; 64];
+    // Hash overlapping runs of three words so that word order matters.
+    for shingle in words.windows(3) {
+        let h = fnv1a(shingle.join(" ").as_bytes());
+        for (i, weight) in weights.iter_mut().enumerate() {
+            if h & (1 << i) != 0 {
+                *weight += 1;
+            } else {
+                *weight -= 1;
+            }
+        }
+    }
+    weights
+        .iter()
+        .enumerate()
+        .filter(|(_, weight)| **weight > 0)
+        .fold(0, |hash, (i, _)| hash | (1 << i))
+}
+
+/// A simple hash that's stable across platforms and Rust versions, since hashes are
+/// saved between runs.
+pub fn fnv1a(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf2_9ce4_8422_2325, |hash, byte| {
+        (hash ^ u64::from(*byte)).wrapping_mul(0x0100_0000_01b3)
+    })
+}
@@ -0,0 +1,118 @@
+use std::str::FromStr;
+
+use crate::link::{Link, LinkStatus, RequestError};
+
+/// Matches the ways a link can fail, like `404`, `5xx`, or `timeout`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum StatusPattern {
+    /// A specific status code.
+    Code(u16),
+    /// A class of status codes, like `5xx`, given by its first digit.
+    Class(u16),
+    Timeout,
+    /// The host's name couldn't be resolved.
+    Dns,
+    /// We couldn't connect to the host.
+    Connect,
+}
+
+impl StatusPattern {
+    /// Check if a link with the given status is bad in the way this matches.
+    pub fn is_match(self, link: &Link, status: &LinkStatus) -> bool {
+        match status {
+            LinkStatus::Questionable(_) | LinkStatus::Unreachable(_) => {}
+            _ => return false,
+        }
+        let status = link.response.as_ref().map(|response| response.status);
+        match self {
+            StatusPattern::Code(code) => status == Some(code),
+            StatusPattern::Class(class) => status.map(|s| s / 100) == Some(class),
+            StatusPattern::Timeout => link.request_error == Some(RequestError::Timeout),
+            StatusPattern::Dns => link.request_error == Some(RequestError::NameResolution),
+            StatusPattern::Connect => link.request_error == Some(RequestError::Connect),
+        }
+    }
+}
+
+impl FromStr for StatusPattern {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim().to_lowercase();
+        match &s[..] {
+            "timeout" => return Ok(StatusPattern::Timeout),
+            "dns" => return Ok(StatusPattern::Dns),
+            "connect" => return Ok(StatusPattern::Connect),
+            _ => {}
+        }
+        let err = || {
+            format!(
+                "invalid status '{}', expected a status code like '404' or '5xx', \
+                 'timeout', 'dns', or 'connect'",
+                s
+            )
+        };
+        if let Some(class) = s.strip_suffix("xx") {
+            match class.parse() {
+                Ok(class) if (1..=5).contains(&class) => Ok(StatusPattern::Class(class)),
+                _ => Err(err()),
+            }
+        } else {
+            match s.parse() {
+                Ok(code) if (100..600).contains(&code) => Ok(StatusPattern::Code(code)),
+                _ => Err(err()),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use super::*;
+    use crate::link::ResponseInfo;
+
+    #[test]
+    fn parse() {
+        assert_eq!("404".parse(), Ok(StatusPattern::Code(404)));
+        assert_eq!("5XX".parse(), Ok(StatusPattern::Class(5)));
+        assert_eq!(" timeout ".parse(), Ok(StatusPattern::Timeout));
+        assert_eq!("dns".parse(), Ok(StatusPattern::Dns));
+        assert_eq!("connect".parse(), Ok(StatusPattern::Connect));
+        assert!("600".parse::<StatusPattern>().is_err());
+        assert!("6xx".parse::<StatusPattern>().is_err());
+        assert!("teapot".parse::<StatusPattern>().is_err());
+    }
+
+    fn link(status: Option<u16>, request_error: Option<RequestError>) -> Link {
+        let mut link = Link::new(PathBuf::from("README.md"), 1, String::from("https://a.b"));
+        link.response = status.map(|status| ResponseInfo {
+            status,
+            location: None,
+            last_modified: None,
+            etag: None,
+            latency: None,
+        });
+        link.request_error = request_error;
+        link
+    }
+
+    #[test]
+    fn matches_bad_links() {
+        let bad = LinkStatus::Unreachable(None);
+        let not_found = link(Some(404), None);
+        assert!(StatusPattern::Code(404).is_match(&not_found, &bad));
+        assert!(StatusPattern::Class(4).is_match(&not_found, &bad));
+        assert!(!StatusPattern::Class(5).is_match(&not_found, &bad));
+        let timed_out = link(None, Some(RequestError::Timeout));
+        assert!(StatusPattern::Timeout.is_match(&timed_out, &bad));
+        assert!(!StatusPattern::Dns.is_match(&timed_out, &bad));
+    }
+
+    #[test]
+    fn only_matches_bad_links() {
+        let not_found = link(Some(404), None);
+        assert!(!StatusPattern::Code(404).is_match(&not_found, &LinkStatus::Reachable));
+    }
+}
@@ -0,0 +1,62 @@
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::github::repo_root;
+use crate::link::normalize_path;
+
+/// The git submodules of the repository we're in, as listed in its `.gitmodules`.
+pub struct Submodules {
+    /// The absolute paths of the submodules.
+    paths: Vec<PathBuf>,
+    /// The absolute path of the current directory, to resolve relative paths against.
+    cwd: PathBuf,
+}
+
+impl Submodules {
+    /// Find the submodules of the repository we're in, if we're in one that has any.
+    pub fn load() -> Option<Self> {
+        let root = repo_root()?;
+        let contents = fs::read_to_string(root.join(".gitmodules")).ok()?;
+        let paths: Vec<PathBuf> = contents
+            .lines()
+            .filter_map(|line| {
+                let (key, value) = line.split_once('=')?;
+                if key.trim() == "path" {
+                    Some(normalize_path(&root.join(value.trim())))
+                } else {
+                    None
+                }
+            })
+            .collect();
+        if paths.is_empty() {
+            return None;
+        }
+        let cwd = env::current_dir().ok()?.canonicalize().ok()?;
+        Some(Submodules { paths, cwd })
+    }
+
+    /// Get the submodule a path is in, or is, if any.
+    pub fn containing(&self, path: &Path) -> Option<&Path> {
+        let path = normalize_path(&self.cwd.join(path));
+        self.paths
+            .iter()
+            .find(|submodule| path.starts_with(submodule))
+            .map(PathBuf::as_path)
+    }
+
+    /// Check if a submodule has been checked out. Uninitialized submodules are left as
+    /// empty directories, if they're there at all.
+    pub fn is_initialized(submodule: &Path) -> bool {
+        submodule.join(".git").exists()
+    }
+
+    /// Get a submodule's path relative to the current directory, for messages.
+    pub fn display(&self, submodule: &Path) -> String {
+        submodule
+            .strip_prefix(&self.cwd)
+            .unwrap_or(submodule)
+            .display()
+            .to_string()
+    }
+}
@@ -0,0 +1,217 @@
+use std::fmt;
+use std::path::Path;
+use std::str::FromStr;
+
+use regex::Regex;
+
+/// Where a link was found and what it's for, to audit some kinds of links on their own.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Tag {
+    /// In a documentation comment in source code, like API reference docs.
+    DocComment,
+    /// In a README.
+    Readme,
+    /// The source of an image.
+    Image,
+    /// An image of a status badge, like the ones from shields.io.
+    Badge,
+    /// The target of a footnote, like `[^1]: https://example.com`.
+    Footnote,
+}
+
+impl Tag {
+    pub fn variants() -> [&'static str; 5] {
+        ["doc-comment", "readme", "image", "badge", "footnote"]
+    }
+
+    /// Find the tags for a link from the file and line it's on. `start` is where the
+    /// whole match for the link starts, and `url` is the link as it was found. Whether
+    /// it's in a doc comment is up to whatever found it.
+    pub fn of(path: &Path, line: &str, start: usize, url: &str) -> Vec<Tag> {
+        lazy_static! {
+            static ref FOOTNOTE_RE: Regex = Regex::new(r"^\s*\[\^[^\]]+\]:").unwrap();
+            static ref BADGE_RE: Regex = Regex::new(
+                r"(?i)^https?://(img\.shields\.io|badgen\.net|badge\.fury\.io|codecov\.io/.*/badge|coveralls\.io/repos/.*/badge|[^/]+/.*/badge\.svg|[^/]+/.*/badges?/)"
+            )
+            .unwrap();
+        }
+        let mut tags = vec![];
+        if path
+            .file_stem()
+            .and_then(|stem| stem.to_str())
+            .is_some_and(|stem| stem.eq_ignore_ascii_case("readme"))
+        {
+            tags.push(Tag::Readme);
+        }
+        let before = &line[..start];
+        let rest = &line[start..];
+        let is_image = before.ends_with('!')
+            || rest
+                .get(..4)
+                .is_some_and(|tag| tag.eq_ignore_ascii_case("<img"));
+        if is_image {
+            tags.push(Tag::Image);
+            // Badges are usually images that link to whatever they're about, like
+            // `[![Build Status](...)](...)`.
+            if before.ends_with("[!") || BADGE_RE.is_match(url) {
+                tags.push(Tag::Badge);
+            }
+        }
+        if FOOTNOTE_RE.is_match(line) {
+            tags.push(Tag::Footnote);
+        }
+        tags
+    }
+}
+
+impl FromStr for Tag {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "doc-comment" => Ok(Tag::DocComment),
+            "readme" => Ok(Tag::Readme),
+            "image" => Ok(Tag::Image),
+            "badge" => Ok(Tag::Badge),
+            "footnote" => Ok(Tag::Footnote),
+            _ => Err(format!("unknown tag '{}'", s)),
+        }
+    }
+}
+
+impl fmt::Display for Tag {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Tag::DocComment => "doc-comment",
+            Tag::Readme => "readme",
+            Tag::Image => "image",
+            Tag::Badge => "badge",
+            Tag::Footnote => "footnote",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// Keeps track of the heading that each line of a Markdown file is under, so links can
+/// be reported along with the section they're in. Headings in code blocks don't count.
+#[derive(Default)]
+pub struct Headings {
+    in_code_block: bool,
+    current: Option<String>,
+}
+
+impl Headings {
+    /// Start keeping track of the headings in a file, if it's Markdown.
+    pub fn of(path: &Path) -> Option<Self> {
+        if path
+            .extension()
+            .is_some_and(|ext| ext == "md" || ext == "mdx")
+        {
+            Some(Headings::default())
+        } else {
+            None
+        }
+    }
+
+    /// Get the heading the lines so far are under.
+    pub fn current(&self) -> Option<&str> {
+        self.current.as_deref()
+    }
+
+    /// Move on past a line, which may be a heading.
+    pub fn line(&mut self, line: &str) {
+        lazy_static! {
+            static ref HEADING_RE: Regex = Regex::new(r"^#{1,6}\s+(.*?)[\s#]*$").unwrap();
+        }
+        if line.trim_start().starts_with("```") || line.trim_start().starts_with("~~~") {
+            self.in_code_block = !self.in_code_block;
+        } else if !self.in_code_block {
+            if let Some(caps) = HEADING_RE.captures(line) {
+                self.current = Some(caps[1].to_string());
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tags_of(path: &str, line: &str, url: &str) -> Vec<Tag> {
+        Tag::of(Path::new(path), line, line.find(url).unwrap(), url)
+    }
+
+    #[test]
+    fn links_in_readmes_are_tagged() {
+        let url = "https://a.io";
+        assert_eq!(
+            tags_of("docs/README.md", "see https://a.io", url),
+            vec![Tag::Readme]
+        );
+        assert_eq!(
+            tags_of("readme.rst", "see https://a.io", url),
+            vec![Tag::Readme]
+        );
+        assert!(tags_of("docs/guide.md", "see https://a.io", url).is_empty());
+    }
+
+    #[test]
+    fn images_and_badges_are_tagged() {
+        let url = "https://a.io/logo.png";
+        let line = "![logo](https://a.io/logo.png)";
+        assert_eq!(Tag::of(Path::new("a.md"), line, 1, url), vec![Tag::Image]);
+        let line = "<img src=\"https://a.io/logo.png\">";
+        assert_eq!(Tag::of(Path::new("a.md"), line, 0, url), vec![Tag::Image]);
+        let url = "https://img.shields.io/crates/v/x.svg";
+        let line = "![version](https://img.shields.io/crates/v/x.svg)";
+        assert_eq!(
+            Tag::of(Path::new("a.md"), line, 1, url),
+            vec![Tag::Image, Tag::Badge]
+        );
+        let url = "https://a.io/build.png";
+        let line = "[![build](https://a.io/build.png)](https://a.io/ci)";
+        assert_eq!(
+            Tag::of(Path::new("a.md"), line, 2, url),
+            vec![Tag::Image, Tag::Badge]
+        );
+    }
+
+    #[test]
+    fn footnote_targets_are_tagged() {
+        let line = "[^1]: https://a.io";
+        assert_eq!(
+            Tag::of(Path::new("a.md"), line, 6, "https://a.io"),
+            vec![Tag::Footnote]
+        );
+    }
+
+    #[test]
+    fn tags_round_trip_through_their_names() {
+        for name in Tag::variants().iter() {
+            assert_eq!(name.parse::<Tag>().unwrap().to_string(), *name);
+        }
+        assert!("badges".parse::<Tag>().is_err());
+    }
+
+    #[test]
+    fn headings_are_only_tracked_in_markdown() {
+        assert!(Headings::of(Path::new("a.md")).is_some());
+        assert!(Headings::of(Path::new("a.mdx")).is_some());
+        assert!(Headings::of(Path::new("a.rst")).is_none());
+    }
+
+    #[test]
+    fn lines_are_under_the_last_heading() {
+        let mut headings = Headings::default();
+        assert_eq!(headings.current(), None);
+        headings.line("## Getting started ##");
+        assert_eq!(headings.current(), Some("Getting started"));
+        headings.line("```sh");
+        headings.line("# not a heading");
+        headings.line("```");
+        assert_eq!(headings.current(), Some("Getting started"));
+        headings.line("#hashtag");
+        headings.line("### Install");
+        assert_eq!(headings.current(), Some("Install"));
+    }
+}
@@ -0,0 +1,102 @@
+use std::env;
+
+use isahc::http::Request;
+use isahc::AsyncReadResponseExt;
+use regex::Regex;
+use serde_json::Value;
+use url::Url;
+
+use crate::checker::Checker;
+use crate::config::Config;
+use crate::github::Target;
+use crate::link::{Link, LinkStatus, Reason};
+
+/// Check what happened to the issue or pull request a link points to, returning a
+/// warning if it was closed as not planned, or closed without being merged. Docs that
+/// say something is "tracked in #123" shouldn't point to an issue nobody's working on.
+pub async fn check(link: &Link, checker: &Checker, config: &Config) -> Option<LinkStatus> {
+    let reason = match Target::parse(&link.raw) {
+        Some(target @ Target::Issue { .. }) => github(target, checker).await?,
+        Some(_) => return None,
+        None => gitlab(&link.raw, checker, config).await?,
+    };
+    Some(LinkStatus::Questionable(Reason::Other(reason)))
+}
+
+async fn github(target: Target, checker: &Checker) -> Option<String> {
+    let number = match &target {
+        Target::Issue { number, .. } => *number,
+        _ => return None,
+    };
+    let node = checker.github.as_ref()?.lookup(target).await?;
+    match node["__typename"].as_str()? {
+        "Issue" if node["stateReason"] == "NOT_PLANNED" => {
+            Some(format!("issue #{} was closed as not planned", number))
+        }
+        "PullRequest" if node["state"] == "CLOSED" && node["merged"] == false => Some(format!(
+            "pull request #{} was closed without being merged",
+            number
+        )),
+        _ => None,
+    }
+}
+
+/// GitLab doesn't record why an issue was closed, so only merge requests are checked.
+/// Any host with GitLab's URLs works, so self-hosted instances are covered too.
+async fn gitlab(url: &str, checker: &Checker, config: &Config) -> Option<String> {
+    lazy_static! {
+        static ref MERGE_REQUEST_RE: Regex =
+            Regex::new(r"^(https?)://([^/]+)/(.+?)/-/merge_requests/(\d+)/?(?:[?#].*)?$").unwrap();
+    }
+    let caps = MERGE_REQUEST_RE.captures(url)?;
+    let api_url = format!(
+        "{}://{}/api/v4/projects/{}/merge_requests/{}",
+        &caps[1],
+        &caps[2],
+        caps[3].replace('/', "%2F"),
+        &caps[4]
+    );
+    let mut request = Request::get(api_url);
+    // The token is only for the GitLab we work with, and isn't sent in the clear.
+    if &caps[1] == "https" && is_gitlab_host(&caps[2], config) {
+        if let Ok(token) = env::var("GITLAB_TOKEN") {
+            request = request.header("PRIVATE-TOKEN", token);
+        }
+    }
+    let permit = checker.requests.acquire().await;
+    let mut response = checker
+        .http_client
+        .send_async(request.body(()).ok()?)
+        .await
+        .ok()?;
+    let text = response.text().await.ok()?;
+    drop(permit);
+    if !response.status().is_success() {
+        return None;
+    }
+    let merge_request: Value = serde_json::from_str(&text).ok()?;
+    if merge_request["state"] == "closed" {
+        Some(format!(
+            "merge request !{} was closed without being merged",
+            &caps[4]
+        ))
+    } else {
+        None
+    }
+}
+
+/// Check if a host is gitlab.com, the GitLab instance CI is running on, or one that's
+/// configured.
+fn is_gitlab_host(host: &str, config: &Config) -> bool {
+    let host = host.to_ascii_lowercase();
+    let ci_host = env::var("CI_SERVER_URL")
+        .ok()
+        .and_then(|url| Url::parse(&url).ok())
+        .and_then(|url| url.host_str().map(str::to_ascii_lowercase));
+    host == "gitlab.com"
+        || ci_host.as_deref() == Some(&host[..])
+        || config
+            .gitlab_hosts
+            .iter()
+            .any(|gitlab| gitlab.eq_ignore_ascii_case(&host))
+}
@@ -0,0 +1,679 @@
+use std::collections::BTreeMap;
+use std::io::{self, IsTerminal, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+use colored::*;
+use failure::{format_err, Error};
+use tokio::sync::mpsc::{unbounded_channel, Receiver, UnboundedReceiver, UnboundedSender};
+
+use crate::checker::Checker;
+use crate::config::Config;
+use crate::editor::Editor;
+use crate::link::{Link, LinkKind, LinkStatus, Reason};
+use crate::log::{Marker, Style};
+use crate::maybe_pluralize;
+
+/// How often to redraw while links are coming in.
+const REDRAW_INTERVAL: Duration = Duration::from_millis(50);
+
+/// The most failing hosts to list.
+const MAX_HOSTS: usize = 5;
+
+enum Key {
+    Up,
+    Down,
+    Retry,
+    Open,
+    Quit,
+}
+
+/// A link that's broken or has a warning.
+struct Failure {
+    file: PathBuf,
+    lnum: usize,
+    col: Option<usize>,
+    url: String,
+    host: Option<String>,
+    /// How the link is described in the log.
+    subject: String,
+    reason: String,
+    warning: bool,
+}
+
+/// What the dashboard ended up showing, once it's closed.
+pub struct Outcome {
+    pub errors: u32,
+    pub warnings: u32,
+    pub skipped: u32,
+    pub ignored: u32,
+    /// Log entries for the links that are still broken, and for those with warnings.
+    pub broken: Vec<String>,
+    pub warned: Vec<String>,
+}
+
+/// A line on the screen.
+struct Line {
+    text: String,
+    color: Option<&'static str>,
+    bold: bool,
+}
+
+impl Line {
+    fn plain(text: String) -> Self {
+        Line {
+            text,
+            color: None,
+            bold: false,
+        }
+    }
+}
+
+/// A live view of a run in the terminal: how many files and links have been found, how
+/// the links have fared, which hosts are failing, and a list of the bad links that can
+/// be checked again or opened in an editor.
+pub struct Dashboard {
+    checker: Arc<Checker>,
+    roots: Vec<(PathBuf, Arc<Config>)>,
+    editor: Editor,
+    style: Style,
+    color: bool,
+    files: usize,
+    found: usize,
+    ok: u32,
+    warnings: u32,
+    errors: u32,
+    skipped: u32,
+    ignored: u32,
+    failures: Vec<Failure>,
+    selected: usize,
+    /// What the last action did, shown in place of the key bindings.
+    message: Option<String>,
+    done: bool,
+    quit: bool,
+    last_draw: Option<Instant>,
+    keys: UnboundedReceiver<Key>,
+    /// Tells the thread reading keys to leave the terminal alone, while an editor is open.
+    paused: Arc<AtomicBool>,
+    /// Tells the thread reading keys to stop.
+    stopped: Arc<AtomicBool>,
+    reader: Option<JoinHandle<()>>,
+    terminal: term::RawMode,
+}
+
+impl Dashboard {
+    /// Take over the terminal.
+    pub fn start(
+        checker: Arc<Checker>,
+        roots: Vec<(PathBuf, Arc<Config>)>,
+        editor: Editor,
+        style: Style,
+        color: bool,
+    ) -> Result<Self, Error> {
+        if !io::stdin().is_terminal() || !io::stdout().is_terminal() {
+            return Err(format_err!(
+                "--tui needs a terminal for both stdin and stdout"
+            ));
+        }
+        let terminal = term::RawMode::enable()?;
+        let (tx, keys) = unbounded_channel();
+        let paused = Arc::new(AtomicBool::new(false));
+        let stopped = Arc::new(AtomicBool::new(false));
+        let reader = {
+            let paused = paused.clone();
+            let stopped = stopped.clone();
+            thread::spawn(move || read_keys(tx, paused, stopped))
+        };
+        let mut dashboard = Dashboard {
+            checker,
+            roots,
+            editor,
+            style,
+            color,
+            files: 0,
+            found: 0,
+            ok: 0,
+            warnings: 0,
+            errors: 0,
+            skipped: 0,
+            ignored: 0,
+            failures: vec![],
+            selected: 0,
+            message: None,
+            done: false,
+            quit: false,
+            last_draw: None,
+            keys,
+            paused,
+            stopped,
+            reader: Some(reader),
+            terminal,
+        };
+        dashboard.enter_screen();
+        dashboard.draw(true);
+        Ok(dashboard)
+    }
+
+    pub fn file_scanned(&mut self) {
+        self.files += 1;
+        self.draw(false);
+    }
+
+    pub fn link_found(&mut self) {
+        self.found += 1;
+        self.draw(false);
+    }
+
+    pub fn link_checked(&mut self, link: &Link) {
+        if let Some(failure) = self.record(link) {
+            self.failures.push(failure);
+        }
+        self.draw(false);
+    }
+
+    /// Wait for the next link to be checked, handling keys in the meantime. Returns
+    /// `None` once all the links are in. If the user quits early, the dashboard closes
+    /// but the rest of the links are still waited on, so they make it into the results.
+    pub async fn next_link(&mut self, rx: &mut Receiver<Link>) -> Option<Link> {
+        if self.quit {
+            return rx.recv().await;
+        }
+        loop {
+            tokio::select! {
+                link = rx.recv() => return link,
+                key = self.keys.recv() => match key {
+                    Some(Key::Quit) => {
+                        self.close();
+                        return rx.recv().await;
+                    }
+                    Some(key) => self.handle(key).await,
+                    // We can't read keys any more, so just wait on the links.
+                    None => return rx.recv().await,
+                },
+            }
+        }
+    }
+
+    /// Show the final results until the user quits, then give the terminal back.
+    pub async fn finish(mut self) -> Outcome {
+        self.done = true;
+        if !self.quit {
+            self.draw(true);
+            while let Some(key) = self.keys.recv().await {
+                match key {
+                    Key::Quit => break,
+                    key => self.handle(key).await,
+                }
+            }
+        }
+        let entries = |warning: bool, marker: Marker| -> Vec<String> {
+            self.failures
+                .iter()
+                .filter(|failure| failure.warning == warning)
+                .map(|failure| {
+                    self.style
+                        .entry(marker, &failure.subject, &[&failure.reason[..]])
+                })
+                .collect()
+        };
+        Outcome {
+            errors: self.errors,
+            warnings: self.warnings,
+            skipped: self.skipped,
+            ignored: self.ignored,
+            broken: entries(false, Marker::Bad),
+            warned: entries(true, Marker::Warning),
+        }
+    }
+
+    /// Give the terminal back, once the user is done with the dashboard.
+    fn close(&mut self) {
+        if self.quit {
+            return;
+        }
+        self.quit = true;
+        self.stopped.store(true, Ordering::SeqCst);
+        if let Some(reader) = self.reader.take() {
+            // The thread only waits on the terminal for a moment at a time, so it sees
+            // that it's been stopped soon enough.
+            let _ = reader.join();
+        }
+        self.leave_screen();
+        self.terminal.suspend();
+    }
+
+    /// Count a link's result, returning it as a failure if it's bad.
+    fn record(&mut self, link: &Link) -> Option<Failure> {
+        let (reason, warning) = match link.status.as_ref()? {
+            LinkStatus::Reachable => {
+                self.ok += 1;
+                return None;
+            }
+            LinkStatus::Skipped(_) => {
+                self.skipped += 1;
+                return None;
+            }
+            LinkStatus::Ignored(_) => {
+                self.ignored += 1;
+                return None;
+            }
+            LinkStatus::Questionable(reason) => {
+                self.warnings += 1;
+                (reason.to_string(), true)
+            }
+            LinkStatus::Unreachable(reason) => {
+                self.errors += 1;
+                (
+                    reason
+                        .clone()
+                        .unwrap_or_else(Reason::unreachable)
+                        .to_string(),
+                    false,
+                )
+            }
+        };
+        Some(Failure {
+            file: link.file.clone(),
+            lnum: link.lnum,
+            col: link.col,
+            url: link.raw.clone(),
+            host: match link.kind {
+                LinkKind::Http => link.host(),
+                _ => None,
+            },
+            subject: link.to_string(),
+            reason,
+            warning,
+        })
+    }
+
+    async fn handle(&mut self, key: Key) {
+        match key {
+            Key::Up => self.selected = self.selected.saturating_sub(1),
+            Key::Down => {
+                if self.selected + 1 < self.failures.len() {
+                    self.selected += 1;
+                }
+            }
+            Key::Retry => self.retry().await,
+            Key::Open => self.open(),
+            Key::Quit => {}
+        }
+        self.draw(true);
+    }
+
+    /// Check the selected link again, say after fixing it.
+    async fn retry(&mut self) {
+        let failure = match self.failures.get(self.selected) {
+            Some(failure) => failure,
+            None => return,
+        };
+        let mut link = Link::new(failure.file.clone(), failure.lnum, failure.url.clone());
+        link.col = failure.col;
+        let config = self.config_for(&failure.file);
+        self.message = Some(format!("Checking {} again...", link.raw));
+        self.draw(true);
+        link.verify(self.checker.clone(), config).await;
+        let failure = self.failures.remove(self.selected);
+        if failure.warning {
+            self.warnings -= 1;
+        } else {
+            self.errors -= 1;
+        }
+        match self.record(&link) {
+            Some(failure) => {
+                self.message = Some(format!("Still failing: {}", failure.reason));
+                self.failures.insert(self.selected, failure);
+            }
+            None => {
+                self.message = Some(format!("Fixed: {}", link.raw));
+                self.selected = self.selected.min(self.failures.len().saturating_sub(1));
+            }
+        }
+    }
+
+    /// Open the file the selected link is in at its line, in the user's editor. Editors
+    /// that open a window return right away, while terminal editors take over until
+    /// they're closed.
+    fn open(&mut self) {
+        let (file, lnum, col) = match self.failures.get(self.selected) {
+            Some(failure) => (failure.file.clone(), failure.lnum, failure.col),
+            None => return,
+        };
+        self.paused.store(true, Ordering::SeqCst);
+        self.leave_screen();
+        self.terminal.suspend();
+        let result = self.editor.open(&file, lnum, col);
+        self.terminal.resume();
+        self.enter_screen();
+        self.paused.store(false, Ordering::SeqCst);
+        self.message = Some(match result {
+            Ok(()) => format!("Edited {}, press r to check the link again", file.display()),
+            Err(e) => format!("Couldn't open an editor: {}", e),
+        });
+    }
+
+    /// Get the config for the root a file was found under.
+    fn config_for(&self, file: &Path) -> Arc<Config> {
+        self.roots
+            .iter()
+            .filter(|(root, _)| file.starts_with(root))
+            .max_by_key(|(root, _)| root.components().count())
+            .or_else(|| self.roots.first())
+            .map(|(_, config)| config.clone())
+            .unwrap_or_default()
+    }
+
+    fn enter_screen(&self) {
+        // Switch to the alternate screen, so the terminal's contents come back after, and
+        // hide the cursor.
+        write_terminal("\x1b[?1049h\x1b[?25l");
+    }
+
+    fn leave_screen(&self) {
+        write_terminal("\x1b[?25h\x1b[?1049l");
+    }
+
+    fn draw(&mut self, force: bool) {
+        if self.quit {
+            return;
+        }
+        if !force
+            && self
+                .last_draw
+                .is_some_and(|last| last.elapsed() < REDRAW_INTERVAL)
+        {
+            return;
+        }
+        self.last_draw = Some(Instant::now());
+        let (cols, rows) = term::size();
+        let checked = self.ok + self.warnings + self.errors + self.skipped + self.ignored;
+        let mut lines = vec![
+            Line {
+                text: format!(
+                    "check-links: {} files scanned, {} of {} links checked{}",
+                    self.files,
+                    checked,
+                    self.found,
+                    if self.done { "" } else { "..." }
+                ),
+                color: None,
+                bold: true,
+            },
+            Line::plain(format!(
+                "{} {} ok   {} {} warnings   {} {} errors   {} {} skipped   {} ignored",
+                self.style.marker(Marker::Ok),
+                self.ok,
+                self.style.marker(Marker::Warning),
+                self.warnings,
+                self.style.marker(Marker::Bad),
+                self.errors,
+                self.style.marker(Marker::Passed),
+                self.skipped,
+                self.ignored,
+            )),
+            Line::plain(String::new()),
+        ];
+
+        let mut hosts: BTreeMap<&str, u32> = BTreeMap::new();
+        for failure in self.failures.iter().filter(|failure| !failure.warning) {
+            if let Some(host) = &failure.host {
+                *hosts.entry(host).or_default() += 1;
+            }
+        }
+        if !hosts.is_empty() {
+            let mut hosts: Vec<(&str, u32)> = hosts.into_iter().collect();
+            hosts.sort_by_key(|(_, n)| std::cmp::Reverse(*n));
+            lines.push(Line {
+                text: String::from("Failing hosts"),
+                color: None,
+                bold: true,
+            });
+            for (host, n) in hosts.iter().take(MAX_HOSTS) {
+                lines.push(Line::plain(format!(
+                    "  {} ({} broken link{})",
+                    host,
+                    n,
+                    maybe_pluralize(*n)
+                )));
+            }
+            lines.push(Line::plain(String::new()));
+        }
+
+        lines.push(Line {
+            text: format!("Failures ({})", self.failures.len()),
+            color: None,
+            bold: true,
+        });
+        // Scroll the list so that the selected failure is on screen, leaving a row for
+        // the key bindings.
+        let list_rows = rows.saturating_sub(lines.len() + 1).max(1);
+        let start = (self.selected + 1).saturating_sub(list_rows);
+        for (i, failure) in self.failures.iter().enumerate().skip(start).take(list_rows) {
+            let marker = if failure.warning {
+                Marker::Warning
+            } else {
+                Marker::Bad
+            };
+            lines.push(Line {
+                text: format!(
+                    "{} {} {}:{}  {}  {}",
+                    if i == self.selected { ">" } else { " " },
+                    self.style.marker(marker),
+                    failure.file.display(),
+                    failure.lnum,
+                    failure.url,
+                    failure.reason
+                ),
+                color: Some(if failure.warning { "yellow" } else { "red" }),
+                bold: i == self.selected,
+            });
+        }
+        while lines.len() < rows.saturating_sub(1) {
+            lines.push(Line::plain(String::new()));
+        }
+        let help = if self.done {
+            "Done. [up/down] select  [r] check again  [enter] open in editor  [q] quit"
+        } else {
+            "[up/down] select  [r] check again  [enter] open in editor  [q] close"
+        };
+        lines.push(Line {
+            text: self.message.clone().unwrap_or_else(|| String::from(help)),
+            color: Some("cyan"),
+            bold: false,
+        });
+
+        let mut screen = String::from("\x1b[H\x1b[2J");
+        for (i, line) in lines.iter().enumerate() {
+            if i > 0 {
+                screen.push('\n');
+            }
+            let text: String = line.text.chars().take(cols).collect();
+            match (self.color, line.color, line.bold) {
+                (true, Some(color), true) => screen.push_str(&text.color(color).bold().to_string()),
+                (true, Some(color), false) => screen.push_str(&text.color(color).to_string()),
+                (true, None, true) => screen.push_str(&text.bold().to_string()),
+                _ => screen.push_str(&text),
+            }
+        }
+        write_terminal(&screen);
+    }
+}
+
+impl Drop for Dashboard {
+    fn drop(&mut self) {
+        self.close();
+    }
+}
+
+fn write_terminal(s: &str) {
+    let mut stdout = io::stdout();
+    // There's nothing useful to do if the terminal can't be written to.
+    let _ = stdout.write_all(s.as_bytes()).and_then(|_| stdout.flush());
+}
+
+/// Read keys from the terminal until the dashboard goes away.
+fn read_keys(tx: UnboundedSender<Key>, paused: Arc<AtomicBool>, stopped: Arc<AtomicBool>) {
+    let mut escape = vec![];
+    loop {
+        if stopped.load(Ordering::SeqCst) {
+            return;
+        }
+        if paused.load(Ordering::SeqCst) {
+            thread::sleep(REDRAW_INTERVAL);
+            continue;
+        }
+        if !term::poll_stdin(REDRAW_INTERVAL) {
+            continue;
+        }
+        let byte = match term::read_byte() {
+            Some(byte) => byte,
+            None => return,
+        };
+        // Arrow keys come as escape sequences like `ESC [ A`.
+        if byte == 0x1b || !escape.is_empty() {
+            escape.push(byte);
+            if escape.len() < 3 {
+                continue;
+            }
+        }
+        let key = match (&escape[..], byte) {
+            ([0x1b, b'[', b'A'], _) | (_, b'k') => Some(Key::Up),
+            ([0x1b, b'[', b'B'], _) | (_, b'j') => Some(Key::Down),
+            ([], b'r') => Some(Key::Retry),
+            ([], b'o') | ([], b'\r') | ([], b'\n') => Some(Key::Open),
+            // Ctrl-C doesn't send a signal in raw mode.
+            ([], b'q') | ([], 3) => Some(Key::Quit),
+            _ => None,
+        };
+        escape.clear();
+        if let Some(key) = key {
+            if tx.send(key).is_err() {
+                return;
+            }
+        }
+    }
+}
+
+#[cfg(unix)]
+mod term {
+    use std::io;
+    use std::mem;
+    use std::time::Duration;
+
+    /// Puts the terminal in raw mode, so keys are read as they're pressed, until it's
+    /// dropped.
+    pub struct RawMode {
+        original: libc::termios,
+        raw: libc::termios,
+    }
+
+    impl RawMode {
+        pub fn enable() -> io::Result<Self> {
+            unsafe {
+                let mut original: libc::termios = mem::zeroed();
+                if libc::tcgetattr(libc::STDIN_FILENO, &mut original) != 0 {
+                    return Err(io::Error::last_os_error());
+                }
+                let mut raw = original;
+                libc::cfmakeraw(&mut raw);
+                // Keep turning newlines into carriage returns and newlines on output.
+                raw.c_oflag |= libc::OPOST;
+                let mode = RawMode { original, raw };
+                mode.resume();
+                Ok(mode)
+            }
+        }
+
+        /// Give the terminal back for a while, like when an editor is open.
+        pub fn suspend(&self) {
+            unsafe {
+                libc::tcsetattr(libc::STDIN_FILENO, libc::TCSANOW, &self.original);
+            }
+        }
+
+        pub fn resume(&self) {
+            unsafe {
+                libc::tcsetattr(libc::STDIN_FILENO, libc::TCSANOW, &self.raw);
+            }
+        }
+    }
+
+    impl Drop for RawMode {
+        fn drop(&mut self) {
+            self.suspend();
+        }
+    }
+
+    /// Get the number of columns and rows in the terminal.
+    pub fn size() -> (usize, usize) {
+        unsafe {
+            let mut size: libc::winsize = mem::zeroed();
+            if libc::ioctl(libc::STDOUT_FILENO, libc::TIOCGWINSZ, &mut size) == 0
+                && size.ws_col > 0
+                && size.ws_row > 0
+            {
+                (usize::from(size.ws_col), usize::from(size.ws_row))
+            } else {
+                (80, 24)
+            }
+        }
+    }
+
+    /// Wait a while for a key to be pressed.
+    pub fn poll_stdin(timeout: Duration) -> bool {
+        let mut fd = libc::pollfd {
+            fd: libc::STDIN_FILENO,
+            events: libc::POLLIN,
+            revents: 0,
+        };
+        unsafe { libc::poll(&mut fd, 1, timeout.as_millis() as libc::c_int) > 0 }
+    }
+
+    /// Read a byte from stdin, bypassing the buffering of `io::stdin` so that `poll_stdin`
+    /// sees everything that hasn't been read.
+    pub fn read_byte() -> Option<u8> {
+        let mut byte = 0u8;
+        let n = unsafe { libc::read(libc::STDIN_FILENO, (&mut byte as *mut u8).cast(), 1) };
+        if n == 1 {
+            Some(byte)
+        } else {
+            None
+        }
+    }
+}
+
+#[cfg(not(unix))]
+mod term {
+    use std::io;
+    use std::time::Duration;
+
+    pub struct RawMode;
+
+    impl RawMode {
+        pub fn enable() -> io::Result<Self> {
+            Err(io::Error::other(
+                "--tui is only supported in Unix terminals",
+            ))
+        }
+
+        pub fn suspend(&self) {}
+
+        pub fn resume(&self) {}
+    }
+
+    pub fn size() -> (usize, usize) {
+        (80, 24)
+    }
+
+    pub fn poll_stdin(_timeout: Duration) -> bool {
+        false
+    }
+
+    pub fn read_byte() -> Option<u8> {
+        None
+    }
+}
@@ -0,0 +1,370 @@
+use std::collections::HashSet;
+use std::fmt;
+use std::ops::Range;
+
+use globset::Glob;
+use isahc::http::{HeaderName, HeaderValue};
+use regex::Regex;
+use serde::Serialize;
+use toml_edit::{ImDocument, Item};
+
+use crate::config::{Config, Latency};
+use crate::ignores::Date;
+
+/// A problem found in a config file.
+#[derive(Serialize)]
+pub struct Diagnostic {
+    pub severity: DiagnosticSeverity,
+    /// The 1-based line and column of the problem, when we know where it is.
+    pub line: Option<usize>,
+    pub column: Option<usize>,
+    pub message: String,
+}
+
+#[derive(Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DiagnosticSeverity {
+    Error,
+    Warning,
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let (Some(line), Some(column)) = (self.line, self.column) {
+            write!(f, "{}:{}: ", line, column)?;
+        }
+        let severity = match self.severity {
+            DiagnosticSeverity::Error => "error",
+            DiagnosticSeverity::Warning => "warning",
+        };
+        write!(f, "{}: {}", severity, self.message)
+    }
+}
+
+/// Checks a config file, keeping track of the problems found along with where they are.
+struct Validator<'a> {
+    contents: &'a str,
+    diagnostics: Vec<Diagnostic>,
+}
+
+impl<'a> Validator<'a> {
+    fn report(
+        &mut self,
+        severity: DiagnosticSeverity,
+        span: Option<Range<usize>>,
+        message: String,
+    ) {
+        let (line, column) = match span {
+            Some(span) => {
+                let before = &self.contents[..span.start];
+                let line = before.matches('\n').count() + 1;
+                let column = match before.rfind('\n') {
+                    Some(i) => span.start - i,
+                    None => span.start + 1,
+                };
+                (Some(line), Some(column))
+            }
+            None => (None, None),
+        };
+        self.diagnostics.push(Diagnostic {
+            severity,
+            line,
+            column,
+            message,
+        });
+    }
+
+    fn error(&mut self, span: Option<Range<usize>>, message: String) {
+        self.report(DiagnosticSeverity::Error, span, message);
+    }
+
+    fn warning(&mut self, span: Option<Range<usize>>, message: String) {
+        self.report(DiagnosticSeverity::Warning, span, message);
+    }
+
+    fn check_regex(&mut self, item: Option<&Item>, name: &str) {
+        if let Some(item) = item {
+            if let Some(pattern) = item.as_str() {
+                if let Err(e) = Regex::new(pattern) {
+                    // Regex errors span several lines to point at the problem.
+                    let e = e.to_string();
+                    let e = e
+                        .lines()
+                        .last()
+                        .unwrap_or_default()
+                        .trim_start_matches("error: ");
+                    self.error(item.span(), format!("invalid regex for '{}': {}", name, e));
+                }
+            }
+        }
+    }
+
+    /// Check the parts of the config that serde can't, like whether patterns are valid
+    /// and whether rules conflict.
+    fn check_rules(&mut self, doc: &ImDocument<&str>) {
+        let root = doc.as_item();
+
+        if let Some(exclude) = root.get("exclude").and_then(Item::as_array) {
+            let mut seen = HashSet::new();
+            for glob in exclude.iter() {
+                if let Some(s) = glob.as_str() {
+                    if let Err(e) = Glob::new(s) {
+                        self.error(glob.span(), format!("invalid glob in 'exclude': {}", e));
+                    } else if !seen.insert(s) {
+                        self.warning(glob.span(), format!("'{}' is excluded more than once", s));
+                    }
+                }
+            }
+        }
+
+        if let Some(forbid) = root.get("forbid").and_then(Item::as_array) {
+            for glob in forbid.iter() {
+                if let Some(s) = glob.as_str() {
+                    if let Err(e) = Glob::new(s) {
+                        self.error(glob.span(), format!("invalid glob in 'forbid': {}", e));
+                    } else if s.contains("://") {
+                        self.warning(
+                            glob.span(),
+                            format!("'{}' has a scheme, but 'forbid' globs match hosts and paths without one", s),
+                        );
+                    }
+                }
+            }
+        }
+
+        if let Some(assertions) = root.get("assert").and_then(Item::as_array_of_tables) {
+            let mut seen = HashSet::new();
+            for assertion in assertions.iter() {
+                let url = assertion.get("url");
+                self.check_regex(url, "url");
+                self.check_regex(assertion.get("matches"), "matches");
+                if !assertion.contains_key("contains") && !assertion.contains_key("matches") {
+                    self.warning(
+                        assertion.span(),
+                        String::from(
+                            "assertion has neither 'contains' nor 'matches' so it has no effect",
+                        ),
+                    );
+                }
+                if let Some(url) = url.and_then(Item::as_str) {
+                    if !seen.insert(url) {
+                        self.warning(
+                            assertion.get("url").and_then(Item::span),
+                            format!("more than one assertion for URLs matching '{}', all of them must pass", url),
+                        );
+                    }
+                }
+            }
+        }
+
+        if let Some(rules) = root.get("accept").and_then(Item::as_array_of_tables) {
+            for (i, rule) in rules.iter().enumerate() {
+                self.check_regex(rule.get("url"), "url");
+                if let Some(value) = rule.get("value") {
+                    if let Some(s) = value.as_str() {
+                        if HeaderValue::from_str(s).is_err() {
+                            self.error(value.span(), format!("invalid Accept header '{}'", s));
+                        }
+                    }
+                }
+                // Only the first matching rule applies, so a catch-all hides the rest.
+                if rule
+                    .get("url")
+                    .and_then(Item::as_str)
+                    .is_some_and(|url| matches!(url, "" | "." | ".*" | "^" | "^.*"))
+                    && i + 1 < rules.len()
+                {
+                    self.warning(
+                        rule.get("url").and_then(Item::span),
+                        String::from(
+                            "this rule matches every URL, so the rules after it never apply",
+                        ),
+                    );
+                }
+            }
+        }
+
+        if let Some(rules) = root.get("ignore").and_then(Item::as_array_of_tables) {
+            for rule in rules.iter() {
+                self.check_regex(rule.get("url"), "url");
+                if let Some(until) = rule.get("until") {
+                    if let Some(s) = until.as_str() {
+                        if let Err(e) = s.parse::<Date>() {
+                            self.error(until.span(), e);
+                        }
+                    }
+                } else {
+                    self.warning(
+                        rule.span(),
+                        String::from("ignore has no 'until' date, so it never expires"),
+                    );
+                }
+            }
+        }
+
+        if let Some(rules) = root.get("header").and_then(Item::as_array_of_tables) {
+            for rule in rules.iter() {
+                self.check_regex(rule.get("url"), "url");
+                self.check_regex(rule.get("matches"), "matches");
+                if let Some(name) = rule.get("name") {
+                    if let Some(s) = name.as_str() {
+                        if HeaderName::from_bytes(s.as_bytes()).is_err() {
+                            self.error(name.span(), format!("invalid header name '{}'", s));
+                        }
+                    }
+                }
+                if rule.get("absent").and_then(Item::as_bool) == Some(true)
+                    && rule.contains_key("matches")
+                {
+                    self.warning(
+                        rule.get("matches").and_then(Item::span),
+                        String::from("'matches' has no effect when the header must be absent"),
+                    );
+                }
+            }
+        }
+
+        if let Some(rules) = root.get("rewrite").and_then(Item::as_array_of_tables) {
+            lazy_static! {
+                // A numbered group followed by more of a name, like `$1x`.
+                static ref GROUP_NAME_RE: Regex = Regex::new(r"\$(\d+)([A-Za-z_]\w*)").unwrap();
+            }
+            for rule in rules.iter() {
+                self.check_regex(rule.get("url"), "url");
+                if let Some(replacement) = rule.get("replacement").and_then(Item::as_str) {
+                    // `$1x` is taken to mean a group named `1x`, which is rarely what's meant.
+                    if let Some(caps) = GROUP_NAME_RE.captures(replacement) {
+                        self.warning(
+                            rule.get("replacement").and_then(Item::span),
+                            format!(
+                                "'{}' refers to a group named '{}{}', use '${{{}}}{}' for group {} followed by text",
+                                &caps[0], &caps[1], &caps[2], &caps[1], &caps[2], &caps[1]
+                            ),
+                        );
+                    }
+                }
+            }
+        }
+
+        if let Some(slas) = root.get("sla").and_then(Item::as_table_like) {
+            for (host, sla) in slas.iter() {
+                if host.contains("://") || host.contains('/') {
+                    self.warning(
+                        sla.span(),
+                        format!("'{}' isn't a hostname, so this SLA never applies", host),
+                    );
+                }
+                if let Some(latency) = sla.get("max_latency") {
+                    if let Some(s) = latency.as_str() {
+                        if let Err(e) = s.parse::<Latency>() {
+                            self.error(latency.span(), e);
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// A step along the path to a key in a config file.
+enum Segment {
+    Key(String),
+    Index(usize),
+}
+
+fn segments(path: &serde_ignored::Path, out: &mut Vec<Segment>) {
+    match path {
+        serde_ignored::Path::Root => {}
+        serde_ignored::Path::Seq { parent, index } => {
+            segments(parent, out);
+            out.push(Segment::Index(*index));
+        }
+        serde_ignored::Path::Map { parent, key } => {
+            segments(parent, out);
+            out.push(Segment::Key(key.clone()));
+        }
+        serde_ignored::Path::Some { parent }
+        | serde_ignored::Path::NewtypeStruct { parent }
+        | serde_ignored::Path::NewtypeVariant { parent } => segments(parent, out),
+    }
+}
+
+/// Find where a key is in the config file.
+fn key_span(doc: &ImDocument<&str>, path: &[Segment]) -> Option<Range<usize>> {
+    let mut item = doc.as_item();
+    for (i, segment) in path.iter().enumerate() {
+        match segment {
+            Segment::Key(key) => {
+                let (k, next) = item.as_table_like()?.get_key_value(key)?;
+                if i == path.len() - 1 {
+                    return k.span();
+                }
+                item = next;
+            }
+            Segment::Index(index) => item = item.get(*index)?,
+        }
+    }
+    item.span()
+}
+
+/// Validate a config file, returning the parsed config if it's usable along with any
+/// problems found.
+pub fn validate(contents: &str) -> (Option<Config>, Vec<Diagnostic>) {
+    let mut validator = Validator {
+        contents,
+        diagnostics: vec![],
+    };
+
+    let doc = match ImDocument::parse(contents) {
+        Ok(doc) => doc,
+        Err(e) => {
+            validator.error(e.span(), e.message().to_string());
+            return (None, validator.diagnostics);
+        }
+    };
+
+    validator.check_rules(&doc);
+
+    let mut unknown = vec![];
+    let result: Result<Config, _> =
+        serde_ignored::deserialize(toml::Deserializer::new(contents), |path| {
+            let mut path_segments = vec![];
+            segments(&path, &mut path_segments);
+            unknown.push((path.to_string(), path_segments));
+        });
+    for (name, path) in unknown {
+        let span = key_span(&doc, &path);
+        validator.error(span, format!("unknown key '{}'", name));
+    }
+    let config = match result {
+        Ok(config) => Some(config),
+        Err(e) => {
+            // Invalid patterns are also caught by serde, but we've already reported them.
+            let n_errors = validator.diagnostics.len();
+            validator.error(e.span(), e.message().to_string());
+            let duplicate = match validator.diagnostics.last() {
+                Some(last) => validator.diagnostics[..n_errors]
+                    .iter()
+                    .any(|d| d.line.is_some() && d.line == last.line),
+                None => false,
+            };
+            if duplicate {
+                validator.diagnostics.pop();
+            }
+            None
+        }
+    };
+
+    validator
+        .diagnostics
+        .sort_by_key(|d| (d.line.is_none(), d.line, d.column));
+    let has_errors = validator
+        .diagnostics
+        .iter()
+        .any(|d| d.severity == DiagnosticSeverity::Error);
+    if has_errors {
+        (None, validator.diagnostics)
+    } else {
+        (config, validator.diagnostics)
+    }
+}